@@ -27,3 +27,341 @@ CREATE TABLE portfolios (
 );
 
 CREATE INDEX idx_portfolios_user ON portfolios(user_id); 
+
+-- cash on hold for open buy orders, available cash is balance - reserved
+ALTER TABLE accounts ADD COLUMN reserved BIGINT NOT NULL DEFAULT 0;
+
+-- one position per user and product, concurrent first buys cannot create duplicates
+CREATE UNIQUE INDEX idx_portfolios_user_product ON portfolios(user_id, product_id);
+
+-- order lifecycle, orders placed before this were filled on insert
+ALTER TABLE orders ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'FILLED';
+ALTER TABLE orders ADD COLUMN filled_lot INT NOT NULL DEFAULT 0;
+UPDATE orders SET filled_lot = lot;
+ALTER TABLE orders ALTER COLUMN status SET DEFAULT 'NEW';
+CREATE INDEX idx_orders_user_status ON orders(user_id, status);
+
+-- lots on hold for open sell orders
+ALTER TABLE portfolios ADD COLUMN reserved_lot INT NOT NULL DEFAULT 0;
+
+CREATE TABLE trades (
+  trade_id SERIAL PRIMARY KEY,
+  order_id INT NOT NULL,
+  user_id INT NOT NULL,
+  product_id INT NOT NULL,
+  side VARCHAR(1) NOT NULL,
+  price BIGINT NOT NULL,
+  lot INT NOT NULL,
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (order_id) REFERENCES orders(order_id),
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (product_id) REFERENCES products(product_id)
+);
+
+CREATE INDEX idx_trades_order ON trades(order_id);
+
+-- market orders, avg_price is the average execution price
+ALTER TABLE orders ADD COLUMN order_type VARCHAR(10) NOT NULL DEFAULT 'LIMIT';
+ALTER TABLE orders ADD COLUMN avg_price NUMERIC(20, 5);
+UPDATE orders SET avg_price = price WHERE filled_lot > 0;
+
+-- working order scans at startup and at market close
+CREATE INDEX idx_orders_status ON orders(status);
+
+-- previous close, ARA/ARB bands are computed from it
+ALTER TABLE products ADD COLUMN reference_price INT;
+
+-- stop orders and the orders they release
+ALTER TABLE orders ADD COLUMN stop_price INT;
+ALTER TABLE orders ADD COLUMN parent_order_id INT REFERENCES orders(order_id);
+
+-- trailing stops keep their mark so a restart does not reset the trail
+ALTER TABLE orders ALTER COLUMN order_type TYPE VARCHAR(20);
+ALTER TABLE orders ADD COLUMN trail_amount INT;
+ALTER TABLE orders ADD COLUMN trail_percent NUMERIC(5,2);
+ALTER TABLE orders ADD COLUMN high_water_mark INT;
+ALTER TABLE orders ADD COLUMN triggered_price INT;
+
+-- OCO and bracket groups hang off their first order through parent_order_id
+ALTER TABLE orders ADD COLUMN link_type VARCHAR(10) NOT NULL DEFAULT 'SINGLE';
+CREATE INDEX idx_orders_parent ON orders(parent_order_id);
+
+-- algo orders worked over a time window through child orders
+CREATE TABLE algo_orders (
+  algo_id SERIAL PRIMARY KEY,
+  user_id INT NOT NULL,
+  product_id INT NOT NULL,
+  product_symbol VARCHAR(10) NOT NULL,
+  side VARCHAR(1) NOT NULL,
+  strategy VARCHAR(10) NOT NULL,
+  lot INT NOT NULL,
+  price INT,
+  start_at TIMESTAMPTZ NOT NULL,
+  end_at TIMESTAMPTZ NOT NULL,
+  status VARCHAR(20) NOT NULL,
+  filled_lot INT NOT NULL DEFAULT 0,
+  avg_price NUMERIC(20, 5),
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (product_id) REFERENCES products(product_id)
+);
+
+CREATE INDEX idx_algo_orders_status ON algo_orders(status);
+
+ALTER TABLE orders ADD COLUMN algo_id INT REFERENCES algo_orders(algo_id);
+CREATE INDEX idx_orders_algo ON orders(algo_id);
+
+-- fees charged per fill, and their sum per order, in rupiah
+ALTER TABLE trades
+    ADD COLUMN commission BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN levy BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN vat BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN tax BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE orders
+    ADD COLUMN commission BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN levy BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN vat BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN tax BIGINT NOT NULL DEFAULT 0;
+
+-- T+2 settlement: what each trade still owes in cash and lots until its settlement date
+CREATE TABLE settlements (
+  settlement_id SERIAL PRIMARY KEY,
+  trade_id INT NOT NULL,
+  user_id INT NOT NULL,
+  product_id INT NOT NULL,
+  cash BIGINT NOT NULL,
+  lot INT NOT NULL,
+  trade_date DATE NOT NULL,
+  settle_date DATE NOT NULL,
+  status VARCHAR(10) NOT NULL DEFAULT 'PENDING',
+  settled_at TIMESTAMPTZ,
+  FOREIGN KEY (trade_id) REFERENCES trades(trade_id),
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (product_id) REFERENCES products(product_id)
+);
+
+CREATE INDEX idx_settlements_due ON settlements(status, settle_date);
+CREATE INDEX idx_settlements_user ON settlements(user_id, product_id);
+
+-- lots held per buy, sells close them oldest first
+CREATE TABLE position_lots (
+  lot_id SERIAL PRIMARY KEY,
+  trade_id INT,
+  user_id INT NOT NULL,
+  product_id INT NOT NULL,
+  lot INT NOT NULL,
+  cost BIGINT NOT NULL,
+  FOREIGN KEY (trade_id) REFERENCES trades(trade_id),
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (product_id) REFERENCES products(product_id)
+);
+
+CREATE INDEX idx_position_lots_user ON position_lots(user_id, product_id);
+
+-- positions held before lots were tracked open as a single lot at their cost
+INSERT INTO position_lots (user_id, product_id, lot, cost)
+SELECT user_id, product_id, lot, invested_value FROM portfolios WHERE lot > 0;
+
+-- gain or loss of every sell fill
+CREATE TABLE realized_pnl (
+  pnl_id SERIAL PRIMARY KEY,
+  trade_id INT NOT NULL,
+  user_id INT NOT NULL,
+  product_id INT NOT NULL,
+  product_symbol VARCHAR(10) NOT NULL,
+  lot INT NOT NULL,
+  proceeds BIGINT NOT NULL,
+  cost_basis BIGINT NOT NULL,
+  realized BIGINT NOT NULL,
+  method VARCHAR(10) NOT NULL,
+  trade_date DATE NOT NULL,
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (trade_id) REFERENCES trades(trade_id),
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (product_id) REFERENCES products(product_id)
+);
+
+CREATE INDEX idx_realized_pnl_user ON realized_pnl(user_id, trade_date);
+
+-- corporate actions, loaded from config/corporate_actions.toml
+CREATE TABLE corporate_actions (
+  action_id SERIAL PRIMARY KEY,
+  product_id INT NOT NULL,
+  product_symbol VARCHAR(10) NOT NULL,
+  kind VARCHAR(20) NOT NULL,
+  ex_date DATE NOT NULL,
+  pay_date DATE,
+  ratio_from INT,
+  ratio_to INT,
+  amount NUMERIC(20, 5),
+  status VARCHAR(10) NOT NULL DEFAULT 'ANNOUNCED',
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (product_id, kind, ex_date),
+  FOREIGN KEY (product_id) REFERENCES products(product_id)
+);
+
+CREATE INDEX idx_corporate_actions_status ON corporate_actions(status);
+
+-- dividends owed to the holders on the ex-date, paid on the pay date
+CREATE TABLE dividend_entitlements (
+  entitlement_id SERIAL PRIMARY KEY,
+  action_id INT NOT NULL,
+  user_id INT NOT NULL,
+  shares BIGINT NOT NULL,
+  gross BIGINT NOT NULL,
+  tax BIGINT NOT NULL,
+  net BIGINT NOT NULL,
+  status VARCHAR(10) NOT NULL DEFAULT 'PENDING',
+  paid_at TIMESTAMPTZ,
+  FOREIGN KEY (action_id) REFERENCES corporate_actions(action_id),
+  FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx_dividend_entitlements_action ON dividend_entitlements(action_id);
+
+-- every change a corporate action made to a position, order or account
+CREATE TABLE corporate_action_log (
+  log_id SERIAL PRIMARY KEY,
+  action_id INT NOT NULL,
+  user_id INT NOT NULL,
+  order_id INT,
+  target VARCHAR(20) NOT NULL,
+  lot_before INT,
+  lot_after INT,
+  price_before NUMERIC(20, 5),
+  price_after NUMERIC(20, 5),
+  cash BIGINT NOT NULL DEFAULT 0,
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (action_id) REFERENCES corporate_actions(action_id),
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (order_id) REFERENCES orders(order_id)
+);
+
+CREATE INDEX idx_corporate_action_log_action ON corporate_action_log(action_id);
+
+-- quantities in shares instead of 100-share lots, and the board every order trades on
+ALTER TABLE orders RENAME COLUMN lot TO quantity;
+ALTER TABLE orders RENAME COLUMN filled_lot TO filled_quantity;
+UPDATE orders SET quantity = quantity * 100, filled_quantity = filled_quantity * 100;
+ALTER TABLE orders ADD COLUMN board VARCHAR(12) NOT NULL DEFAULT 'REGULAR';
+
+ALTER TABLE trades RENAME COLUMN lot TO quantity;
+UPDATE trades SET quantity = quantity * 100;
+
+ALTER TABLE portfolios RENAME COLUMN lot TO shares;
+ALTER TABLE portfolios RENAME COLUMN reserved_lot TO reserved_shares;
+UPDATE portfolios SET shares = shares * 100, reserved_shares = reserved_shares * 100;
+
+ALTER TABLE algo_orders RENAME COLUMN lot TO quantity;
+ALTER TABLE algo_orders RENAME COLUMN filled_lot TO filled_quantity;
+UPDATE algo_orders SET quantity = quantity * 100, filled_quantity = filled_quantity * 100;
+
+ALTER TABLE settlements RENAME COLUMN lot TO shares;
+UPDATE settlements SET shares = shares * 100;
+
+ALTER TABLE position_lots RENAME COLUMN lot TO shares;
+UPDATE position_lots SET shares = shares * 100;
+
+ALTER TABLE realized_pnl RENAME COLUMN lot TO shares;
+UPDATE realized_pnl SET shares = shares * 100;
+
+ALTER TABLE corporate_action_log RENAME COLUMN lot_before TO quantity_before;
+ALTER TABLE corporate_action_log RENAME COLUMN lot_after TO quantity_after;
+UPDATE corporate_action_log
+SET quantity_before = quantity_before * 100, quantity_after = quantity_after * 100;
+
+-- prices are whole rupiah everywhere, matching the INT the service reads them as
+ALTER TABLE orders ALTER COLUMN price TYPE INT USING ROUND(price)::integer;
+ALTER TABLE trades ALTER COLUMN price TYPE INT;
+ALTER TABLE dividend_entitlements ALTER COLUMN shares TYPE INT;
+
+-- append-only cash ledger, accounts.balance is the sum of its cash legs
+CREATE TABLE journal_entries (
+  entry_id SERIAL PRIMARY KEY,
+  account_id INT NOT NULL,
+  kind VARCHAR(20) NOT NULL,
+  reference_id INT,
+  balance_after BIGINT NOT NULL,
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (account_id) REFERENCES accounts(account_id)
+);
+
+CREATE INDEX idx_journal_entries_account ON journal_entries(account_id, entry_id);
+
+CREATE TABLE journal_legs (
+  leg_id SERIAL PRIMARY KEY,
+  entry_id INT NOT NULL,
+  book VARCHAR(12) NOT NULL,
+  debit BIGINT NOT NULL DEFAULT 0,
+  credit BIGINT NOT NULL DEFAULT 0,
+  CHECK (debit >= 0 AND credit >= 0 AND (debit = 0) <> (credit = 0)),
+  FOREIGN KEY (entry_id) REFERENCES journal_entries(entry_id)
+);
+
+CREATE INDEX idx_journal_legs_entry ON journal_legs(entry_id);
+
+-- balances from before the ledger open it, against the user's bank
+INSERT INTO journal_entries (account_id, kind, balance_after)
+SELECT account_id, 'OPENING_BALANCE', balance FROM accounts WHERE balance <> 0;
+
+INSERT INTO journal_legs (entry_id, book, debit, credit)
+SELECT entry_id, 'CASH', GREATEST(balance_after, 0), GREATEST(-balance_after, 0)
+FROM journal_entries WHERE kind = 'OPENING_BALANCE'
+UNION ALL
+SELECT entry_id, 'BANK', GREATEST(-balance_after, 0), GREATEST(balance_after, 0)
+FROM journal_entries WHERE kind = 'OPENING_BALANCE';
+
+-- deposits and withdrawals, the balance moves once the payment gateway completes them
+CREATE TABLE cash_requests (
+  request_id SERIAL PRIMARY KEY,
+  user_id INT NOT NULL,
+  account_id INT NOT NULL,
+  kind VARCHAR(10) NOT NULL,
+  amount BIGINT NOT NULL CHECK (amount > 0),
+  status VARCHAR(10) NOT NULL DEFAULT 'PENDING',
+  gateway_ref VARCHAR(40),
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (user_id) REFERENCES users(user_id),
+  FOREIGN KEY (account_id) REFERENCES accounts(account_id)
+);
+
+CREATE INDEX idx_cash_requests_user ON cash_requests(user_id);
+
+-- every state change of a cash request and who made it
+CREATE TABLE cash_request_log (
+  log_id SERIAL PRIMARY KEY,
+  request_id INT NOT NULL,
+  from_status VARCHAR(10),
+  to_status VARCHAR(10) NOT NULL,
+  actor VARCHAR(40) NOT NULL,
+  note TEXT,
+  created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (request_id) REFERENCES cash_requests(request_id)
+);
+
+CREATE INDEX idx_cash_request_log_request ON cash_request_log(request_id);
+
+-- a position held from before its trades were recorded opens a lot without a trade, which
+-- keeps what the position stood at when it opened for reconciliation to replay from.
+-- Sells and splits move the lot's shares and cost, never these. Opening lots from before
+-- take the position as it stands now, the trades it has seen included.
+ALTER TABLE position_lots
+  ADD COLUMN opened_shares INT,
+  ADD COLUMN opened_cost BIGINT,
+  ADD COLUMN opened_avg_price NUMERIC(20, 5),
+  ADD COLUMN opened_at TIMESTAMPTZ;
+
+UPDATE position_lots l
+SET opened_shares = p.shares, opened_cost = p.invested_value, opened_avg_price = p.avg_price,
+  opened_at = NOW()
+FROM portfolios p
+WHERE l.trade_id IS NULL AND p.user_id = l.user_id AND p.product_id = l.product_id;
+
+-- a position's average price is what it cost per share, fees included like its invested value
+UPDATE portfolios SET avg_price = ROUND(invested_value::NUMERIC / shares, 5) WHERE shares > 0;
+
+UPDATE position_lots
+SET opened_avg_price = ROUND(opened_cost::NUMERIC / opened_shares, 5)
+WHERE trade_id IS NULL AND opened_shares > 0;
@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+use crate::types::Money;
+
 #[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct Account {
     pub account_id: Option<i32>,
     pub user_id: i32,
-    pub balance: i64,
-    pub invested_value: i64,
+    pub balance: Money,
+    pub invested_value: Money,
 }
 
 impl Account {
@@ -13,8 +15,8 @@ impl Account {
         Self {
             account_id: None,
             user_id,
-            balance: 0,
-            invested_value: 0,
+            balance: Money::ZERO,
+            invested_value: Money::ZERO,
         }
     }
 }
@@ -22,22 +24,51 @@ impl Account {
 #[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
 pub struct GetAccount {
     pub account_id: i32,
-    pub balance: i64,
-    pub invested_value: i64,
+    pub balance: Money,
+    pub invested_value: Money,
+    pub reserved: Money,
 }
 
 impl GetAccount {
-    pub fn new(balance: i64, invested_value: i64, account_id: i32) -> Self {
+    pub fn new(balance: Money, invested_value: Money, reserved: Money, account_id: i32) -> Self {
         Self {
             account_id,
             balance,
             invested_value,
+            reserved,
         }
     }
+
+    /// Cash that is not on hold for open orders
+    pub fn available(&self) -> Money {
+        self.balance - self.reserved
+    }
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
 pub struct GetAccountDTO {
-    pub balance: i64,
-    pub invested_value: i64,
+    /// Trade-date balance, every trade counted as soon as it is done
+    pub balance: Money,
+    /// Balance without the trades that have not settled yet
+    pub settled_balance: Money,
+    pub invested_value: Money,
+    pub available: Money,
+    pub reserved: Money,
+    /// Settled cash that no open order or unsettled buy needs
+    pub withdrawable: Money,
+}
+
+impl GetAccountDTO {
+    /// `pending_cash` is the net cash of the unsettled trades
+    pub fn new(account: GetAccount, pending_cash: Money) -> Self {
+        let settled_balance = account.balance - pending_cash;
+        Self {
+            balance: account.balance,
+            settled_balance,
+            invested_value: account.invested_value,
+            available: account.available(),
+            reserved: account.reserved,
+            withdrawable: settled_balance.min(account.available()).max(Money::ZERO),
+        }
+    }
 }
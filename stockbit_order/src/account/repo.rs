@@ -1,7 +1,8 @@
 use anyhow::Result;
-use sqlx::Postgres;
+use sqlx::{Postgres, Transaction};
 
 use super::model::GetAccount;
+use crate::types::Money;
 
 #[derive(Clone)]
 pub struct AccountRepo {
@@ -15,26 +16,90 @@ impl AccountRepo {
 
     pub async fn get_account_by_user_id(&self, user_id: i32) -> Result<GetAccount, sqlx::Error> {
         sqlx::query_as::<_, GetAccount>(
-            r#"SELECT balance, invested_value, account_id FROM accounts WHERE user_id = $1"#,
+            r#"SELECT balance, invested_value, reserved, account_id FROM accounts WHERE user_id = $1"#,
         )
         .bind(user_id)
         .fetch_one(&self.pool)
         .await
     }
 
-    pub async fn update_account(&self, account: &GetAccount) -> Result<i32, sqlx::Error> {
-        let row: (i32,) = sqlx::query_as(
-            r#"
-            UPDATE accounts
-            SET balance = $1, invested_value = $2 
-            WHERE account_id = $3
-            RETURNING account_id"#,
+    pub async fn get_user_ids(&self) -> Result<Vec<i32>, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT user_id FROM accounts ORDER BY user_id"#)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Reads the account and holds its row lock until the transaction ends,
+    /// so orders of the same user are applied one after another
+    pub async fn lock_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+    ) -> Result<GetAccount, sqlx::Error> {
+        sqlx::query_as::<_, GetAccount>(
+            r#"SELECT balance, invested_value, reserved, account_id FROM accounts
+            WHERE user_id = $1 FOR UPDATE"#,
         )
-        .bind(&account.balance)
-        .bind(&account.invested_value)
-        .bind(&account.account_id)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .fetch_one(&mut **tx)
+        .await
+    }
+
+    /// Moves the balance by the cash of a journal entry and returns the new balance.
+    /// Only posting to the ledger calls this, so the balance is the sum of the ledger.
+    pub async fn post_cash(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        account_id: i32,
+        cash: Money,
+    ) -> Result<Money, sqlx::Error> {
+        let row: (Money,) = sqlx::query_as(
+            r#"UPDATE accounts SET balance = balance + $1 WHERE account_id = $2
+            RETURNING balance"#,
+        )
+        .bind(cash)
+        .bind(account_id)
+        .fetch_one(&mut **tx)
         .await?;
         Ok(row.0)
     }
+
+    pub async fn adjust_invested(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        account_id: i32,
+        delta: Money,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE accounts SET invested_value = invested_value + $1 WHERE account_id = $2"#,
+        )
+        .bind(delta)
+        .bind(account_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn reserve(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        account_id: i32,
+        amount: Money,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE accounts SET reserved = reserved + $1 WHERE account_id = $2"#)
+            .bind(amount)
+            .bind(account_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn release(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        account_id: i32,
+        amount: Money,
+    ) -> Result<(), sqlx::Error> {
+        self.reserve(tx, account_id, -amount).await
+    }
 }
@@ -0,0 +1,280 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::{OrderError, Rejection};
+use crate::order::model::{Board, Order, OrderForm, OrderType, Side};
+use crate::product::model::Product;
+use crate::types::{Price, Quantity};
+
+/// Parent order the algo engine works through child orders over a time window
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug, Clone)]
+pub struct AlgoOrder {
+    pub algo_id: Option<i32>,
+    pub user_id: i32,
+    pub product_id: i32,
+    pub product_symbol: String,
+    #[sqlx(try_from = "String")]
+    pub side: Side,
+    #[sqlx(try_from = "String")]
+    pub strategy: AlgoStrategy,
+    /// Shares to work, whole lots as the children trade on the regular board
+    pub quantity: Quantity,
+    /// Limit price of every child order, none sends market children
+    pub price: Option<Price>,
+    pub start_at: DateTime<Utc>,
+    pub end_at: DateTime<Utc>,
+    #[sqlx(try_from = "String")]
+    pub status: AlgoStatus,
+    /// Shares filled over all children, as of the last time the algo was worked
+    pub filled_quantity: Quantity,
+    pub avg_price: Option<Decimal>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AlgoOrder {
+    pub fn new(
+        algo_params: &AlgoParams,
+        user_id: i32,
+        product: &Product,
+        start_at: DateTime<Utc>,
+        end_at: DateTime<Utc>,
+    ) -> Result<AlgoOrder, OrderError> {
+        if algo_params.quantity <= Quantity::ZERO || !algo_params.quantity.is_round_lot() {
+            return Err(OrderError::InvalidAlgo("need a quantity in whole lots"));
+        }
+        if end_at <= start_at {
+            return Err(OrderError::InvalidAlgo(
+                "must end after they start, within today's trading day",
+            ));
+        }
+        Ok(Self {
+            algo_id: None,
+            user_id,
+            product_id: product.product_id,
+            product_symbol: product.symbol.clone(),
+            side: algo_params.side.try_into()?,
+            strategy: algo_params.strategy,
+            quantity: algo_params.quantity,
+            price: algo_params.price,
+            start_at,
+            end_at,
+            status: AlgoStatus::Running,
+            filled_quantity: Quantity::ZERO,
+            avg_price: None,
+            created_at: Utc::now(),
+        })
+    }
+
+    /// Form of a child order for `quantity` shares on the regular board, good for the
+    /// day like the algo itself
+    pub fn child_form(&self, quantity: Quantity) -> OrderForm {
+        OrderForm {
+            symbol: self.product_symbol.clone(),
+            side: match self.side {
+                Side::Buy => 'B',
+                Side::Sell => 'S',
+            },
+            price: self.price.unwrap_or_default(),
+            quantity,
+            expiry: String::from("GFD"),
+            expiry_date: None,
+            order_type: match self.price {
+                Some(_) => OrderType::Limit,
+                None => OrderType::Market,
+            },
+            stop_price: None,
+            trail_amount: None,
+            trail_percent: None,
+            board: Board::Regular,
+        }
+    }
+
+    /// Moves the algo to `next`, refusing transitions its lifecycle does not allow
+    pub fn transition(&mut self, next: AlgoStatus) -> Result<(), OrderError> {
+        if !self.status.can_transition_to(next) {
+            return Err(OrderError::InvalidAlgoTransition(self.status, next));
+        }
+        self.status = next;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlgoStrategy {
+    /// Even slices over the window
+    Twap,
+    /// Slices following the intraday volume profile
+    Vwap,
+}
+
+impl TryFrom<String> for AlgoStrategy {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "TWAP" => Ok(AlgoStrategy::Twap),
+            "VWAP" => Ok(AlgoStrategy::Vwap),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for AlgoStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlgoStrategy::Twap => write!(f, "TWAP"),
+            AlgoStrategy::Vwap => write!(f, "VWAP"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlgoStatus {
+    Running,
+    /// Sends no new slices, children already working stay on the book
+    Paused,
+    Completed,
+    Cancelled,
+    /// Window ended before the whole quantity filled
+    Expired,
+}
+
+impl AlgoStatus {
+    /// Algos the engine still looks at
+    pub fn is_active(&self) -> bool {
+        matches!(self, AlgoStatus::Running | AlgoStatus::Paused)
+    }
+
+    pub fn can_transition_to(&self, next: AlgoStatus) -> bool {
+        use AlgoStatus::*;
+        matches!(
+            (self, next),
+            (Running, Paused | Completed | Cancelled | Expired)
+                | (Paused, Running | Completed | Cancelled | Expired)
+        )
+    }
+}
+
+impl TryFrom<String> for AlgoStatus {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "RUNNING" => Ok(AlgoStatus::Running),
+            "PAUSED" => Ok(AlgoStatus::Paused),
+            "COMPLETED" => Ok(AlgoStatus::Completed),
+            "CANCELLED" => Ok(AlgoStatus::Cancelled),
+            "EXPIRED" => Ok(AlgoStatus::Expired),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for AlgoStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlgoStatus::Running => write!(f, "RUNNING"),
+            AlgoStatus::Paused => write!(f, "PAUSED"),
+            AlgoStatus::Completed => write!(f, "COMPLETED"),
+            AlgoStatus::Cancelled => write!(f, "CANCELLED"),
+            AlgoStatus::Expired => write!(f, "EXPIRED"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AlgoForm {
+    pub algo: AlgoParams,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AlgoParams {
+    pub symbol: String,
+    pub side: char,
+    /// Shares, whole lots
+    pub quantity: Quantity,
+    pub strategy: AlgoStrategy,
+    /// Limit price of every child order, market children when missing
+    #[serde(default)]
+    pub price: Option<Price>,
+    /// Exchange time the schedule starts today, right away when missing
+    #[serde(default)]
+    pub start_time: Option<NaiveTime>,
+    /// Exchange time the schedule ends today
+    pub end_time: NaiveTime,
+}
+
+/// Pauses, resumes or cancels an algo
+#[derive(Serialize, Deserialize)]
+pub struct AlgoControlForm {
+    pub algo_id: i32,
+    pub action: AlgoAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlgoAction {
+    Pause,
+    /// Continues on schedule, slices missed while paused are caught up
+    Resume,
+    /// Stops the algo and cancels its working children
+    Cancel,
+}
+
+impl AlgoAction {
+    pub fn status(self) -> AlgoStatus {
+        match self {
+            AlgoAction::Pause => AlgoStatus::Paused,
+            AlgoAction::Resume => AlgoStatus::Running,
+            AlgoAction::Cancel => AlgoStatus::Cancelled,
+        }
+    }
+}
+
+/// Pushed to the owner's websocket whenever an algo sends a slice, fills or changes status
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlgoProgress {
+    pub algo_id: i32,
+    pub symbol: String,
+    pub strategy: AlgoStrategy,
+    pub status: AlgoStatus,
+    pub quantity: Quantity,
+    pub filled_quantity: Quantity,
+    /// Shares of children still working
+    pub working_quantity: Quantity,
+    pub avg_price: Option<Decimal>,
+    /// Why the last slice was refused, the algo pauses on it
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rejection: Option<Rejection>,
+}
+
+impl AlgoProgress {
+    pub fn new(algo: &AlgoOrder, children: &[Order]) -> Self {
+        let filled_quantity: Quantity = children.iter().map(|child| child.filled_quantity).sum();
+        let working_quantity = children
+            .iter()
+            .filter(|child| child.status.is_working())
+            .map(Order::remaining_quantity)
+            .sum();
+        let filled_value: Decimal = children
+            .iter()
+            .filter_map(|child| Some(child.avg_price? * Decimal::from(child.filled_quantity)))
+            .sum();
+        Self {
+            algo_id: algo.algo_id.unwrap_or_default(),
+            symbol: algo.product_symbol.clone(),
+            strategy: algo.strategy,
+            status: algo.status,
+            quantity: algo.quantity,
+            filled_quantity,
+            working_quantity,
+            avg_price: (filled_quantity > Quantity::ZERO)
+                .then(|| filled_value / Decimal::from(filled_quantity)),
+            rejection: None,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use sqlx::{Postgres, Transaction};
+
+use super::model::AlgoOrder;
+
+const ALGO_COLUMNS: &str = r#"algo_id, user_id, product_id, product_symbol, side, strategy,
+    quantity, price, start_at, end_at, status, filled_quantity, avg_price, created_at"#;
+
+#[derive(Clone)]
+pub struct AlgoRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl AlgoRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, algo: &AlgoOrder) -> Result<i32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as(
+            r#"INSERT INTO algo_orders (user_id, product_id, product_symbol, side, strategy,
+                quantity, price, start_at, end_at, status, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING algo_id"#,
+        )
+        .bind(algo.user_id)
+        .bind(algo.product_id)
+        .bind(&algo.product_symbol)
+        .bind(algo.side.to_string())
+        .bind(algo.strategy.to_string())
+        .bind(algo.quantity)
+        .bind(algo.price)
+        .bind(algo.start_at)
+        .bind(algo.end_at)
+        .bind(algo.status.to_string())
+        .bind(algo.created_at)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.0)
+    }
+
+    /// Locks the algo row for the rest of the transaction. Children can still be inserted
+    /// meanwhile, their foreign key only needs a key share lock.
+    pub async fn lock_by_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        algo_id: i32,
+    ) -> Result<AlgoOrder, sqlx::Error> {
+        sqlx::query_as::<_, AlgoOrder>(&format!(
+            "SELECT {ALGO_COLUMNS} FROM algo_orders WHERE algo_id = $1 FOR NO KEY UPDATE"
+        ))
+        .bind(algo_id)
+        .fetch_one(&mut **tx)
+        .await
+    }
+
+    /// Running and paused algos, oldest first
+    pub async fn get_active(&self) -> Result<Vec<AlgoOrder>, sqlx::Error> {
+        sqlx::query_as::<_, AlgoOrder>(&format!(
+            "SELECT {ALGO_COLUMNS} FROM algo_orders
+            WHERE status IN ('RUNNING', 'PAUSED') ORDER BY algo_id"
+        ))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        algo: &AlgoOrder,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE algo_orders SET status = $1, filled_quantity = $2, avg_price = $3
+            WHERE algo_id = $4",
+        )
+        .bind(algo.status.to_string())
+        .bind(algo.filled_quantity)
+        .bind(algo.avg_price)
+        .bind(algo.algo_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+}
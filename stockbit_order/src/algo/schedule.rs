@@ -0,0 +1,57 @@
+use chrono::{DateTime, Timelike, Utc};
+
+use super::model::{AlgoOrder, AlgoStrategy};
+use crate::calendar;
+use crate::cfg::{CONFIG, VOLUME_PROFILE};
+use crate::types::{Lot, Quantity};
+
+/// Shares the algo should have sent by the end of the slice running at `at`, in whole
+/// lots. TWAP spreads the quantity evenly over the window, VWAP in proportion to the
+/// volume the profile expects up to the end of the slice. The last slice always completes
+/// the quantity.
+pub fn target_quantity(algo: &AlgoOrder, at: DateTime<Utc>) -> Quantity {
+    let window = (algo.end_at - algo.start_at).num_seconds().max(1);
+    let slice = CONFIG.algo_slice_secs.max(1) as i64;
+    let elapsed = (at - algo.start_at).num_seconds().clamp(0, window);
+    let slice_end = ((elapsed / slice + 1) * slice).min(window);
+
+    let even = slice_end as f64 / window as f64;
+    let share = match algo.strategy {
+        AlgoStrategy::Twap => even,
+        AlgoStrategy::Vwap => {
+            let from = seconds_of_day(algo.start_at);
+            let total = expected_volume(from, from + window);
+            // a window the profile has no volume for is sliced evenly
+            if total > 0.0 {
+                expected_volume(from, from + slice_end) / total
+            } else {
+                even
+            }
+        }
+    };
+    let lots = algo.quantity.lots().get();
+    Lot::new((lots as f64 * share).floor() as i32).shares()
+}
+
+fn seconds_of_day(at: DateTime<Utc>) -> i64 {
+    at.with_timezone(&calendar::exchange_offset())
+        .num_seconds_from_midnight() as i64
+}
+
+/// Volume weight the profile expects between two exchange times, in seconds of the day.
+/// Each bucket's weight spreads evenly over its time.
+fn expected_volume(from: i64, to: i64) -> f64 {
+    VOLUME_PROFILE
+        .buckets
+        .iter()
+        .map(|bucket| {
+            let start = bucket.start.num_seconds_from_midnight() as i64;
+            let end = bucket.end.num_seconds_from_midnight() as i64;
+            let overlap = to.min(end) - from.max(start);
+            if overlap <= 0 || end <= start {
+                return 0.0;
+            }
+            bucket.weight as f64 * overlap as f64 / (end - start) as f64
+        })
+        .sum()
+}
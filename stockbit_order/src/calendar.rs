@@ -0,0 +1,210 @@
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::cfg::{CONFIG, MARKET_CONFIG, Session};
+
+/// IDX trades on Western Indonesia Time, UTC+7 without daylight saving
+const EXCHANGE_UTC_OFFSET_SECS: i32 = 7 * 3600;
+
+/// How far ahead to look for the next trading day, covers the longest holiday break
+const MAX_CLOSED_DAYS: i64 = 30;
+
+pub fn exchange_offset() -> FixedOffset {
+    FixedOffset::east_opt(EXCHANGE_UTC_OFFSET_SECS).expect("valid offset")
+}
+
+/// Current time on the exchange clock
+pub fn now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&exchange_offset())
+}
+
+/// Current trading date on the exchange clock
+pub fn today() -> NaiveDate {
+    now().date_naive()
+}
+
+/// Trading date an instant belongs to
+pub fn market_date(at: DateTime<Utc>) -> NaiveDate {
+    at.with_timezone(&exchange_offset()).date_naive()
+}
+
+/// Trading day an order placed at `at` is worked on: the same day while the market has
+/// not closed yet, otherwise the next trading day
+pub fn trading_date(at: DateTime<Utc>) -> NaiveDate {
+    let at = at.with_timezone(&exchange_offset());
+    let date = at.date_naive();
+    if is_trading_day(date) && at.time() < close_on(date) {
+        return date;
+    }
+    next_trading_day(date)
+}
+
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    MARKET_CONFIG.trading_days.contains(&date.weekday()) && !MARKET_CONFIG.holidays.contains(&date)
+}
+
+/// First trading day strictly after `date`
+pub fn next_trading_day(date: NaiveDate) -> NaiveDate {
+    (1..=MAX_CLOSED_DAYS)
+        .map(|days| date + Duration::days(days))
+        .find(|date| is_trading_day(*date))
+        .expect("no trading day within a month")
+}
+
+/// Day a trade done on `trade_date` settles, `settlement_days` trading days later
+pub fn settlement_date(trade_date: NaiveDate) -> NaiveDate {
+    (0..CONFIG.settlement_days).fold(trade_date, |date, _| next_trading_day(date))
+}
+
+fn sessions_on(date: NaiveDate) -> &'static [Session] {
+    match date.weekday() {
+        Weekday::Fri => &MARKET_CONFIG.friday_sessions,
+        _ => &MARKET_CONFIG.sessions,
+    }
+}
+
+/// Market close of a trading day, the end of its last phase before post-trading. GFD
+/// orders expire here.
+pub fn close_on(date: NaiveDate) -> NaiveTime {
+    sessions_on(date)
+        .iter()
+        .filter(|s| s.phase != MarketPhase::PostTrading)
+        .map(|s| s.end)
+        .max()
+        .unwrap_or(NaiveTime::MIN)
+}
+
+pub fn at_exchange_time(date: NaiveDate, time: NaiveTime) -> DateTime<FixedOffset> {
+    date.and_time(time)
+        .and_local_timezone(exchange_offset())
+        .single()
+        .expect("fixed offset is unambiguous")
+}
+
+/// Market phase at an instant on the exchange clock
+pub fn phase_at(at: DateTime<FixedOffset>) -> MarketPhase {
+    let date = at.date_naive();
+    if !is_trading_day(date) {
+        return MarketPhase::Closed;
+    }
+    let time = at.time();
+    sessions_on(date)
+        .iter()
+        .find(|s| s.start <= time && time < s.end)
+        .map(|s| s.phase)
+        .unwrap_or(MarketPhase::Closed)
+}
+
+pub fn phase() -> MarketPhase {
+    phase_at(now())
+}
+
+/// Start of the first phase `wanted` accepts strictly after `after`
+pub fn next_phase(
+    after: DateTime<FixedOffset>,
+    wanted: impl Fn(MarketPhase) -> bool,
+) -> DateTime<FixedOffset> {
+    let mut date = after.date_naive();
+    for _ in 0..=MAX_CLOSED_DAYS {
+        if is_trading_day(date) {
+            let start = sessions_on(date)
+                .iter()
+                .filter(|s| wanted(s.phase))
+                .map(|s| at_exchange_time(date, s.start))
+                .find(|start| *start > after);
+            if let Some(start) = start {
+                return start;
+            }
+        }
+        date += Duration::days(1);
+    }
+    panic!("no trading session within a month")
+}
+
+/// Start of the next continuous trading session strictly after `after`
+pub fn next_open(after: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    next_phase(after, MarketPhase::is_continuous)
+}
+
+/// Next pre-opening call auction strictly after `after`
+pub fn next_uncross(after: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    let mut date = after.date_naive();
+    loop {
+        let uncross = at_exchange_time(date, MARKET_CONFIG.auction.uncross);
+        if is_trading_day(date) && uncross > after {
+            return uncross;
+        }
+        date = next_trading_day(date);
+    }
+}
+
+/// First market close of a trading day strictly after `after`
+pub fn next_close(after: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    let mut date = after.date_naive();
+    loop {
+        let close = at_exchange_time(date, close_on(date));
+        if is_trading_day(date) && close > after {
+            return close;
+        }
+        date = next_trading_day(date);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MarketPhase {
+    PreOpening,
+    #[serde(rename = "SESSION_1")]
+    Session1,
+    LunchBreak,
+    #[serde(rename = "SESSION_2")]
+    Session2,
+    PreClosing,
+    PostTrading,
+    Closed,
+}
+
+impl MarketPhase {
+    /// Phases where orders are matched as they come in
+    pub fn is_continuous(self) -> bool {
+        matches!(self, MarketPhase::Session1 | MarketPhase::Session2)
+    }
+}
+
+impl fmt::Display for MarketPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            MarketPhase::PreOpening => "PRE_OPENING",
+            MarketPhase::Session1 => "SESSION_1",
+            MarketPhase::LunchBreak => "LUNCH_BREAK",
+            MarketPhase::Session2 => "SESSION_2",
+            MarketPhase::PreClosing => "PRE_CLOSING",
+            MarketPhase::PostTrading => "POST_TRADING",
+            MarketPhase::Closed => "CLOSED",
+        };
+        write!(f, "{}", phase)
+    }
+}
+
+/// Body of `GET /market/status`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MarketStatus {
+    pub phase: MarketPhase,
+    pub trading_day: bool,
+    pub time: DateTime<FixedOffset>,
+    pub next_open: DateTime<FixedOffset>,
+}
+
+impl MarketStatus {
+    pub fn now() -> Self {
+        let now = now();
+        Self {
+            phase: phase_at(now),
+            trading_day: is_trading_day(now.date_naive()),
+            time: now,
+            next_open: next_open(now),
+        }
+    }
+}
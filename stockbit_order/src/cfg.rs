@@ -1,10 +1,88 @@
+use chrono::{NaiveDate, NaiveTime, Weekday};
 use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+
+use crate::calendar::MarketPhase;
+use crate::corporate::model::ActionDefinition;
+use crate::pnl::model::PnlMethod;
+use crate::types::{Lot, Money, Price, Quantity};
 
 #[derive(serde::Deserialize)]
 pub struct AppConfig {
     pub jwt_public_key: String,
     pub database_url: String,
     pub redis_url: String,
+    /// How far from the reference price a market order may execute, in percent
+    #[serde(default = "default_market_protection_pct")]
+    pub market_protection_pct: u32,
+    /// How often stop orders are checked against the `last_price:{symbol}` feed in Redis
+    #[serde(default = "default_price_feed_poll_secs")]
+    pub price_feed_poll_secs: u64,
+    /// Most orders a single basket may carry
+    #[serde(default = "default_basket_max_orders")]
+    pub basket_max_orders: usize,
+    /// Largest HTTP request or websocket frame read, in bytes. The default fits a basket
+    /// of `basket_max_orders` orders.
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+    /// How often algo orders are worked
+    #[serde(default = "default_algo_tick_secs")]
+    pub algo_tick_secs: u64,
+    /// Length of one algo slice, TWAP and VWAP targets step at slice boundaries
+    #[serde(default = "default_algo_slice_secs")]
+    pub algo_slice_secs: u64,
+    /// Trading days between a trade and its settlement
+    #[serde(default = "default_settlement_days")]
+    pub settlement_days: u32,
+    /// Cost method realized P&L is booked with, `AVERAGE` or `FIFO`
+    #[serde(default)]
+    pub pnl_method: PnlMethod,
+    /// Completes approved deposits and withdrawals through the in-process mock payment
+    /// gateway, for development only. A real gateway calls `/gateway/callback` instead.
+    #[serde(default)]
+    pub mock_gateway: bool,
+    /// How long the mock gateway takes before it calls back
+    #[serde(default = "default_mock_gateway_delay_secs")]
+    pub mock_gateway_delay_secs: u64,
+    /// Bearer token the back office reviews cash requests on `/internal/cash/review`
+    /// with. Unset, the route refuses everyone.
+    pub admin_token: Option<String>,
+    /// Key the payment gateway signs `/gateway/callback` bodies with, the hex
+    /// HMAC-SHA256 goes in `X-Signature`. Unset, every callback is refused.
+    pub gateway_secret: Option<String>,
+}
+
+fn default_market_protection_pct() -> u32 {
+    5
+}
+
+fn default_price_feed_poll_secs() -> u64 {
+    2
+}
+
+fn default_basket_max_orders() -> usize {
+    100
+}
+
+fn default_max_request_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_algo_tick_secs() -> u64 {
+    5
+}
+
+fn default_algo_slice_secs() -> u64 {
+    60
+}
+
+fn default_settlement_days() -> u32 {
+    2
+}
+
+fn default_mock_gateway_delay_secs() -> u64 {
+    2
 }
 
 // Initialize config once
@@ -18,3 +96,318 @@ pub static CONFIG: Lazy<AppConfig> = Lazy::new(|| {
         .try_deserialize()
         .expect("env not ready")
 });
+
+/// Exchange rules an order is validated against, loaded from `config/market.toml`
+/// (or the file named by `MARKET_CONFIG`). Anything missing falls back to the IDX defaults.
+#[derive(serde::Deserialize)]
+pub struct MarketConfig {
+    #[serde(default = "default_tick_sizes")]
+    pub tick_sizes: Vec<TickSize>,
+    #[serde(default = "default_price_bands")]
+    pub price_bands: Vec<PriceBand>,
+    #[serde(default)]
+    pub lot: LotRule,
+    /// Lowest price the regular market accepts
+    #[serde(default = "default_min_price")]
+    pub min_price: Price,
+    /// Trading day schedule from Monday to Thursday
+    #[serde(default = "default_sessions")]
+    pub sessions: Vec<Session>,
+    /// Friday schedule with its longer lunch break
+    #[serde(default = "default_friday_sessions")]
+    pub friday_sessions: Vec<Session>,
+    #[serde(default)]
+    pub auction: AuctionConfig,
+    /// Days of the week the exchange trades, Monday to Friday unless set
+    #[serde(default = "default_trading_days")]
+    pub trading_days: Vec<Weekday>,
+    /// Exchange holidays falling on trading days
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+}
+
+/// Phase running from `start` up to, not including, `end` in exchange time
+#[derive(serde::Deserialize)]
+pub struct Session {
+    pub phase: MarketPhase,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// Tick size for prices from `from` up to the next tier
+#[derive(serde::Deserialize)]
+pub struct TickSize {
+    pub from: Price,
+    pub tick: Price,
+}
+
+/// Auto-rejection band for reference prices from `from` up to the next tier, in percent
+#[derive(serde::Deserialize)]
+pub struct PriceBand {
+    pub from: Price,
+    pub ara_pct: u32,
+    pub arb_pct: u32,
+}
+
+/// Pre-opening call auction. Orders collect during the PRE_OPENING session and
+/// trade at a single price at `uncross`.
+#[derive(serde::Deserialize)]
+pub struct AuctionConfig {
+    pub uncross: NaiveTime,
+}
+
+impl Default for AuctionConfig {
+    fn default() -> Self {
+        Self {
+            uncross: NaiveTime::from_hms_opt(8, 59, 0).expect("valid time"),
+        }
+    }
+}
+
+/// Round lot of the regular board, `min` and `max` count lots of `size` shares
+#[derive(serde::Deserialize)]
+pub struct LotRule {
+    #[serde(default = "default_lot_size")]
+    pub size: Quantity,
+    pub min: Lot,
+    pub max: Lot,
+}
+
+impl Default for LotRule {
+    fn default() -> Self {
+        Self {
+            size: default_lot_size(),
+            min: Lot::new(1),
+            max: Lot::new(50_000),
+        }
+    }
+}
+
+fn default_lot_size() -> Quantity {
+    Quantity::new(100)
+}
+
+fn default_tick_sizes() -> Vec<TickSize> {
+    [(0, 1), (200, 2), (500, 5), (2_000, 10), (5_000, 25)]
+        .into_iter()
+        .map(|(from, tick)| TickSize {
+            from: Price::new(from),
+            tick: Price::new(tick),
+        })
+        .collect()
+}
+
+fn default_price_bands() -> Vec<PriceBand> {
+    [(0, 35), (200, 25), (5_000, 20)]
+        .into_iter()
+        .map(|(from, pct)| PriceBand {
+            from: Price::new(from),
+            ara_pct: pct,
+            arb_pct: pct,
+        })
+        .collect()
+}
+
+fn default_min_price() -> Price {
+    Price::new(50)
+}
+
+fn default_trading_days() -> Vec<Weekday> {
+    vec![
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+    ]
+}
+
+fn schedule(phases: [(MarketPhase, &str, &str); 6]) -> Vec<Session> {
+    let time = |t| NaiveTime::parse_from_str(t, "%H:%M").expect("valid time");
+    phases
+        .into_iter()
+        .map(|(phase, start, end)| Session {
+            phase,
+            start: time(start),
+            end: time(end),
+        })
+        .collect()
+}
+
+fn default_sessions() -> Vec<Session> {
+    schedule([
+        (MarketPhase::PreOpening, "08:45", "09:00"),
+        (MarketPhase::Session1, "09:00", "12:00"),
+        (MarketPhase::LunchBreak, "12:00", "13:30"),
+        (MarketPhase::Session2, "13:30", "15:50"),
+        (MarketPhase::PreClosing, "15:50", "16:01"),
+        (MarketPhase::PostTrading, "16:01", "16:15"),
+    ])
+}
+
+fn default_friday_sessions() -> Vec<Session> {
+    schedule([
+        (MarketPhase::PreOpening, "08:45", "09:00"),
+        (MarketPhase::Session1, "09:00", "11:30"),
+        (MarketPhase::LunchBreak, "11:30", "14:00"),
+        (MarketPhase::Session2, "14:00", "15:50"),
+        (MarketPhase::PreClosing, "15:50", "16:01"),
+        (MarketPhase::PostTrading, "16:01", "16:15"),
+    ])
+}
+
+pub static MARKET_CONFIG: Lazy<MarketConfig> = Lazy::new(|| {
+    dotenvy::dotenv().ok();
+    let path = std::env::var("MARKET_CONFIG").unwrap_or_else(|_| "config/market".to_string());
+
+    let mut market: MarketConfig = config::Config::builder()
+        .add_source(config::File::with_name(&path).required(false))
+        .build()
+        .expect("market config unreadable")
+        .try_deserialize()
+        .expect("market config invalid");
+    // tiers are looked up from the top down
+    market.tick_sizes.sort_by_key(|t| t.from);
+    market.price_bands.sort_by_key(|b| b.from);
+    market.sessions.sort_by_key(|s| s.start);
+    market.friday_sessions.sort_by_key(|s| s.start);
+    market
+});
+
+/// Intraday volume curve VWAP algos follow, loaded from `config/volume_profile.toml`
+/// (or the file named by `VOLUME_PROFILE`)
+#[derive(serde::Deserialize)]
+pub struct VolumeProfile {
+    #[serde(default = "default_volume_buckets")]
+    pub buckets: Vec<VolumeBucket>,
+}
+
+/// Share of the day's volume traded from `start` up to `end` in exchange time, relative
+/// to the other buckets
+#[derive(serde::Deserialize)]
+pub struct VolumeBucket {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub weight: u32,
+}
+
+fn default_volume_buckets() -> Vec<VolumeBucket> {
+    let time = |t| NaiveTime::parse_from_str(t, "%H:%M").expect("valid time");
+    [
+        ("09:00", "09:30", 14),
+        ("09:30", "10:30", 16),
+        ("10:30", "12:00", 17),
+        ("13:30", "14:30", 13),
+        ("14:30", "15:30", 18),
+        ("15:30", "15:50", 22),
+    ]
+    .into_iter()
+    .map(|(start, end, weight)| VolumeBucket {
+        start: time(start),
+        end: time(end),
+        weight,
+    })
+    .collect()
+}
+
+pub static VOLUME_PROFILE: Lazy<VolumeProfile> = Lazy::new(|| {
+    dotenvy::dotenv().ok();
+    let path =
+        std::env::var("VOLUME_PROFILE").unwrap_or_else(|_| "config/volume_profile".to_string());
+
+    config::Config::builder()
+        .add_source(config::File::with_name(&path).required(false))
+        .build()
+        .expect("volume profile unreadable")
+        .try_deserialize()
+        .expect("volume profile invalid")
+});
+
+/// Broker fee schedule charged on every fill, loaded from `config/fees.toml` (or the file
+/// named by `FEE_SCHEDULE`). Rates are in percent of the traded value.
+#[derive(serde::Deserialize)]
+pub struct FeeSchedule {
+    #[serde(default = "default_commission_tiers")]
+    pub commission: Vec<CommissionTier>,
+    /// Smallest commission an order pays in total, in rupiah
+    #[serde(default)]
+    pub min_commission: Money,
+    /// Exchange, clearing and depository levies, charged on both sides
+    #[serde(default = "default_levy_pct")]
+    pub levy_pct: Decimal,
+    /// VAT charged on the commission
+    #[serde(default = "default_vat_pct")]
+    pub vat_pct: Decimal,
+    /// Final income tax on the value of a sell
+    #[serde(default = "default_sell_tax_pct")]
+    pub sell_tax_pct: Decimal,
+    /// Final income tax withheld on cash dividends
+    #[serde(default = "default_dividend_tax_pct")]
+    pub dividend_tax_pct: Decimal,
+}
+
+/// Commission for fills worth from `from` rupiah up to the next tier
+#[derive(serde::Deserialize)]
+pub struct CommissionTier {
+    pub from: Money,
+    pub buy_pct: Decimal,
+    pub sell_pct: Decimal,
+}
+
+fn default_commission_tiers() -> Vec<CommissionTier> {
+    vec![CommissionTier {
+        from: Money::ZERO,
+        buy_pct: dec!(0.1),
+        sell_pct: dec!(0.1),
+    }]
+}
+
+fn default_levy_pct() -> Decimal {
+    dec!(0.043)
+}
+
+fn default_vat_pct() -> Decimal {
+    dec!(11)
+}
+
+fn default_sell_tax_pct() -> Decimal {
+    dec!(0.1)
+}
+
+fn default_dividend_tax_pct() -> Decimal {
+    dec!(10)
+}
+
+pub static FEE_SCHEDULE: Lazy<FeeSchedule> = Lazy::new(|| {
+    dotenvy::dotenv().ok();
+    let path = std::env::var("FEE_SCHEDULE").unwrap_or_else(|_| "config/fees".to_string());
+
+    let mut fees: FeeSchedule = config::Config::builder()
+        .add_source(config::File::with_name(&path).required(false))
+        .build()
+        .expect("fee schedule unreadable")
+        .try_deserialize()
+        .expect("fee schedule invalid");
+    fees.commission.sort_by_key(|t| t.from);
+    fees
+});
+
+/// Corporate actions announced in `config/corporate_actions.toml` (or the file named by
+/// `CORPORATE_ACTIONS`)
+#[derive(serde::Deserialize)]
+pub struct CorporateActions {
+    #[serde(default)]
+    pub actions: Vec<ActionDefinition>,
+}
+
+/// Reads the announced corporate actions. Unlike the other files this one is read again
+/// on every run, so new announcements need no restart.
+pub fn corporate_actions() -> Result<CorporateActions, config::ConfigError> {
+    let path = std::env::var("CORPORATE_ACTIONS")
+        .unwrap_or_else(|_| "config/corporate_actions".to_string());
+
+    config::Config::builder()
+        .add_source(config::File::with_name(&path).required(false))
+        .build()?
+        .try_deserialize()
+}
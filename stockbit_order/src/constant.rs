@@ -3,7 +3,20 @@ pub const NOT_FOUND: &str = "HTTP/1.1 404 NOT FOUND\r\n\r\n";
 pub const BAD_REQUEST: &str = "HTTP/1.1 400 Bad Request\r\n\r\n";
 pub const OK_RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n";
 pub const INTERNAL_ERROR: &str = "HTTP/1.1 500 Internal Error\r\n\r\n";
+pub const UNPROCESSABLE_ENTITY: &str =
+    "HTTP/1.1 422 Unprocessable Entity\r\nContent-Type: application/json\r\n\r\n";
+pub const CONTINUE: &str = "HTTP/1.1 100 Continue\r\n\r\n";
+
+// request parser only knows GET/POST/OPTIONS, other methods ride on POST with this header
+pub const METHOD_OVERRIDE: &str = "x-http-method-override";
+
+// hex HMAC-SHA256 of a payment gateway callback's body
+pub const SIGNATURE: &str = "x-signature";
 
 pub const LOGGING_INCOMING_REQUEST: &str = "Incoming Request handling by: ";
 pub const LOGGING_HANDSHAKE: &str = "Handshake handling by: ";
 pub const LOGGING_MESSAGE: &str = "Message handling by: ";
+
+// entries per page of the ledger, by default and at most
+pub const LEDGER_PAGE_SIZE: i64 = 50;
+pub const LEDGER_MAX_PAGE_SIZE: i64 = 200;
@@ -0,0 +1,250 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::OrderError;
+use crate::order::model::Board;
+use crate::order::validation;
+use crate::product::model::Product;
+use crate::types::{Money, Price, Quantity};
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ActionKind {
+    /// `ratio_from` old shares become `ratio_to` new ones, more than before
+    Split,
+    /// `ratio_from` old shares become `ratio_to` new ones, fewer than before
+    ReverseSplit,
+    /// `amount` rupiah per share paid on `pay_date` to the holders on the ex-date
+    CashDividend,
+}
+
+impl TryFrom<String> for ActionKind {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "SPLIT" => Ok(ActionKind::Split),
+            "REVERSE_SPLIT" => Ok(ActionKind::ReverseSplit),
+            "CASH_DIVIDEND" => Ok(ActionKind::CashDividend),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionKind::Split => write!(f, "SPLIT"),
+            ActionKind::ReverseSplit => write!(f, "REVERSE_SPLIT"),
+            ActionKind::CashDividend => write!(f, "CASH_DIVIDEND"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ActionStatus {
+    Announced,
+    /// Positions and orders were adjusted, or dividend entitlements recorded, on the ex-date
+    Applied,
+    /// Dividends reached the accounts
+    Paid,
+}
+
+impl TryFrom<String> for ActionStatus {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "ANNOUNCED" => Ok(ActionStatus::Announced),
+            "APPLIED" => Ok(ActionStatus::Applied),
+            "PAID" => Ok(ActionStatus::Paid),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for ActionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionStatus::Announced => write!(f, "ANNOUNCED"),
+            ActionStatus::Applied => write!(f, "APPLIED"),
+            ActionStatus::Paid => write!(f, "PAID"),
+        }
+    }
+}
+
+/// One action as announced in `config/corporate_actions.toml`
+#[derive(Deserialize, Debug)]
+pub struct ActionDefinition {
+    pub symbol: String,
+    pub kind: ActionKind,
+    pub ex_date: NaiveDate,
+    #[serde(default)]
+    pub pay_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub ratio_from: Option<i32>,
+    #[serde(default)]
+    pub ratio_to: Option<i32>,
+    /// Dividend per share, in rupiah
+    #[serde(default)]
+    pub amount: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug, Clone)]
+pub struct CorporateAction {
+    pub action_id: Option<i32>,
+    pub product_id: i32,
+    pub product_symbol: String,
+    #[sqlx(try_from = "String")]
+    pub kind: ActionKind,
+    pub ex_date: NaiveDate,
+    pub pay_date: Option<NaiveDate>,
+    pub ratio_from: Option<i32>,
+    pub ratio_to: Option<i32>,
+    pub amount: Option<Decimal>,
+    #[sqlx(try_from = "String")]
+    pub status: ActionStatus,
+}
+
+impl CorporateAction {
+    pub fn new(definition: &ActionDefinition, product: &Product) -> Result<Self, OrderError> {
+        let ratio = definition.ratio_from.zip(definition.ratio_to);
+        match definition.kind {
+            ActionKind::Split | ActionKind::ReverseSplit => {
+                let Some((from, to)) = ratio.filter(|(from, to)| *from > 0 && *to > 0) else {
+                    return Err(OrderError::InvalidCorporateAction("need a positive ratio"));
+                };
+                if (definition.kind == ActionKind::Split) != (to > from) {
+                    return Err(OrderError::InvalidCorporateAction(
+                        "split ratios add shares, reverse split ratios remove them",
+                    ));
+                }
+            }
+            ActionKind::CashDividend => {
+                if definition
+                    .amount
+                    .is_none_or(|amount| amount <= Decimal::ZERO)
+                {
+                    return Err(OrderError::InvalidCorporateAction("need a positive amount"));
+                }
+                if definition
+                    .pay_date
+                    .is_none_or(|pay_date| pay_date < definition.ex_date)
+                {
+                    return Err(OrderError::InvalidCorporateAction(
+                        "need a pay date from the ex-date on",
+                    ));
+                }
+            }
+        }
+        Ok(Self {
+            action_id: None,
+            product_id: product.product_id,
+            product_symbol: product.symbol.clone(),
+            kind: definition.kind,
+            ex_date: definition.ex_date,
+            pay_date: definition.pay_date,
+            ratio_from: definition.ratio_from,
+            ratio_to: definition.ratio_to,
+            amount: definition.amount,
+            status: ActionStatus::Announced,
+        })
+    }
+
+    /// What a ratio that takes a holding past the largest quantity is refused with
+    const TOO_MANY_SHARES: OrderError =
+        OrderError::InvalidCorporateAction("would leave a holding with more shares than fit");
+
+    fn ratio(&self) -> (i64, i64) {
+        (
+            self.ratio_from.unwrap_or(1) as i64,
+            self.ratio_to.unwrap_or(1) as i64,
+        )
+    }
+
+    /// Whole shares `shares` turn into, with the fraction of a share left over. Refused
+    /// when they would not fit in a quantity.
+    pub fn adjust_shares(&self, shares: Quantity) -> Result<(Quantity, Decimal), OrderError> {
+        let (from, to) = self.ratio();
+        let adjusted = Decimal::from(shares) * Decimal::from(to) / Decimal::from(from);
+        let whole = adjusted.floor();
+        let whole_shares = Quantity::from_decimal(whole).ok_or(Self::TOO_MANY_SHARES)?;
+        Ok((whole_shares, adjusted - whole))
+    }
+
+    /// Shares the unfilled `quantity` of an order on `board` turns into, cut to what the
+    /// board takes: whole lots on the regular board, and nothing once an odd-lot order
+    /// outgrows its board
+    pub fn adjust_quantity(
+        &self,
+        quantity: Quantity,
+        board: Board,
+    ) -> Result<Quantity, OrderError> {
+        let (shares, _) = self.adjust_shares(quantity)?;
+        let (_, max) = validation::quantity_range(board);
+        Ok(match board {
+            Board::Regular => shares.round_lots().min(max),
+            _ if shares > max => Quantity::ZERO,
+            _ => shares,
+        })
+    }
+
+    /// Shares of an open lot or unsettled trade moved by the ratio, cut to whole shares
+    /// the way the database rescales them
+    pub fn rescale(&self, shares: Quantity) -> Result<Quantity, OrderError> {
+        let (from, to) = self.ratio();
+        i32::try_from(shares.get() as i64 * to / from)
+            .map(Quantity::new)
+            .map_err(|_| Self::TOO_MANY_SHARES)
+    }
+
+    pub fn adjust_price(&self, price: Decimal) -> Decimal {
+        let (from, to) = self.ratio();
+        price * Decimal::from(from) / Decimal::from(to)
+    }
+
+    /// Price in rupiah moved by the ratio, e.g. a stop price or trail amount
+    pub fn adjust_rupiah(&self, price: Price) -> Price {
+        Price::from_decimal(self.adjust_price(Decimal::from(price))).unwrap_or_default()
+    }
+}
+
+/// Split that moved one of the user's positions, and when it did
+#[derive(sqlx::FromRow, Debug)]
+pub struct AppliedSplit {
+    #[sqlx(flatten)]
+    pub action: CorporateAction,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Dividend a holder is owed for the shares held on the ex-date
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct Entitlement {
+    pub entitlement_id: Option<i32>,
+    pub action_id: i32,
+    pub user_id: i32,
+    pub shares: Quantity,
+    pub gross: Money,
+    /// Final income tax withheld on the dividend
+    pub tax: Money,
+    pub net: Money,
+}
+
+/// Audit trail of one change a corporate action made to a position, order or account
+#[derive(Serialize, Debug, Default)]
+pub struct Adjustment {
+    pub action_id: i32,
+    pub user_id: i32,
+    pub order_id: Option<i32>,
+    /// What was changed: `PORTFOLIO`, `ORDER`, `ENTITLEMENT` or `DIVIDEND`
+    pub target: &'static str,
+    pub quantity_before: Option<Quantity>,
+    pub quantity_after: Option<Quantity>,
+    pub price_before: Option<Decimal>,
+    pub price_after: Option<Decimal>,
+    /// Cash credited to the account: dividends, or the fractions of a share a split left
+    pub cash: Money,
+}
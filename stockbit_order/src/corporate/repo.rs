@@ -0,0 +1,175 @@
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+
+use super::model::{ActionStatus, Adjustment, AppliedSplit, CorporateAction, Entitlement};
+
+const ACTION_COLUMNS: &str = r#"action_id, product_id, product_symbol, kind, ex_date, pay_date,
+    ratio_from, ratio_to, amount, status"#;
+
+#[derive(Clone)]
+pub struct CorporateActionRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl CorporateActionRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Records an announced action, returns false when it was known already
+    pub async fn insert(&self, action: &CorporateAction) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"INSERT INTO corporate_actions (product_id, product_symbol, kind, ex_date,
+                pay_date, ratio_from, ratio_to, amount, status)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (product_id, kind, ex_date) DO NOTHING"#,
+        )
+        .bind(action.product_id)
+        .bind(&action.product_symbol)
+        .bind(action.kind.to_string())
+        .bind(action.ex_date)
+        .bind(action.pay_date)
+        .bind(action.ratio_from)
+        .bind(action.ratio_to)
+        .bind(action.amount)
+        .bind(action.status.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Actions with an ex-date or a dividend pay date on or before `date` still to process,
+    /// by ex-date
+    pub async fn get_due(&self, date: NaiveDate) -> Result<Vec<CorporateAction>, sqlx::Error> {
+        sqlx::query_as::<_, CorporateAction>(&format!(
+            "SELECT {ACTION_COLUMNS} FROM corporate_actions
+            WHERE (status = 'ANNOUNCED' AND ex_date <= $1)
+                OR (status = 'APPLIED' AND kind = 'CASH_DIVIDEND' AND pay_date <= $1)
+            ORDER BY ex_date, action_id"
+        ))
+        .bind(date)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn lock_by_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        action_id: i32,
+    ) -> Result<CorporateAction, sqlx::Error> {
+        sqlx::query_as::<_, CorporateAction>(&format!(
+            "SELECT {ACTION_COLUMNS} FROM corporate_actions WHERE action_id = $1 FOR UPDATE"
+        ))
+        .bind(action_id)
+        .fetch_one(&mut **tx)
+        .await
+    }
+
+    /// Splits that adjusted a position of the user, in the order they were applied
+    pub async fn get_position_splits(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+    ) -> Result<Vec<AppliedSplit>, sqlx::Error> {
+        sqlx::query_as::<_, AppliedSplit>(&format!(
+            "SELECT {ACTION_COLUMNS}, applied_at FROM corporate_actions
+            JOIN (SELECT action_id, MIN(created_at) AS applied_at FROM corporate_action_log
+                WHERE user_id = $1 AND target = 'PORTFOLIO' GROUP BY action_id) l
+            USING (action_id)
+            WHERE kind IN ('SPLIT', 'REVERSE_SPLIT')
+            ORDER BY applied_at"
+        ))
+        .bind(user_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    pub async fn set_status(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        action_id: i32,
+        status: ActionStatus,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE corporate_actions SET status = $1 WHERE action_id = $2"#)
+            .bind(status.to_string())
+            .bind(action_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_entitlement(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        entitlement: &Entitlement,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO dividend_entitlements (action_id, user_id, shares, gross, tax, net)
+            VALUES ($1, $2, $3, $4, $5, $6)"#,
+        )
+        .bind(entitlement.action_id)
+        .bind(entitlement.user_id)
+        .bind(entitlement.shares)
+        .bind(entitlement.gross)
+        .bind(entitlement.tax)
+        .bind(entitlement.net)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Locks the dividends of an action that were not paid yet
+    pub async fn lock_unpaid(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        action_id: i32,
+    ) -> Result<Vec<Entitlement>, sqlx::Error> {
+        sqlx::query_as::<_, Entitlement>(
+            r#"SELECT entitlement_id, action_id, user_id, shares, gross, tax, net
+            FROM dividend_entitlements WHERE action_id = $1 AND status = 'PENDING'
+            ORDER BY entitlement_id FOR UPDATE"#,
+        )
+        .bind(action_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    pub async fn mark_paid(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        entitlement_id: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE dividend_entitlements SET status = 'PAID', paid_at = CURRENT_TIMESTAMP
+            WHERE entitlement_id = $1"#,
+        )
+        .bind(entitlement_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn log(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        adjustment: &Adjustment,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO corporate_action_log (action_id, user_id, order_id, target,
+                quantity_before, quantity_after, price_before, price_after, cash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+        )
+        .bind(adjustment.action_id)
+        .bind(adjustment.user_id)
+        .bind(adjustment.order_id)
+        .bind(adjustment.target)
+        .bind(adjustment.quantity_before)
+        .bind(adjustment.quantity_after)
+        .bind(adjustment.price_before)
+        .bind(adjustment.price_after)
+        .bind(adjustment.cash)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+}
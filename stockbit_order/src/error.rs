@@ -1,5 +1,11 @@
 use std::{error::Error, fmt::Debug};
 
+use crate::algo::model::AlgoStatus;
+use crate::calendar::MarketPhase;
+use crate::funding::model::CashStatus;
+use crate::order::model::{Board, OrderStatus};
+use crate::types::{Money, Price, Quantity};
+
 #[derive(thiserror::Error)]
 pub enum OrderError {
     #[error("Serde error")]
@@ -13,6 +19,204 @@ pub enum OrderError {
 
     #[error("Request body error")]
     BadRequest,
+
+    #[error("Unknown order side '{0}'")]
+    InvalidSide(char),
+
+    #[error("Insufficient shares to sell")]
+    InsufficientShares,
+
+    #[error("Insufficient buying power")]
+    InsufficientFunds,
+
+    #[error("Order not found")]
+    OrderNotFound,
+
+    #[error("Expiry must be GTC, GFD or GTD with a date from today on")]
+    InvalidExpiry,
+
+    #[error("No liquidity within the market protection band")]
+    NoLiquidity,
+
+    #[error("Order {0} cannot move to {1}")]
+    InvalidTransition(OrderStatus, OrderStatus),
+
+    #[error("Quantity must be between {0} and {1} shares")]
+    InvalidQuantity(Quantity, Quantity),
+
+    #[error("Quantity must be a multiple of the {0} share lot")]
+    NotRoundLot(Quantity),
+
+    #[error("The {0} board only takes limit orders")]
+    LimitOnlyBoard(Board),
+
+    #[error("Price must be at least {0}")]
+    PriceTooLow(Price),
+
+    #[error("Price must be a multiple of the {0} tick")]
+    InvalidTick(Price),
+
+    #[error("Price must be between {0} and {1}")]
+    PriceOutOfBand(Price, Price),
+
+    #[error("Order {0} is too large")]
+    TooLarge(&'static str),
+
+    #[error("Market orders are not accepted during {0}")]
+    MarketClosed(MarketPhase),
+
+    #[error("Stop orders need a stop price on a valid tick")]
+    InvalidStopPrice,
+
+    #[error("Trailing stops need either a positive trail_amount or a trail_percent below 100")]
+    InvalidTrail(&'static str),
+
+    #[error("Linked orders {0}")]
+    InvalidLink(&'static str),
+
+    #[error("Baskets take from 1 up to {0} orders")]
+    InvalidBasket(usize),
+
+    #[error("Another order of the all-or-none basket was rejected")]
+    BasketRejected,
+
+    #[error("Algo orders {0}")]
+    InvalidAlgo(&'static str),
+
+    #[error("Algo order not found")]
+    AlgoNotFound,
+
+    #[error("Algo {0} cannot move to {1}")]
+    InvalidAlgoTransition(AlgoStatus, AlgoStatus),
+
+    #[error("Corporate actions {0}")]
+    InvalidCorporateAction(&'static str),
+
+    #[error("Amount must be positive")]
+    InvalidAmount,
+
+    #[error("Only {0} of settled cash can be withdrawn")]
+    WithdrawalLimit(Money),
+
+    #[error("Cash request not found")]
+    CashRequestNotFound,
+
+    #[error("Cash request {0} cannot move to {1}")]
+    InvalidCashTransition(CashStatus, CashStatus),
+}
+
+/// Reason sent back to the client when an order is refused
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Rejection {
+    pub code: String,
+    pub reason: String,
+    /// Order field the rejection is about, when there is one
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub field: Option<String>,
+}
+
+impl OrderError {
+    /// What an amount that does not fit refuses the order with, it is always down to the
+    /// size of the order
+    pub const OVERFLOW: OrderError = OrderError::TooLarge("quantity");
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            OrderError::Serde => "SERDE",
+            OrderError::Redis => "REDIS",
+            OrderError::Database => "DATABASE",
+            OrderError::BadRequest => "BAD_REQUEST",
+            OrderError::InvalidSide(_) => "INVALID_SIDE",
+            OrderError::InsufficientShares => "INSUFFICIENT_SHARES",
+            OrderError::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            OrderError::OrderNotFound => "ORDER_NOT_FOUND",
+            OrderError::InvalidExpiry => "INVALID_EXPIRY",
+            OrderError::NoLiquidity => "NO_LIQUIDITY",
+            OrderError::InvalidTransition(_, _) => "INVALID_TRANSITION",
+            OrderError::InvalidQuantity(_, _) => "INVALID_QUANTITY",
+            OrderError::NotRoundLot(_) => "NOT_ROUND_LOT",
+            OrderError::LimitOnlyBoard(_) => "LIMIT_ONLY_BOARD",
+            OrderError::PriceTooLow(_) => "PRICE_TOO_LOW",
+            OrderError::InvalidTick(_) => "INVALID_TICK",
+            OrderError::PriceOutOfBand(_, _) => "PRICE_OUT_OF_BAND",
+            OrderError::TooLarge(_) => "TOO_LARGE",
+            OrderError::MarketClosed(_) => "MARKET_CLOSED",
+            OrderError::InvalidStopPrice => "INVALID_STOP_PRICE",
+            OrderError::InvalidTrail(_) => "INVALID_TRAIL",
+            OrderError::InvalidLink(_) => "INVALID_LINK",
+            OrderError::InvalidBasket(_) => "INVALID_BASKET",
+            OrderError::BasketRejected => "BASKET_REJECTED",
+            OrderError::InvalidAlgo(_) => "INVALID_ALGO",
+            OrderError::AlgoNotFound => "ALGO_NOT_FOUND",
+            OrderError::InvalidAlgoTransition(_, _) => "INVALID_ALGO_TRANSITION",
+            OrderError::InvalidCorporateAction(_) => "INVALID_CORPORATE_ACTION",
+            OrderError::InvalidAmount => "INVALID_AMOUNT",
+            OrderError::WithdrawalLimit(_) => "WITHDRAWAL_LIMIT",
+            OrderError::CashRequestNotFound => "CASH_REQUEST_NOT_FOUND",
+            OrderError::InvalidCashTransition(_, _) => "INVALID_CASH_TRANSITION",
+        }
+    }
+
+    /// Business rule rejections, as opposed to malformed requests or infra failures
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            OrderError::InvalidSide(_)
+                | OrderError::InsufficientShares
+                | OrderError::InsufficientFunds
+                | OrderError::OrderNotFound
+                | OrderError::InvalidExpiry
+                | OrderError::NoLiquidity
+                | OrderError::InvalidTransition(_, _)
+                | OrderError::InvalidQuantity(_, _)
+                | OrderError::NotRoundLot(_)
+                | OrderError::LimitOnlyBoard(_)
+                | OrderError::PriceTooLow(_)
+                | OrderError::InvalidTick(_)
+                | OrderError::PriceOutOfBand(_, _)
+                | OrderError::TooLarge(_)
+                | OrderError::MarketClosed(_)
+                | OrderError::InvalidStopPrice
+                | OrderError::InvalidTrail(_)
+                | OrderError::InvalidLink(_)
+                | OrderError::InvalidBasket(_)
+                | OrderError::BasketRejected
+                | OrderError::InvalidAlgo(_)
+                | OrderError::AlgoNotFound
+                | OrderError::InvalidAlgoTransition(_, _)
+                | OrderError::InvalidAmount
+                | OrderError::WithdrawalLimit(_)
+                | OrderError::CashRequestNotFound
+                | OrderError::InvalidCashTransition(_, _)
+        )
+    }
+
+    /// Order field a rejection points at
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            OrderError::InvalidSide(_) => Some("side"),
+            OrderError::InvalidExpiry => Some("expiry"),
+            OrderError::InsufficientShares
+            | OrderError::InvalidQuantity(_, _)
+            | OrderError::NotRoundLot(_) => Some("quantity"),
+            OrderError::LimitOnlyBoard(_) => Some("order_type"),
+            OrderError::PriceTooLow(_)
+            | OrderError::InvalidTick(_)
+            | OrderError::PriceOutOfBand(_, _) => Some("price"),
+            OrderError::InvalidStopPrice => Some("stop_price"),
+            OrderError::InvalidTrail(field) | OrderError::TooLarge(field) => Some(field),
+            OrderError::InvalidAmount | OrderError::WithdrawalLimit(_) => Some("amount"),
+            _ => None,
+        }
+    }
+
+    pub fn rejection(&self) -> Rejection {
+        Rejection {
+            code: self.code().to_string(),
+            reason: self.to_string(),
+            field: self.field().map(str::to_string),
+        }
+    }
 }
 
 impl Debug for OrderError {
@@ -0,0 +1,99 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+use crate::cfg::FEE_SCHEDULE;
+use crate::order::model::Side;
+use crate::types::{Money, Price, Quantity};
+
+/// What a fill costs on top of its value, in rupiah. Orders carry the sum over their fills.
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Fees {
+    pub commission: Money,
+    pub levy: Money,
+    pub vat: Money,
+    /// Final income tax, sells only
+    pub tax: Money,
+}
+
+impl Fees {
+    pub fn total(&self) -> Money {
+        self.commission + self.levy + self.vat + self.tax
+    }
+
+    pub fn checked_total(&self) -> Option<Money> {
+        self.commission
+            .checked_add(self.levy)?
+            .checked_add(self.vat)?
+            .checked_add(self.tax)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        Some(Fees {
+            commission: self.commission.checked_add(other.commission)?,
+            levy: self.levy.checked_add(other.levy)?,
+            vat: self.vat.checked_add(other.vat)?,
+            tax: self.tax.checked_add(other.tax)?,
+        })
+    }
+}
+
+impl AddAssign for Fees {
+    fn add_assign(&mut self, other: Self) {
+        self.commission += other.commission;
+        self.levy += other.levy;
+        self.vat += other.vat;
+        self.tax += other.tax;
+    }
+}
+
+/// Fees on a fill worth `value`. `charged` is the commission the order already paid on
+/// earlier fills, the minimum commission is topped up on the first fills.
+pub fn for_fill(side: Side, value: Money, charged: Money) -> Fees {
+    let schedule = &*FEE_SCHEDULE;
+    let tier = schedule
+        .commission
+        .iter()
+        .rev()
+        .find(|t| t.from <= value)
+        .or(schedule.commission.first());
+    let rate = tier.map_or(Decimal::ZERO, |t| match side {
+        Side::Buy => t.buy_pct,
+        Side::Sell => t.sell_pct,
+    });
+    let commission = value.percent(rate).max(schedule.min_commission - charged);
+    Fees {
+        commission,
+        levy: value.percent(schedule.levy_pct),
+        vat: commission.percent(schedule.vat_pct),
+        tax: match side {
+            Side::Buy => Money::ZERO,
+            Side::Sell => value.percent(schedule.sell_tax_pct),
+        },
+    }
+}
+
+/// Cash a buy of `quantity` shares at `price` holds: the value plus the most its fills can
+/// cost at the priciest tier. Linear in the quantity so partial fills release exactly what
+/// they held. `None` when it does not fit in rupiah.
+pub fn buy_hold(price: Price, quantity: Quantity) -> Option<Money> {
+    let schedule = &*FEE_SCHEDULE;
+    let rate = schedule
+        .commission
+        .iter()
+        .map(|t| t.buy_pct)
+        .max()
+        .unwrap_or_default();
+    let fee_pct = rate + rate * schedule.vat_pct / Decimal::ONE_HUNDRED + schedule.levy_pct;
+    let share_fees =
+        Money::from_decimal((Decimal::from(price) * fee_pct / Decimal::ONE_HUNDRED).ceil())
+            .unwrap_or_default();
+    share_fees
+        .checked_times(quantity.get().into())
+        .and_then(|fees| (price * quantity).checked_add(fees))
+}
+
+/// Tax withheld on a cash dividend of `gross`
+pub fn dividend_tax(gross: Money) -> Money {
+    gross.percent(FEE_SCHEDULE.dividend_tax_pct)
+}
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::OrderError;
+use crate::types::Money;
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CashKind {
+    Deposit,
+    Withdrawal,
+}
+
+impl TryFrom<String> for CashKind {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "DEPOSIT" => Ok(CashKind::Deposit),
+            "WITHDRAWAL" => Ok(CashKind::Withdrawal),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for CashKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CashKind::Deposit => write!(f, "DEPOSIT"),
+            CashKind::Withdrawal => write!(f, "WITHDRAWAL"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CashStatus {
+    /// Waiting for review, a withdrawal holds its amount from here on
+    Pending,
+    /// Sent to the payment gateway
+    Approved,
+    /// Refused in review or failed at the gateway, nothing moved
+    Rejected,
+    /// The gateway moved the money and the balance followed
+    Completed,
+}
+
+impl CashStatus {
+    pub fn can_transition_to(&self, next: CashStatus) -> bool {
+        use CashStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Approved | Rejected) | (Approved, Completed | Rejected)
+        )
+    }
+}
+
+impl TryFrom<String> for CashStatus {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "PENDING" => Ok(CashStatus::Pending),
+            "APPROVED" => Ok(CashStatus::Approved),
+            "REJECTED" => Ok(CashStatus::Rejected),
+            "COMPLETED" => Ok(CashStatus::Completed),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for CashStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CashStatus::Pending => write!(f, "PENDING"),
+            CashStatus::Approved => write!(f, "APPROVED"),
+            CashStatus::Rejected => write!(f, "REJECTED"),
+            CashStatus::Completed => write!(f, "COMPLETED"),
+        }
+    }
+}
+
+/// Deposit or withdrawal of a user, the balance only moves once it completes
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct CashRequest {
+    pub request_id: Option<i32>,
+    pub user_id: i32,
+    #[serde(skip)]
+    pub account_id: i32,
+    #[sqlx(try_from = "String")]
+    pub kind: CashKind,
+    pub amount: Money,
+    #[sqlx(try_from = "String")]
+    pub status: CashStatus,
+    /// Reference the payment gateway calls back with, set on approval. Kept from the
+    /// user, it is what a callback is matched on.
+    #[serde(skip)]
+    pub gateway_ref: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CashRequest {
+    pub fn new(
+        kind: CashKind,
+        amount: Money,
+        user_id: i32,
+        account_id: i32,
+    ) -> Result<Self, OrderError> {
+        if amount <= Money::ZERO {
+            return Err(OrderError::InvalidAmount);
+        }
+        let now = Utc::now();
+        Ok(Self {
+            request_id: None,
+            user_id,
+            account_id,
+            kind,
+            amount,
+            status: CashStatus::Pending,
+            gateway_ref: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Moves the request to `next`, refusing transitions the workflow does not allow
+    pub fn transition(&mut self, next: CashStatus) -> Result<(), OrderError> {
+        if !self.status.can_transition_to(next) {
+            return Err(OrderError::InvalidCashTransition(self.status, next));
+        }
+        self.status = next;
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+}
+
+/// Body of `POST /account/deposit` and `POST /account/withdrawal`
+#[derive(Serialize, Deserialize)]
+pub struct CashForm {
+    pub amount: Money,
+}
+
+/// Body of `POST /internal/cash/review`
+#[derive(Serialize, Deserialize)]
+pub struct ReviewForm {
+    pub request_id: i32,
+    pub approve: bool,
+    /// Who reviewed, kept in the log
+    pub reviewer: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GatewayStatus {
+    Success,
+    Failed,
+}
+
+/// Body of `POST /gateway/callback`, what the payment gateway reports for an approved
+/// request
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GatewayCallback {
+    pub request_id: i32,
+    pub gateway_ref: String,
+    pub status: GatewayStatus,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// One state change of a cash request
+#[derive(Serialize, Debug)]
+pub struct CashLog {
+    pub request_id: i32,
+    /// None when the request was created
+    pub from_status: Option<CashStatus>,
+    pub to_status: CashStatus,
+    /// User, reviewer or gateway that made the change
+    pub actor: String,
+    pub note: Option<String>,
+}
@@ -0,0 +1,101 @@
+use sqlx::{Postgres, Transaction};
+
+use super::model::{CashLog, CashRequest};
+
+const CASH_COLUMNS: &str = r#"request_id, user_id, account_id, kind, amount, status, gateway_ref,
+    created_at, updated_at"#;
+
+#[derive(Clone)]
+pub struct FundingRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl FundingRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        cash: &CashRequest,
+    ) -> Result<i32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as(
+            r#"INSERT INTO cash_requests (user_id, account_id, kind, amount, status,
+                created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING request_id"#,
+        )
+        .bind(cash.user_id)
+        .bind(cash.account_id)
+        .bind(cash.kind.to_string())
+        .bind(cash.amount)
+        .bind(cash.status.to_string())
+        .bind(cash.created_at)
+        .bind(cash.updated_at)
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok(row.0)
+    }
+
+    pub async fn lock_by_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        request_id: i32,
+    ) -> Result<CashRequest, sqlx::Error> {
+        sqlx::query_as::<_, CashRequest>(&format!(
+            "SELECT {CASH_COLUMNS} FROM cash_requests WHERE request_id = $1 FOR UPDATE"
+        ))
+        .bind(request_id)
+        .fetch_one(&mut **tx)
+        .await
+    }
+
+    pub async fn update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        cash: &CashRequest,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE cash_requests SET status = $1, gateway_ref = $2, updated_at = $3
+            WHERE request_id = $4"#,
+        )
+        .bind(cash.status.to_string())
+        .bind(&cash.gateway_ref)
+        .bind(cash.updated_at)
+        .bind(cash.request_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn log(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        log: &CashLog,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO cash_request_log (request_id, from_status, to_status, actor, note)
+            VALUES ($1, $2, $3, $4, $5)"#,
+        )
+        .bind(log.request_id)
+        .bind(log.from_status.map(|status| status.to_string()))
+        .bind(log.to_status.to_string())
+        .bind(&log.actor)
+        .bind(&log.note)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Requests of the user, newest first
+    pub async fn get_all_by_user_id(&self, user_id: i32) -> Result<Vec<CashRequest>, sqlx::Error> {
+        sqlx::query_as::<_, CashRequest>(&format!(
+            "SELECT {CASH_COLUMNS} FROM cash_requests WHERE user_id = $1
+            ORDER BY request_id DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
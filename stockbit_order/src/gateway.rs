@@ -0,0 +1,38 @@
+use chrono::Utc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::cfg::CONFIG;
+use crate::funding::model::{CashRequest, GatewayCallback, GatewayStatus};
+use crate::svc::Service;
+
+/// Reference the payment gateway knows an approved request by
+pub fn reference(cash: &CashRequest) -> String {
+    format!(
+        "MOCK-{}-{}",
+        cash.request_id.unwrap_or_default(),
+        Utc::now().timestamp_millis()
+    )
+}
+
+/// Hands an approved request to the payment gateway. Only the local mock exists: with
+/// `mock_gateway` on it reports success after `mock_gateway_delay_secs`, through the same
+/// path a signed `POST /gateway/callback` of a real gateway takes.
+pub fn submit(svc: Service, cash: &CashRequest) {
+    if !CONFIG.mock_gateway {
+        return;
+    }
+    let callback = GatewayCallback {
+        request_id: cash.request_id.unwrap_or_default(),
+        gateway_ref: cash.gateway_ref.clone().unwrap_or_default(),
+        status: GatewayStatus::Success,
+        reason: None,
+    };
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(CONFIG.mock_gateway_delay_secs)).await;
+        let request_id = callback.request_id;
+        if let Err(e) = svc.complete_cash_request(callback).await {
+            info!("error mock gateway {} {:?}", request_id, e);
+        }
+    });
+}
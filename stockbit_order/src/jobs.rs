@@ -0,0 +1,137 @@
+use chrono::NaiveTime;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::calendar;
+use crate::cfg::CONFIG;
+use crate::svc::Service;
+
+/// Expires GFD and due GTD orders every time the market closes
+pub async fn run_end_of_day(svc: Arc<Service>) {
+    loop {
+        let now = calendar::now();
+        let close = calendar::next_close(now);
+        tokio::time::sleep((close - now).to_std().unwrap_or_default()).await;
+
+        match svc.expire_orders(close.date_naive()).await {
+            Ok(expired) => info!("Expired {} orders at close of {}", expired, close),
+            Err(e) => info!("error expire orders {:?}", e),
+        }
+    }
+}
+
+/// Settles the trades due at the start of every day, and right away when the service starts
+pub async fn run_settlement(svc: Arc<Service>) {
+    loop {
+        let today = calendar::today();
+        match svc.settle_trades(today).await {
+            Ok(0) => {}
+            Ok(settled) => info!("Settled {} trades due by {}", settled, today),
+            Err(e) => info!("error settle trades {:?}", e),
+        }
+        let next_day =
+            calendar::at_exchange_time(today.succ_opt().unwrap_or(today), NaiveTime::MIN);
+        tokio::time::sleep((next_day - calendar::now()).to_std().unwrap_or_default()).await;
+    }
+}
+
+/// Checks positions and balances against the trade history every night. Only reports,
+/// repairs are made with the `reconcile --apply` command.
+pub async fn run_reconciliation(svc: Arc<Service>) {
+    loop {
+        let today = calendar::today();
+        let next_day =
+            calendar::at_exchange_time(today.succ_opt().unwrap_or(today), NaiveTime::MIN);
+        tokio::time::sleep((next_day - calendar::now()).to_std().unwrap_or_default()).await;
+        match svc.reconcile(false).await {
+            Ok(report) if report.users.is_empty() && report.errors.is_empty() => {
+                info!("Reconciled {} users, no drift", report.users_checked)
+            }
+            Ok(report) => info!(
+                "Reconciliation found drift {}",
+                serde_json::to_string(&report).unwrap_or_default()
+            ),
+            Err(e) => info!("error reconciliation {:?}", e),
+        }
+    }
+}
+
+/// Processes corporate actions at the start of every day, before anything trades, and right
+/// away when the service starts
+pub async fn run_corporate_actions(svc: Arc<Service>) {
+    loop {
+        let today = calendar::today();
+        match svc.run_corporate_actions(today).await {
+            Ok(0) => {}
+            Ok(processed) => info!("Processed {} corporate actions due by {}", processed, today),
+            Err(e) => info!("error corporate actions {:?}", e),
+        }
+        let next_day =
+            calendar::at_exchange_time(today.succ_opt().unwrap_or(today), NaiveTime::MIN);
+        tokio::time::sleep((next_day - calendar::now()).to_std().unwrap_or_default()).await;
+    }
+}
+
+/// Works the orders queued outside trading hours as each continuous session opens,
+/// right away when the service starts mid-session, and whenever bracket exits activate
+pub async fn run_session_open(svc: Arc<Service>) {
+    loop {
+        let now = calendar::now();
+        if calendar::phase_at(now).is_continuous() {
+            match svc.release_queued_orders().await {
+                Ok(released) => info!("Released {} queued orders at {}", released, now),
+                Err(e) => info!("error release queued orders {:?}", e),
+            }
+        }
+        let open = calendar::next_open(now);
+        tokio::select! {
+            _ = tokio::time::sleep((open - now).to_std().unwrap_or_default()) => {},
+            _ = svc.wait_for_queued() => {},
+        }
+    }
+}
+
+/// Uncrosses the pre-opening call auction every trading day
+pub async fn run_call_auction(svc: Arc<Service>) {
+    loop {
+        let now = calendar::now();
+        let uncross = calendar::next_uncross(now);
+        tokio::time::sleep((uncross - now).to_std().unwrap_or_default()).await;
+
+        match svc.run_call_auction().await {
+            Ok(matched) => info!("Call auction matched {} shares at {}", matched, uncross),
+            Err(e) => info!("error call auction {:?}", e),
+        }
+    }
+}
+
+/// Works algo orders every `algo_tick_secs`
+pub async fn run_algos(svc: Arc<Service>) {
+    let mut tick = tokio::time::interval(Duration::from_secs(CONFIG.algo_tick_secs));
+    loop {
+        tick.tick().await;
+        match svc.work_algos().await {
+            Ok(0) => {}
+            Ok(sent) => info!("Algo orders sent {} shares", sent),
+            Err(e) => info!("error work algo orders {:?}", e),
+        }
+    }
+}
+
+/// Checks stop orders whenever the matcher prints a trade, and every
+/// `price_feed_poll_secs` against the Redis price feed
+pub async fn run_trigger_watch(svc: Arc<Service>) {
+    let mut poll = tokio::time::interval(Duration::from_secs(CONFIG.price_feed_poll_secs));
+    loop {
+        tokio::select! {
+            _ = svc.wait_for_trade() => {},
+            _ = poll.tick() => {},
+        }
+        match svc.check_triggers().await {
+            Ok(0) => {}
+            Ok(released) => info!("Released {} triggered stop orders", released),
+            Err(e) => info!("error check stop orders {:?}", e),
+        }
+    }
+}
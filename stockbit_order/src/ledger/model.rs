@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::corporate::model::Entitlement;
+use crate::error::OrderError;
+use crate::funding::model::{CashKind, CashRequest};
+use crate::order::model::Side;
+use crate::trade::model::Trade;
+use crate::types::Money;
+
+/// Why cash moved
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EntryKind {
+    /// Balance the account had when the ledger started
+    OpeningBalance,
+    /// Value of a buy fill paid to the clearing house
+    OrderDebit,
+    /// Value of a sell fill received from the clearing house
+    SaleCredit,
+    /// Commission, levy, VAT and sell tax of a fill
+    Fee,
+    /// Cash dividend paid net of its tax
+    Dividend,
+    /// Cash paid for the fractional shares a split left
+    CashInLieu,
+    Deposit,
+    Withdrawal,
+    /// Correction booked when reconciliation repairs a balance
+    Reconciliation,
+}
+
+impl TryFrom<String> for EntryKind {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "OPENING_BALANCE" => Ok(EntryKind::OpeningBalance),
+            "ORDER_DEBIT" => Ok(EntryKind::OrderDebit),
+            "SALE_CREDIT" => Ok(EntryKind::SaleCredit),
+            "FEE" => Ok(EntryKind::Fee),
+            "DIVIDEND" => Ok(EntryKind::Dividend),
+            "CASH_IN_LIEU" => Ok(EntryKind::CashInLieu),
+            "DEPOSIT" => Ok(EntryKind::Deposit),
+            "WITHDRAWAL" => Ok(EntryKind::Withdrawal),
+            "RECONCILIATION" => Ok(EntryKind::Reconciliation),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryKind::OpeningBalance => write!(f, "OPENING_BALANCE"),
+            EntryKind::OrderDebit => write!(f, "ORDER_DEBIT"),
+            EntryKind::SaleCredit => write!(f, "SALE_CREDIT"),
+            EntryKind::Fee => write!(f, "FEE"),
+            EntryKind::Dividend => write!(f, "DIVIDEND"),
+            EntryKind::CashInLieu => write!(f, "CASH_IN_LIEU"),
+            EntryKind::Deposit => write!(f, "DEPOSIT"),
+            EntryKind::Withdrawal => write!(f, "WITHDRAWAL"),
+            EntryKind::Reconciliation => write!(f, "RECONCILIATION"),
+        }
+    }
+}
+
+/// Ledger account a leg posts to. Only `Cash` is the user's money, the others are
+/// where it came from or went to.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Book {
+    Cash,
+    /// Trade values owed to or by the clearing house
+    Clearing,
+    Commission,
+    Levy,
+    Vat,
+    /// Sell tax and dividend tax withheld
+    Tax,
+    /// Dividends and cash in lieu paid by the issuer
+    Issuer,
+    /// Money moved in from or out to the user's bank
+    Bank,
+    /// Other side of reconciliation corrections, until someone explains them
+    Suspense,
+}
+
+impl TryFrom<String> for Book {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "CASH" => Ok(Book::Cash),
+            "CLEARING" => Ok(Book::Clearing),
+            "COMMISSION" => Ok(Book::Commission),
+            "LEVY" => Ok(Book::Levy),
+            "VAT" => Ok(Book::Vat),
+            "TAX" => Ok(Book::Tax),
+            "ISSUER" => Ok(Book::Issuer),
+            "BANK" => Ok(Book::Bank),
+            "SUSPENSE" => Ok(Book::Suspense),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Book::Cash => write!(f, "CASH"),
+            Book::Clearing => write!(f, "CLEARING"),
+            Book::Commission => write!(f, "COMMISSION"),
+            Book::Levy => write!(f, "LEVY"),
+            Book::Vat => write!(f, "VAT"),
+            Book::Tax => write!(f, "TAX"),
+            Book::Issuer => write!(f, "ISSUER"),
+            Book::Bank => write!(f, "BANK"),
+            Book::Suspense => write!(f, "SUSPENSE"),
+        }
+    }
+}
+
+/// One side of an entry, either a debit or a credit. A debit to `Cash` adds to the
+/// balance, a credit takes from it.
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug, Clone)]
+pub struct Leg {
+    #[serde(skip)]
+    pub entry_id: i32,
+    #[sqlx(try_from = "String")]
+    pub book: Book,
+    pub debit: Money,
+    pub credit: Money,
+}
+
+impl Leg {
+    pub fn debit(book: Book, amount: Money) -> Self {
+        Self {
+            entry_id: 0,
+            book,
+            debit: amount,
+            credit: Money::ZERO,
+        }
+    }
+
+    pub fn credit(book: Book, amount: Money) -> Self {
+        Self {
+            entry_id: 0,
+            book,
+            debit: Money::ZERO,
+            credit: amount,
+        }
+    }
+}
+
+/// Journal entry of the append-only cash ledger, its legs always balance
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct JournalEntry {
+    pub entry_id: Option<i32>,
+    #[serde(skip)]
+    pub account_id: i32,
+    #[sqlx(try_from = "String")]
+    pub kind: EntryKind,
+    /// Trade, corporate action or cash request the entry books
+    pub reference_id: Option<i32>,
+    /// Balance of the account once the entry was posted
+    pub balance_after: Money,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub legs: Vec<Leg>,
+}
+
+impl JournalEntry {
+    /// Zero legs are left out. Legs that do not balance are a bug, not a rejection.
+    pub fn new(
+        account_id: i32,
+        kind: EntryKind,
+        reference_id: Option<i32>,
+        legs: Vec<Leg>,
+    ) -> Self {
+        let legs: Vec<Leg> = legs
+            .into_iter()
+            .filter(|leg| !leg.debit.is_zero() || !leg.credit.is_zero())
+            .collect();
+        let debit: Money = legs.iter().map(|leg| leg.debit).sum();
+        let credit: Money = legs.iter().map(|leg| leg.credit).sum();
+        assert_eq!(debit, credit, "unbalanced {} entry", kind);
+        Self {
+            entry_id: None,
+            account_id,
+            kind,
+            reference_id,
+            balance_after: Money::ZERO,
+            created_at: Utc::now(),
+            legs,
+        }
+    }
+
+    /// Entries of a fill: its value against the clearing house, then its fees
+    pub fn for_trade(account_id: i32, trade_id: i32, trade: &Trade) -> Vec<Self> {
+        let value = trade.value();
+        let fees = trade.fees;
+        let mut entries = vec![match trade.side {
+            Side::Buy => Self::new(
+                account_id,
+                EntryKind::OrderDebit,
+                Some(trade_id),
+                vec![
+                    Leg::debit(Book::Clearing, value),
+                    Leg::credit(Book::Cash, value),
+                ],
+            ),
+            Side::Sell => Self::new(
+                account_id,
+                EntryKind::SaleCredit,
+                Some(trade_id),
+                vec![
+                    Leg::debit(Book::Cash, value),
+                    Leg::credit(Book::Clearing, value),
+                ],
+            ),
+        }];
+        if !fees.total().is_zero() {
+            entries.push(Self::new(
+                account_id,
+                EntryKind::Fee,
+                Some(trade_id),
+                vec![
+                    Leg::debit(Book::Commission, fees.commission),
+                    Leg::debit(Book::Levy, fees.levy),
+                    Leg::debit(Book::Vat, fees.vat),
+                    Leg::debit(Book::Tax, fees.tax),
+                    Leg::credit(Book::Cash, fees.total()),
+                ],
+            ));
+        }
+        entries
+    }
+
+    pub fn dividend(account_id: i32, entitlement: &Entitlement) -> Self {
+        Self::new(
+            account_id,
+            EntryKind::Dividend,
+            Some(entitlement.action_id),
+            vec![
+                Leg::debit(Book::Cash, entitlement.net),
+                Leg::debit(Book::Tax, entitlement.tax),
+                Leg::credit(Book::Issuer, entitlement.gross),
+            ],
+        )
+    }
+
+    pub fn cash_in_lieu(account_id: i32, action_id: i32, cash: Money) -> Self {
+        Self::new(
+            account_id,
+            EntryKind::CashInLieu,
+            Some(action_id),
+            vec![
+                Leg::debit(Book::Cash, cash),
+                Leg::credit(Book::Issuer, cash),
+            ],
+        )
+    }
+
+    /// Money a completed deposit brought in, or a completed withdrawal sent out
+    pub fn for_cash(cash: &CashRequest) -> Self {
+        let (kind, legs) = match cash.kind {
+            CashKind::Deposit => (
+                EntryKind::Deposit,
+                vec![
+                    Leg::debit(Book::Cash, cash.amount),
+                    Leg::credit(Book::Bank, cash.amount),
+                ],
+            ),
+            CashKind::Withdrawal => (
+                EntryKind::Withdrawal,
+                vec![
+                    Leg::debit(Book::Bank, cash.amount),
+                    Leg::credit(Book::Cash, cash.amount),
+                ],
+            ),
+        };
+        Self::new(cash.account_id, kind, cash.request_id, legs)
+    }
+
+    /// Moves the balance by `delta` to where reconciliation says it should be
+    pub fn correction(account_id: i32, delta: Money) -> Self {
+        let legs = match delta > Money::ZERO {
+            true => vec![
+                Leg::debit(Book::Cash, delta),
+                Leg::credit(Book::Suspense, delta),
+            ],
+            false => vec![
+                Leg::debit(Book::Suspense, -delta),
+                Leg::credit(Book::Cash, -delta),
+            ],
+        };
+        Self::new(account_id, EntryKind::Reconciliation, None, legs)
+    }
+
+    /// What the entry adds to the account balance
+    pub fn cash(&self) -> Money {
+        self.legs
+            .iter()
+            .filter(|leg| leg.book == Book::Cash)
+            .map(|leg| leg.debit - leg.credit)
+            .sum()
+    }
+}
+
+/// One page of `GET /account/ledger`, newest entries first
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LedgerPage {
+    /// Balance of the account
+    pub balance: Money,
+    /// Sum of the cash legs of every entry, equal to `balance` unless something moved
+    /// the balance outside of the ledger
+    pub ledger_balance: Money,
+    pub entries: Vec<JournalEntry>,
+    /// `before` of the next page, none on the last one
+    pub next_before: Option<i32>,
+}
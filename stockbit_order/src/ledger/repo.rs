@@ -0,0 +1,120 @@
+use sqlx::{Postgres, Transaction};
+
+use super::model::{JournalEntry, Leg};
+use crate::types::Money;
+
+#[derive(Clone)]
+pub struct LedgerRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl LedgerRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Appends the entry with its legs, entries are never updated or deleted
+    pub async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        entry: &JournalEntry,
+    ) -> Result<i32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as(
+            r#"INSERT INTO journal_entries (account_id, kind, reference_id, balance_after,
+                created_at)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING entry_id"#,
+        )
+        .bind(entry.account_id)
+        .bind(entry.kind.to_string())
+        .bind(entry.reference_id)
+        .bind(entry.balance_after)
+        .bind(entry.created_at)
+        .fetch_one(&mut **tx)
+        .await?;
+        for leg in &entry.legs {
+            sqlx::query(
+                r#"INSERT INTO journal_legs (entry_id, book, debit, credit)
+                VALUES ($1, $2, $3, $4)"#,
+            )
+            .bind(row.0)
+            .bind(leg.book.to_string())
+            .bind(leg.debit)
+            .bind(leg.credit)
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(row.0)
+    }
+
+    /// Up to `limit` entries of the account older than entry `before`, newest first
+    pub async fn get_page(
+        &self,
+        account_id: i32,
+        before: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<JournalEntry>, sqlx::Error> {
+        let mut entries = sqlx::query_as::<_, JournalEntry>(
+            r#"SELECT entry_id, account_id, kind, reference_id, balance_after, created_at
+            FROM journal_entries
+            WHERE account_id = $1 AND ($2::integer IS NULL OR entry_id < $2)
+            ORDER BY entry_id DESC
+            LIMIT $3"#,
+        )
+        .bind(account_id)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let ids: Vec<i32> = entries.iter().filter_map(|entry| entry.entry_id).collect();
+        let legs = sqlx::query_as::<_, Leg>(
+            r#"SELECT entry_id, book, debit, credit FROM journal_legs
+            WHERE entry_id = ANY($1) ORDER BY leg_id"#,
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        for leg in legs {
+            if let Some(entry) = entries
+                .iter_mut()
+                .find(|entry| entry.entry_id == Some(leg.entry_id))
+            {
+                entry.legs.push(leg);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Cash the account got from anything but trades: deposits, withdrawals, dividends and
+    /// the like. Reconciliation corrections are left out, they follow the history rather
+    /// than make it.
+    pub async fn cash_outside_trades(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        account_id: i32,
+    ) -> Result<Money, sqlx::Error> {
+        let row: (Money,) = sqlx::query_as(
+            r#"SELECT COALESCE(SUM(l.debit - l.credit), 0)::bigint
+            FROM journal_legs l JOIN journal_entries e ON e.entry_id = l.entry_id
+            WHERE e.account_id = $1 AND l.book = 'CASH'
+                AND e.kind NOT IN ('ORDER_DEBIT', 'SALE_CREDIT', 'FEE', 'RECONCILIATION')"#,
+        )
+        .bind(account_id)
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok(row.0)
+    }
+
+    /// Sum of the cash legs of the account, what its balance should be
+    pub async fn cash_balance(&self, account_id: i32) -> Result<Money, sqlx::Error> {
+        let row: (Money,) = sqlx::query_as(
+            r#"SELECT COALESCE(SUM(l.debit - l.credit), 0)::bigint
+            FROM journal_legs l JOIN journal_entries e ON e.entry_id = l.entry_id
+            WHERE e.account_id = $1 AND l.book = 'CASH'"#,
+        )
+        .bind(account_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.0)
+    }
+}
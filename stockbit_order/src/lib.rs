@@ -1,15 +1,31 @@
 pub mod account;
+pub mod algo;
+pub mod calendar;
 pub mod cfg;
 pub mod constant;
+pub mod corporate;
 pub mod db;
 pub mod error;
+pub mod fee;
+pub mod funding;
+pub mod gateway;
+pub mod jobs;
+pub mod ledger;
 pub mod logging;
+pub mod matching;
 pub mod mdw;
+pub mod notify;
 pub mod order;
+pub mod pnl;
 pub mod portfolio;
 pub mod product;
+pub mod reconcile;
 pub mod redis;
+pub mod risk;
 pub mod server;
+pub mod settlement;
 pub mod socket;
 pub mod svc;
+pub mod trade;
+pub mod types;
 pub mod utils;
@@ -20,6 +20,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let server = Server::new(db_pool.clone(), redis_conn.clone());
 
+    // `reconcile` reports drift of positions and balances, `reconcile --apply` repairs it
+    if std::env::args().nth(1).as_deref() == Some("reconcile") {
+        let apply = std::env::args().any(|arg| arg == "--apply");
+        println!("{}", server.reconcile(apply).await?);
+        db_pool.close().await;
+        return Ok(());
+    }
+
     let server_handle = tokio::spawn(async move {
         let _ = server.start(shutdown_rx).await;
     });
@@ -0,0 +1,178 @@
+use super::book::OrderBook;
+use crate::order::model::Side;
+use crate::types::Price;
+
+/// Price a call auction uncrosses at and what it would trade there. The whole book is
+/// counted, so the shares are wider than a `Quantity`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Equilibrium {
+    pub price: Price,
+    /// Shares that execute at `price`
+    pub volume: i64,
+    /// Shares left unmatched on the heavier side at `price`
+    pub imbalance: i64,
+}
+
+/// Indicative equilibrium price (IEP) of the book. Among the limit prices on the book it
+/// picks the one that, in order:
+/// 1. matches the most shares,
+/// 2. leaves the smallest imbalance,
+/// 3. is closest to `reference`, when there is one,
+/// 4. is the lowest.
+///
+/// Returns `None` when no bid crosses any ask.
+pub fn equilibrium(book: &OrderBook, reference: Option<Price>) -> Option<Equilibrium> {
+    let bids = book.depth(Side::Buy);
+    let asks = book.depth(Side::Sell);
+    let mut candidates: Vec<Price> = bids.iter().chain(&asks).map(|(price, _)| *price).collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    candidates
+        .into_iter()
+        .map(|price| {
+            let demand: i64 = bids
+                .iter()
+                .filter(|(p, _)| *p >= price)
+                .map(|(_, shares)| shares)
+                .sum();
+            let supply: i64 = asks
+                .iter()
+                .filter(|(p, _)| *p <= price)
+                .map(|(_, shares)| shares)
+                .sum();
+            Equilibrium {
+                price,
+                volume: demand.min(supply),
+                imbalance: demand.max(supply) - demand.min(supply),
+            }
+        })
+        .filter(|e| e.volume > 0)
+        // candidates ascend, so `min_by_key` keeps the lowest price on a full tie
+        .min_by_key(|e| {
+            let distance = reference.map_or(0, |reference| e.price.abs_diff(reference));
+            (-e.volume, e.imbalance, distance)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::book::RestingOrder;
+    use crate::types::Quantity;
+
+    /// Book of `(price, shares)` bids and asks
+    fn book(bids: &[(i32, i32)], asks: &[(i32, i32)]) -> OrderBook {
+        let mut book = OrderBook::default();
+        let sides = [(Side::Buy, bids), (Side::Sell, asks)];
+        for (order_id, (side, price, quantity)) in sides
+            .iter()
+            .flat_map(|(side, orders)| orders.iter().map(move |(p, q)| (*side, *p, *q)))
+            .enumerate()
+        {
+            book.rest(
+                side,
+                RestingOrder {
+                    order_id: order_id as i32,
+                    user_id: 1,
+                    price: Price::new(price),
+                    quantity: Quantity::new(quantity),
+                },
+            );
+        }
+        book
+    }
+
+    fn at(price: i32, volume: i64, imbalance: i64) -> Option<Equilibrium> {
+        Some(Equilibrium {
+            price: Price::new(price),
+            volume,
+            imbalance,
+        })
+    }
+
+    #[test]
+    fn most_volume_wins_over_imbalance_and_reference() {
+        // 1000 matches 100 with 100 left over, 1010 matches 200 with 200 left over
+        let book = book(&[(1010, 200)], &[(1000, 100), (1010, 300)]);
+
+        assert_eq!(
+            equilibrium(&book, Some(Price::new(1000))),
+            at(1010, 200, 200)
+        );
+    }
+
+    #[test]
+    fn smallest_imbalance_breaks_a_volume_tie() {
+        // both match 100, 1000 leaves 200 over and 1010 leaves 50
+        let book = book(&[(1010, 100), (1000, 200)], &[(1000, 100), (1010, 50)]);
+
+        assert_eq!(
+            equilibrium(&book, Some(Price::new(1000))),
+            at(1010, 100, 50)
+        );
+    }
+
+    #[test]
+    fn closest_to_reference_breaks_an_imbalance_tie() {
+        // both match 100 and leave 100 over
+        let book = book(&[(1010, 100), (1000, 100)], &[(1000, 100), (1010, 100)]);
+
+        assert_eq!(
+            equilibrium(&book, Some(Price::new(1010))),
+            at(1010, 100, 100)
+        );
+        assert_eq!(
+            equilibrium(&book, Some(Price::new(1006))),
+            at(1010, 100, 100)
+        );
+        assert_eq!(
+            equilibrium(&book, Some(Price::new(1004))),
+            at(1000, 100, 100)
+        );
+    }
+
+    #[test]
+    fn lowest_price_breaks_a_full_tie() {
+        let book = book(&[(1010, 100), (1000, 100)], &[(1000, 100), (1010, 100)]);
+
+        assert_eq!(equilibrium(&book, None), at(1000, 100, 100));
+        assert_eq!(
+            equilibrium(&book, Some(Price::new(1005))),
+            at(1000, 100, 100)
+        );
+    }
+
+    #[test]
+    fn book_that_does_not_cross_has_no_equilibrium() {
+        assert_eq!(
+            equilibrium(&book(&[(1000, 100)], &[(1010, 100)]), None),
+            None
+        );
+        assert_eq!(equilibrium(&book(&[(1000, 100)], &[]), None), None);
+        assert_eq!(equilibrium(&OrderBook::default(), None), None);
+    }
+
+    #[test]
+    fn book_deeper_than_one_order_still_counts() {
+        let max = Quantity::MAX.get();
+        let book = book(&[(1000, max), (1000, max)], &[(1000, max), (990, max)]);
+
+        let both = 2 * i64::from(max);
+        assert_eq!(equilibrium(&book, None), at(1000, both, 0));
+    }
+
+    #[test]
+    fn uncross_trades_everything_crossing_at_the_equilibrium() {
+        let mut book = book(&[(1010, 200)], &[(1000, 100), (1010, 300)]);
+        let price = equilibrium(&book, None).expect("book crosses").price;
+
+        let crosses = book.uncross(price);
+
+        assert!(crosses.iter().all(|cross| cross.price == Price::new(1010)));
+        let volume: Quantity = crosses.iter().map(|cross| cross.quantity).sum();
+        assert_eq!(volume, Quantity::new(200));
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.depth(Side::Sell), vec![(Price::new(1010), 200)]);
+    }
+}
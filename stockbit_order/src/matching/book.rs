@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::order::model::{Order, Side};
+use crate::types::{Price, Quantity};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestingOrder {
+    pub order_id: i32,
+    pub user_id: i32,
+    pub price: Price,
+    /// Shares still open on the book
+    pub quantity: Quantity,
+}
+
+impl From<&Order> for RestingOrder {
+    fn from(order: &Order) -> Self {
+        Self {
+            order_id: order.order_id.unwrap_or_default(),
+            user_id: order.user_id,
+            price: order.price,
+            quantity: order.remaining_quantity(),
+        }
+    }
+}
+
+/// Execution between an incoming order and one resting order, at the resting price
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fill {
+    pub maker_order_id: i32,
+    pub maker_user_id: i32,
+    pub price: Price,
+    pub quantity: Quantity,
+}
+
+/// Execution between a buy and a sell order at the call auction price
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cross {
+    pub buy_order_id: i32,
+    pub sell_order_id: i32,
+    pub price: Price,
+    pub quantity: Quantity,
+}
+
+/// Price-time priority limit order book of a single product on one board.
+/// Each price level is FIFO, the best level is matched first.
+#[derive(Clone, Debug, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, VecDeque<RestingOrder>>,
+    asks: BTreeMap<Price, VecDeque<RestingOrder>>,
+    last_price: Option<Price>,
+}
+
+impl OrderBook {
+    /// Matches the order against the opposite side and rests whatever is left
+    pub fn submit(&mut self, side: Side, mut order: RestingOrder) -> Vec<Fill> {
+        let fills = self.match_order(side, &mut order);
+        if order.quantity > Quantity::ZERO {
+            self.rest(side, order);
+        }
+        fills
+    }
+
+    /// Takes liquidity from the opposite side while prices cross, `order.quantity` is reduced
+    /// by what got filled
+    pub fn match_order(&mut self, side: Side, order: &mut RestingOrder) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        while order.quantity > Quantity::ZERO {
+            let level_price = match side {
+                Side::Buy => self.best_ask().filter(|ask| *ask <= order.price),
+                Side::Sell => self.best_bid().filter(|bid| *bid >= order.price),
+            };
+            let Some(level_price) = level_price else {
+                break;
+            };
+            let levels = match side {
+                Side::Buy => &mut self.asks,
+                Side::Sell => &mut self.bids,
+            };
+            let level = levels.get_mut(&level_price).expect("best level exists");
+            while order.quantity > Quantity::ZERO {
+                let Some(maker) = level.front_mut() else {
+                    break;
+                };
+                let quantity = order.quantity.min(maker.quantity);
+                fills.push(Fill {
+                    maker_order_id: maker.order_id,
+                    maker_user_id: maker.user_id,
+                    price: level_price,
+                    quantity,
+                });
+                order.quantity -= quantity;
+                maker.quantity -= quantity;
+                if maker.quantity.is_zero() {
+                    level.pop_front();
+                }
+            }
+            if level.is_empty() {
+                levels.remove(&level_price);
+            }
+            self.last_price = Some(level_price);
+        }
+        fills
+    }
+
+    /// Executes every crossing order at the single auction `price`, bids from the highest
+    /// and asks from the lowest, each level in time priority
+    pub fn uncross(&mut self, price: Price) -> Vec<Cross> {
+        let mut crosses = Vec::new();
+        loop {
+            let bid_price = self.best_bid().filter(|bid| *bid >= price);
+            let ask_price = self.best_ask().filter(|ask| *ask <= price);
+            let (Some(bid_price), Some(ask_price)) = (bid_price, ask_price) else {
+                break;
+            };
+            let bid_level = self.bids.get_mut(&bid_price).expect("best level exists");
+            let ask_level = self.asks.get_mut(&ask_price).expect("best level exists");
+            let bid = bid_level.front_mut().expect("levels are never empty");
+            let ask = ask_level.front_mut().expect("levels are never empty");
+            let quantity = bid.quantity.min(ask.quantity);
+            crosses.push(Cross {
+                buy_order_id: bid.order_id,
+                sell_order_id: ask.order_id,
+                price,
+                quantity,
+            });
+            bid.quantity -= quantity;
+            ask.quantity -= quantity;
+            if bid.quantity.is_zero() {
+                bid_level.pop_front();
+            }
+            if ask.quantity.is_zero() {
+                ask_level.pop_front();
+            }
+            if bid_level.is_empty() {
+                self.bids.remove(&bid_price);
+            }
+            if ask_level.is_empty() {
+                self.asks.remove(&ask_price);
+            }
+            self.last_price = Some(price);
+        }
+        crosses
+    }
+
+    pub fn rest(&mut self, side: Side, order: RestingOrder) {
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        levels.entry(order.price).or_default().push_back(order);
+    }
+
+    /// Removes a resting order, returns it when it was on the book
+    pub fn cancel(&mut self, order_id: i32) -> Option<RestingOrder> {
+        for levels in [&mut self.bids, &mut self.asks] {
+            let found = levels.iter_mut().find_map(|(price, level)| {
+                let index = level.iter().position(|o| o.order_id == order_id)?;
+                Some((*price, index))
+            });
+            if let Some((price, index)) = found {
+                let level = levels.get_mut(&price).expect("level exists");
+                let removed = level.remove(index);
+                if level.is_empty() {
+                    levels.remove(&price);
+                }
+                return removed;
+            }
+        }
+        None
+    }
+
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bids.keys().next_back().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<Price> {
+        self.asks.keys().next().copied()
+    }
+
+    /// Total open shares per price level of one side, in ascending price. Summed wide, a
+    /// level holds more shares than any one order can.
+    pub fn depth(&self, side: Side) -> Vec<(Price, i64)> {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels
+            .iter()
+            .map(|(price, level)| {
+                (
+                    *price,
+                    level.iter().map(|o| i64::from(o.quantity.get())).sum(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn last_price(&self) -> Option<Price> {
+        self.last_price
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_id: i32, price: i32, quantity: i32) -> RestingOrder {
+        RestingOrder {
+            order_id,
+            user_id: order_id * 10,
+            price: Price::new(price),
+            quantity: Quantity::new(quantity),
+        }
+    }
+
+    fn fill(maker_order_id: i32, price: i32, quantity: i32) -> Fill {
+        Fill {
+            maker_order_id,
+            maker_user_id: maker_order_id * 10,
+            price: Price::new(price),
+            quantity: Quantity::new(quantity),
+        }
+    }
+
+    fn levels(levels: &[(i32, i64)]) -> Vec<(Price, i64)> {
+        levels
+            .iter()
+            .map(|(price, shares)| (Price::new(*price), *shares))
+            .collect()
+    }
+
+    #[test]
+    fn best_price_fills_first_then_earliest_order() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Sell, order(1, 1010, 100));
+        book.rest(Side::Sell, order(2, 1000, 100));
+        book.rest(Side::Sell, order(3, 1000, 100));
+
+        let fills = book.submit(Side::Buy, order(4, 1010, 250));
+
+        assert_eq!(
+            fills,
+            vec![fill(2, 1000, 100), fill(3, 1000, 100), fill(1, 1010, 50)]
+        );
+        assert_eq!(book.depth(Side::Sell), levels(&[(1010, 50)]));
+        assert_eq!(book.last_price(), Some(Price::new(1010)));
+    }
+
+    #[test]
+    fn fills_at_the_resting_price() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Buy, order(1, 1000, 100));
+
+        let fills = book.submit(Side::Sell, order(2, 990, 100));
+
+        assert_eq!(fills, vec![fill(1, 1000, 100)]);
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn partial_fill_across_levels_rests_the_remainder() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Buy, order(1, 1000, 100));
+        book.rest(Side::Buy, order(2, 995, 100));
+        book.rest(Side::Buy, order(3, 990, 100));
+
+        let fills = book.submit(Side::Sell, order(4, 995, 300));
+
+        assert_eq!(fills, vec![fill(1, 1000, 100), fill(2, 995, 100)]);
+        assert_eq!(book.depth(Side::Buy), levels(&[(990, 100)]));
+        assert_eq!(book.depth(Side::Sell), levels(&[(995, 100)]));
+        assert_eq!(book.best_ask(), Some(Price::new(995)));
+    }
+
+    #[test]
+    fn partially_filled_maker_keeps_its_place() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Sell, order(1, 1000, 300));
+        book.rest(Side::Sell, order(2, 1000, 100));
+
+        book.submit(Side::Buy, order(3, 1000, 100));
+        let fills = book.submit(Side::Buy, order(4, 1000, 300));
+
+        assert_eq!(fills, vec![fill(1, 1000, 200), fill(2, 1000, 100)]);
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn order_that_does_not_cross_rests() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Sell, order(1, 1010, 100));
+
+        let fills = book.submit(Side::Buy, order(2, 1000, 100));
+
+        assert!(fills.is_empty());
+        assert_eq!(book.best_bid(), Some(Price::new(1000)));
+        assert_eq!(book.best_ask(), Some(Price::new(1010)));
+        assert_eq!(book.last_price(), None);
+    }
+
+    #[test]
+    fn match_order_never_rests() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Sell, order(1, 1000, 100));
+        let mut incoming = order(2, 1000, 300);
+
+        let fills = book.match_order(Side::Buy, &mut incoming);
+
+        assert_eq!(fills, vec![fill(1, 1000, 100)]);
+        assert_eq!(incoming.quantity, Quantity::new(200));
+        assert_eq!(book.best_bid(), None);
+    }
+
+    #[test]
+    fn cancel_removes_the_order_and_its_empty_level() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Buy, order(1, 1000, 100));
+        book.rest(Side::Buy, order(2, 1000, 200));
+        book.rest(Side::Buy, order(3, 995, 100));
+
+        assert_eq!(book.cancel(1), Some(order(1, 1000, 100)));
+        assert_eq!(book.cancel(3), Some(order(3, 995, 100)));
+        assert_eq!(book.cancel(3), None);
+        assert_eq!(book.depth(Side::Buy), levels(&[(1000, 200)]));
+
+        let fills = book.submit(Side::Sell, order(4, 1000, 200));
+        assert_eq!(fills, vec![fill(2, 1000, 200)]);
+    }
+
+    #[test]
+    fn depth_sums_each_level_in_ascending_price() {
+        let mut book = OrderBook::default();
+        book.rest(Side::Buy, order(1, 995, 100));
+        book.rest(Side::Buy, order(2, 1000, 100));
+        book.rest(Side::Buy, order(3, 995, 300));
+        book.rest(Side::Sell, order(4, 1010, 500));
+
+        assert_eq!(book.depth(Side::Buy), levels(&[(995, 400), (1000, 100)]));
+        assert_eq!(book.depth(Side::Sell), levels(&[(1010, 500)]));
+    }
+}
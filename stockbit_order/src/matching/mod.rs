@@ -0,0 +1,40 @@
+pub mod auction;
+pub mod book;
+pub mod trigger;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::order::model::{Board, Order};
+use book::{OrderBook, RestingOrder};
+
+type SharedBook = Arc<Mutex<OrderBook>>;
+
+/// Order books of every product and board, each behind its own lock so symbols and boards
+/// match independently
+#[derive(Default)]
+pub struct MatchingEngine {
+    books: Mutex<HashMap<(i32, Board), SharedBook>>,
+}
+
+impl MatchingEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn book(&self, product_id: i32, board: Board) -> SharedBook {
+        let mut books = self.books.lock().await;
+        Arc::clone(books.entry((product_id, board)).or_default())
+    }
+
+    /// Puts working orders back on their books, `orders` must come in arrival order
+    pub async fn restore(&self, orders: &[Order]) {
+        for order in orders {
+            let book = self.book(order.product_id, order.board).await;
+            book.lock()
+                .await
+                .rest(order.side, RestingOrder::from(order));
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use tokio::sync::{Mutex, Notify};
+
+use crate::order::model::Order;
+use crate::types::Price;
+
+/// Stop orders waiting for their trigger price, per product, together with the prices
+/// the matcher printed since they were last checked
+#[derive(Default)]
+pub struct TriggerStore {
+    stops: Mutex<HashMap<i32, Vec<Order>>>,
+    traded: Mutex<HashMap<i32, Price>>,
+    wake: Notify,
+}
+
+impl TriggerStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watches a stop order, `order` must already be persisted
+    pub async fn arm(&self, order: Order) {
+        let mut stops = self.stops.lock().await;
+        stops.entry(order.product_id).or_default().push(order);
+    }
+
+    /// Stops watching an order, returns it when it was armed
+    pub async fn remove(&self, order_id: i32) -> Option<Order> {
+        let mut stops = self.stops.lock().await;
+        for orders in stops.values_mut() {
+            if let Some(index) = orders.iter().position(|o| o.order_id == Some(order_id)) {
+                return Some(orders.remove(index));
+            }
+        }
+        None
+    }
+
+    /// Products with armed stops and their symbol
+    pub async fn watched(&self) -> Vec<(i32, String)> {
+        let stops = self.stops.lock().await;
+        stops
+            .iter()
+            .filter_map(|(product_id, orders)| {
+                let order = orders.first()?;
+                Some((*product_id, order.product_symbol.clone()))
+            })
+            .collect()
+    }
+
+    /// Records a trade printed by the matcher and wakes the watcher
+    pub async fn traded(&self, product_id: i32, price: Price) {
+        self.traded.lock().await.insert(product_id, price);
+        self.wake.notify_one();
+    }
+
+    pub async fn wait_for_trade(&self) {
+        self.wake.notified().await
+    }
+
+    /// Last traded price per product since the previous call
+    pub async fn take_traded(&self) -> HashMap<i32, Price> {
+        std::mem::take(&mut *self.traded.lock().await)
+    }
+
+    /// Moves the trailing stops of a product along with `last_price`, returns the ones
+    /// whose stop price changed
+    pub async fn trail(&self, product_id: i32, last_price: Price) -> Vec<Order> {
+        let mut stops = self.stops.lock().await;
+        let Some(orders) = stops.get_mut(&product_id) else {
+            return Vec::new();
+        };
+        orders
+            .iter_mut()
+            .filter_map(|order| order.trail(last_price).then(|| order.clone()))
+            .collect()
+    }
+
+    /// Removes and returns the stops `last_price` triggers, in arrival order
+    pub async fn take_triggered(&self, product_id: i32, last_price: Price) -> Vec<Order> {
+        let mut stops = self.stops.lock().await;
+        let Some(orders) = stops.get_mut(&product_id) else {
+            return Vec::new();
+        };
+        let (triggered, waiting) = orders
+            .drain(..)
+            .partition(|order| order.is_triggered_by(last_price));
+        *orders = waiting;
+        if orders.is_empty() {
+            stops.remove(&product_id);
+        }
+        triggered
+    }
+}
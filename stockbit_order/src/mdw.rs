@@ -1,23 +1,20 @@
 use anyhow::{Context, Result, anyhow};
 use auth_validate::jwt::verify_jwt;
 use request_http_parser::parser::{Method, Request};
+use subtle::ConstantTimeEq;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
 use crate::cfg::CONFIG;
-use crate::constant::{BAD_REQUEST, UNAUTHORIZED};
-use crate::utils::extract_token;
+use crate::constant::{BAD_REQUEST, CONTINUE, METHOD_OVERRIDE, SIGNATURE, UNAUTHORIZED};
+use crate::utils::{extract_token, verify_signature};
 
 pub struct Middleware {}
 
 impl Middleware {
+    #[allow(clippy::new_ret_no_self)]
     pub async fn new(stream: &mut TcpStream) -> Result<(Request, i32)> {
-        let mut buffer = [0; 1024];
-        let size = stream
-            .read(&mut buffer)
-            .await
-            .context("Failed to read stream")?;
-        if size >= 1024 {
+        let Some(buffer) = Self::read_request(stream).await? else {
             let _ = stream
                 .write_all(format!("{}{}", BAD_REQUEST, "Requets too large").as_bytes())
                 .await
@@ -26,9 +23,13 @@ impl Middleware {
             let _ = stream.flush().await.context("Failed to flush");
 
             return Err(anyhow!("request too large"));
-        }
-        let req_str = String::from_utf8_lossy(&buffer[..size]);
-        let request = match Request::new(&req_str) {
+        };
+        let req_str = String::from_utf8_lossy(&buffer);
+        let (req_str, deleting) = match req_str.strip_prefix("DELETE ") {
+            Some(rest) => (format!("POST {}", rest), true),
+            None => (req_str.to_string(), false),
+        };
+        let mut request = match Request::new(&req_str) {
             Ok(req) => req,
             Err(e) => {
                 println!("{}", e);
@@ -41,40 +42,50 @@ impl Middleware {
                 return Err(anyhow!("request format invalid"));
             }
         };
+        if deleting {
+            request
+                .headers
+                .insert(METHOD_OVERRIDE.to_string(), "DELETE".to_string());
+        }
         // TODO handle non protected path
-        if request.path == "/order" && request.method == Method::POST {
+        if request.path == "/order" && request.method == Method::POST && !deleting {
             return Ok((request, 0));
         }
+        if request.path == "/market/status" && request.method == Method::GET {
+            return Ok((request, 0));
+        }
+        // reached by the back office and the payment gateway with credentials of their
+        // own, not by users
+        let internal = match request.path.as_str() {
+            "/internal/cash/review" => Some(Self::is_admin(&request)),
+            "/gateway/callback" => Some(Self::is_signed_by_gateway(&request)),
+            _ => None,
+        };
+        if let Some(authorized) = internal.filter(|_| request.method == Method::POST && !deleting) {
+            if authorized {
+                return Ok((request, 0));
+            }
+            stream
+                .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
+                .await?;
+            return Err(anyhow!("internal route unathorized"));
+        }
 
-        let token_opt: Option<String>;
         // ws
-        if request.path.contains("ws") {
-            token_opt = match &request.params {
-                Some(params) => match params.get("token") {
-                    Some(token) => Some(token.to_string()),
-                    _ => None,
-                },
+        let token_opt: Option<String> = if request.path.contains("ws") {
+            match &request.params {
+                Some(params) => params.get("token").map(|token| token.to_string()),
                 _ => None,
-            };
+            }
         // non ws
         } else {
-            token_opt = match extract_token(&request.headers) {
-                Some(token) => Some(token),
-                _ => None,
-            };
-        }
+            extract_token(&request.headers)
+        };
         let token = match token_opt {
             Some(token) => token,
             None => {
                 stream
-                    .write_all(
-                        format!(
-                            "{}{}",
-                            UNAUTHORIZED.to_string(),
-                            "401 unathorized".to_string()
-                        )
-                        .as_bytes(),
-                    )
+                    .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
                     .await?;
                 return Err(anyhow!("extract token error"));
             }
@@ -84,14 +95,7 @@ impl Middleware {
             Ok(user_id) => user_id,
             Err(_) => {
                 stream
-                    .write_all(
-                        format!(
-                            "{}{}",
-                            UNAUTHORIZED.to_string(),
-                            "401 unathorized".to_string()
-                        )
-                        .as_bytes(),
-                    )
+                    .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
                     .await?;
                 return Err(anyhow!("token unathorized"));
             }
@@ -101,4 +105,83 @@ impl Middleware {
             user_id.parse::<i32>().expect("error parsed user_id"),
         ))
     }
+
+    /// Whether the request carries the back office's `admin_token`
+    fn is_admin(request: &Request) -> bool {
+        match (&CONFIG.admin_token, extract_token(&request.headers)) {
+            (Some(admin_token), Some(token)) => {
+                admin_token.as_bytes().ct_eq(token.as_bytes()).into()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the body is signed with the payment gateway's `gateway_secret`
+    fn is_signed_by_gateway(request: &Request) -> bool {
+        match (&CONFIG.gateway_secret, request.headers.get(SIGNATURE)) {
+            (Some(secret), Some(signature)) => verify_signature(
+                secret,
+                request.body.as_deref().unwrap_or_default(),
+                signature,
+            ),
+            _ => false,
+        }
+    }
+
+    /// Reads one request, its head up to the blank line and then `Content-Length` bytes
+    /// of body. `None` when it is longer than `max_request_bytes`.
+    async fn read_request(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        let mut continued = false;
+        loop {
+            match Self::request_len(&buffer) {
+                Some((len, _)) if len > CONFIG.max_request_bytes => return Ok(None),
+                Some((len, _)) if buffer.len() >= len => {
+                    buffer.truncate(len);
+                    return Ok(Some(buffer));
+                }
+                // curl holds bodies over 1KB back until told to go on
+                Some((_, true)) if !continued => {
+                    stream
+                        .write_all(CONTINUE.as_bytes())
+                        .await
+                        .context("Failed to write")?;
+                    continued = true;
+                }
+                None if buffer.len() > CONFIG.max_request_bytes => return Ok(None),
+                _ => {}
+            }
+            let size = stream
+                .read(&mut chunk)
+                .await
+                .context("Failed to read stream")?;
+            if size == 0 {
+                // closed before the request was complete, the parser rejects what came
+                return Ok(Some(buffer));
+            }
+            buffer.extend_from_slice(&chunk[..size]);
+        }
+    }
+
+    /// Full length of the request in `buffer` once its head is in, and whether the client
+    /// waits for `100 Continue` before sending the body
+    fn request_len(buffer: &[u8]) -> Option<(usize, bool)> {
+        let head_len = buffer.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+        let head = String::from_utf8_lossy(&buffer[..head_len]);
+        let mut body_len = 0;
+        let mut expect_continue = false;
+        for (name, value) in head.lines().filter_map(|line| line.split_once(':')) {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                body_len = value.parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("expect") {
+                expect_continue = value.eq_ignore_ascii_case("100-continue");
+            }
+        }
+        Some((
+            head_len + body_len,
+            expect_continue && buffer.len() == head_len,
+        ))
+    }
 }
@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// Open websocket connections per user, used to push messages the user did not ask for
+#[derive(Default)]
+pub struct Notifier {
+    clients: Mutex<HashMap<i32, Vec<UnboundedSender<String>>>>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn subscribe(&self, user_id: i32) -> UnboundedReceiver<String> {
+        let (tx, rx) = unbounded_channel();
+        self.clients
+            .lock()
+            .await
+            .entry(user_id)
+            .or_default()
+            .push(tx);
+        rx
+    }
+
+    /// Sends to every connection of the user, connections that went away are dropped
+    pub async fn publish(&self, user_id: i32, message: &str) {
+        let mut clients = self.clients.lock().await;
+        if let Some(senders) = clients.get_mut(&user_id) {
+            senders.retain(|tx| tx.send(message.to_string()).is_ok());
+            if senders.is_empty() {
+                clients.remove(&user_id);
+            }
+        }
+    }
+}
@@ -1,19 +1,60 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::algo::model::{AlgoControlForm, AlgoForm};
+use crate::calendar;
+use crate::error::{OrderError, Rejection};
+use crate::fee::Fees;
+use crate::types::{Price, Quantity};
 
 /* TODO product save in redis*/
-#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug, Clone)]
 pub struct Order {
     pub order_id: Option<i32>,
     pub product_symbol: String,
     pub product_name: String,
-    pub side: char,
-    pub price: i32,
-    pub lot: i32,
+    #[sqlx(try_from = "String")]
+    pub side: Side,
+    pub price: Price,
+    /// Shares ordered
+    pub quantity: Quantity,
+    #[sqlx(try_from = "String")]
     pub expiry: Expiry,
     pub created_at: DateTime<Utc>,
     pub user_id: i32,
     pub product_id: i32,
+    #[sqlx(try_from = "String")]
+    pub status: OrderStatus,
+    pub filled_quantity: Quantity,
+    #[sqlx(try_from = "String")]
+    pub order_type: OrderType,
+    /// Average execution price, none until the first fill
+    pub avg_price: Option<Decimal>,
+    /// Last traded price that releases a STOP or STOP_LIMIT order
+    pub stop_price: Option<Price>,
+    /// Order this one was released by, e.g. a triggered stop
+    pub parent_order_id: Option<i32>,
+    /// Distance a trailing stop keeps from the best price, in rupiah
+    pub trail_amount: Option<Price>,
+    /// Distance a trailing stop keeps from the best price, in percent of it
+    pub trail_percent: Option<Decimal>,
+    /// Best price a trailing stop has seen: the high for sells, the low for buys
+    pub high_water_mark: Option<Price>,
+    /// Last price that triggered a stop order
+    pub triggered_price: Option<Price>,
+    /// Group the order belongs to, its `parent_order_id` points at the first order of it
+    #[sqlx(try_from = "String")]
+    pub link_type: LinkType,
+    /// Algo order this one is a slice of
+    pub algo_id: Option<i32>,
+    /// Fees charged over all fills so far
+    #[sqlx(flatten)]
+    pub fees: Fees,
+    /// Board the order trades on, each board has its own book
+    #[sqlx(try_from = "String")]
+    pub board: Board,
 }
 
 impl Order {
@@ -22,58 +63,560 @@ impl Order {
         user_id: i32,
         product_id: i32,
         product_name: &str,
-    ) -> Result<Order, anyhow::Error> {
+    ) -> Result<Order, OrderError> {
         Ok(Self {
             order_id: None,
             product_symbol: order_form.symbol.to_string(),
             product_name: product_name.to_string(),
-            side: order_form.side,
-            price: order_form.price as i32,
-            lot: order_form.lot as i32,
-            expiry: order_form.expiry.as_str().try_into()?,
+            side: order_form.side.try_into()?,
+            price: order_form.price,
+            quantity: order_form.quantity,
+            expiry: order_form.expiry()?,
             created_at: Utc::now(),
             user_id,
             product_id,
+            status: OrderStatus::New,
+            filled_quantity: Quantity::ZERO,
+            order_type: order_form.order_type,
+            avg_price: None,
+            stop_price: order_form
+                .stop_price
+                .filter(|_| order_form.order_type.is_stop()),
+            parent_order_id: None,
+            trail_amount: order_form
+                .trail_amount
+                .filter(|_| order_form.order_type == OrderType::TrailingStop),
+            trail_percent: order_form
+                .trail_percent
+                .filter(|_| order_form.order_type == OrderType::TrailingStop),
+            high_water_mark: None,
+            triggered_price: None,
+            link_type: LinkType::Single,
+            algo_id: None,
+            fees: Fees::default(),
+            board: order_form.board,
         })
     }
+
+    /// Take-profit or stop-loss leg of a bracket around `self` as the entry. It closes the
+    /// entry's position, so it waits INACTIVE until the entry is done and then stays good
+    /// till cancelled.
+    pub fn bracket_leg(
+        &self,
+        order_type: OrderType,
+        price: Price,
+        stop_price: Option<Price>,
+    ) -> Order {
+        Order {
+            order_id: None,
+            side: match self.side {
+                Side::Buy => Side::Sell,
+                Side::Sell => Side::Buy,
+            },
+            price,
+            created_at: Utc::now(),
+            status: OrderStatus::Inactive,
+            filled_quantity: Quantity::ZERO,
+            order_type,
+            avg_price: None,
+            stop_price,
+            parent_order_id: self.order_id,
+            trail_amount: None,
+            trail_percent: None,
+            high_water_mark: None,
+            triggered_price: None,
+            link_type: LinkType::Bracket,
+            expiry: Expiry::GTC,
+            algo_id: None,
+            fees: Fees::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Moves a trailing stop along with `last_price`: the mark follows the market in the
+    /// order's favour and the stop price keeps the trail distance from it.
+    /// Returns whether the stop price moved.
+    pub fn trail(&mut self, last_price: Price) -> bool {
+        if self.order_type != OrderType::TrailingStop {
+            return false;
+        }
+        let mark = match (self.side, self.high_water_mark) {
+            (Side::Sell, Some(mark)) => mark.max(last_price),
+            (Side::Buy, Some(mark)) => mark.min(last_price),
+            (_, None) => last_price,
+        };
+        let distance = match (self.trail_amount, self.trail_percent) {
+            (Some(amount), _) => amount,
+            (None, Some(percent)) => {
+                Price::from_decimal(Decimal::from(mark) * percent / Decimal::ONE_HUNDRED)
+                    .unwrap_or_default()
+            }
+            (None, None) => return false,
+        };
+        let stop_price = match self.side {
+            Side::Sell => mark.checked_sub(distance),
+            Side::Buy => mark.checked_add(distance),
+        };
+        let Some(stop_price) = stop_price else {
+            return false;
+        };
+        self.high_water_mark = Some(mark);
+        if self.stop_price == Some(stop_price) {
+            return false;
+        }
+        self.stop_price = Some(stop_price);
+        true
+    }
+
+    /// Entry of a bracket, its exits point at it through `parent_order_id`
+    pub fn is_bracket_entry(&self) -> bool {
+        self.link_type == LinkType::Bracket && self.parent_order_id.is_none()
+    }
+
+    /// Whether `last_price` reaches the stop price: at or above it for buys,
+    /// at or below it for sells
+    pub fn is_triggered_by(&self, last_price: Price) -> bool {
+        match (self.side, self.stop_price) {
+            (Side::Buy, Some(stop)) => last_price >= stop,
+            (Side::Sell, Some(stop)) => last_price <= stop,
+            (_, None) => false,
+        }
+    }
+
+    /// Order a triggered stop releases into the market: a market order for STOP and
+    /// TRAILING_STOP, a limit order at the stop's limit price for STOP_LIMIT
+    pub fn triggered(&self) -> Order {
+        let order_type = match self.order_type {
+            OrderType::StopLimit => OrderType::Limit,
+            _ => OrderType::Market,
+        };
+        Order {
+            order_id: None,
+            created_at: Utc::now(),
+            status: OrderStatus::New,
+            filled_quantity: Quantity::ZERO,
+            order_type,
+            avg_price: None,
+            stop_price: None,
+            parent_order_id: self.order_id,
+            trail_amount: None,
+            trail_percent: None,
+            high_water_mark: None,
+            triggered_price: None,
+            link_type: LinkType::Single,
+            algo_id: None,
+            fees: Fees::default(),
+            ..self.clone()
+        }
+    }
+
+    pub fn remaining_quantity(&self) -> Quantity {
+        self.quantity - self.filled_quantity
+    }
+
+    /// Moves the order to `next`, refusing transitions the lifecycle does not allow
+    pub fn transition(&mut self, next: OrderStatus) -> Result<(), OrderError> {
+        if !self.status.can_transition_to(next) {
+            return Err(OrderError::InvalidTransition(self.status, next));
+        }
+        self.status = next;
+        Ok(())
+    }
+
+    /// Records an execution of `quantity` shares at `price` against the order
+    pub fn fill(&mut self, price: Price, quantity: Quantity) -> Result<(), OrderError> {
+        if quantity <= Quantity::ZERO || quantity > self.remaining_quantity() {
+            return Err(OrderError::BadRequest);
+        }
+        let new_filled_quantity = self
+            .filled_quantity
+            .checked_add(quantity)
+            .ok_or(OrderError::OVERFLOW)?;
+        let next = if new_filled_quantity == self.quantity {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+        self.transition(next)?;
+        let filled_value = self.avg_price.unwrap_or_default() * Decimal::from(self.filled_quantity);
+        self.avg_price = Some(
+            (filled_value + Decimal::from(price) * Decimal::from(quantity))
+                / Decimal::from(new_filled_quantity),
+        );
+        self.filled_quantity = new_filled_quantity;
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct OrderForm {
     pub symbol: String,
     pub side: char,
-    pub price: u32,
-    pub lot: u32,
+    /// Ignored for market orders
+    #[serde(default)]
+    pub price: Price,
+    /// Shares, whole lots on the regular board
+    pub quantity: Quantity,
     pub expiry: String,
+    /// Last trading day of a `GTD` order, `"expiry": "GTD:YYYY-MM-DD"` works as well
+    #[serde(default)]
+    pub expiry_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub order_type: OrderType,
+    /// Trigger price of STOP and STOP_LIMIT orders
+    #[serde(default)]
+    pub stop_price: Option<Price>,
+    /// Trailing distance of a TRAILING_STOP in rupiah, give this or `trail_percent`
+    #[serde(default)]
+    pub trail_amount: Option<Price>,
+    /// Trailing distance of a TRAILING_STOP in percent
+    #[serde(default)]
+    pub trail_percent: Option<Decimal>,
+    #[serde(default)]
+    pub board: Board,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl OrderForm {
+    pub fn expiry(&self) -> Result<Expiry, OrderError> {
+        let expiry = match (self.expiry.as_str(), self.expiry_date) {
+            ("GTD", Some(date)) => Expiry::GTD(date),
+            (expiry, _) => expiry.try_into().map_err(|_| OrderError::InvalidExpiry)?,
+        };
+        if let Expiry::GTD(date) = expiry
+            && date < calendar::today()
+        {
+            return Err(OrderError::InvalidExpiry);
+        }
+        Ok(expiry)
+    }
+}
+
+/// Plain order placed over `POST /order` for `user_id`. That route takes no token, so it
+/// only places orders: cancels, linked orders, baskets and algos need the owner's token.
+#[derive(Serialize, Deserialize)]
 pub struct OrderFormServer {
-    pub symbol: String,
-    pub side: char,
-    pub price: u32,
-    pub lot: u32,
-    pub expiry: String,
+    #[serde(flatten)]
+    pub order: OrderForm,
     pub user_id: u32,
 }
 
+/// One-cancels-other pair on one symbol: the first leg to fill, trigger or be cancelled
+/// cancels the other
+#[derive(Serialize, Deserialize)]
+pub struct OcoForm {
+    pub oco: [OrderForm; 2],
+}
+
+/// Entry order with a take-profit limit and a stop-loss stop on the opposite side. Both
+/// legs activate for the filled quantity once the entry is done and then work as an OCO pair.
+#[derive(Serialize, Deserialize)]
+pub struct BracketForm {
+    pub bracket: OrderForm,
+    /// Limit price of the take-profit leg
+    pub take_profit: Price,
+    /// Stop price of the stop-loss leg
+    pub stop_loss: Price,
+}
+
+/// Pushed to the owner's websocket when an order changes outside their own request
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrderUpdate {
+    pub order_id: i32,
+    pub symbol: String,
+    pub status: OrderStatus,
+    pub filled_quantity: Quantity,
+    pub fees: Fees,
+}
+
+impl From<&Order> for OrderUpdate {
+    fn from(order: &Order) -> Self {
+        Self {
+            order_id: order.order_id.unwrap_or_default(),
+            symbol: order.product_symbol.clone(),
+            status: order.status,
+            filled_quantity: order.filled_quantity,
+            fees: order.fees,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CancelForm {
+    pub cancel: i32,
+}
+
+/// Orders sent together, placed one by one or, with `all_or_none`, all together or none
+#[derive(Serialize, Deserialize)]
+pub struct BasketForm {
+    pub basket: Vec<OrderForm>,
+    #[serde(default)]
+    pub all_or_none: bool,
+}
+
+/// Outcome of one order of a basket, listed in the order of the basket
+#[derive(Serialize, Deserialize)]
+pub struct BasketLeg {
+    pub symbol: String,
+    /// `ok`, `rejected` or `error`
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub order_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rejection: Option<Rejection>,
+}
+
+impl BasketLeg {
+    pub fn new(symbol: &str, result: Result<i32, OrderError>) -> Self {
+        let (status, order_id, rejection) = match result {
+            Ok(order_id) => ("ok", Some(order_id), None),
+            Err(why) if why.is_rejection() => ("rejected", None, Some(why.rejection())),
+            Err(_) => ("error", None, None),
+        };
+        BasketLeg {
+            symbol: symbol.to_string(),
+            status: status.to_string(),
+            order_id,
+            rejection,
+        }
+    }
+}
+
+/// Messages accepted on the order websocket, a plain `OrderForm` places an order
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OrderMessage {
+    Cancel(CancelForm),
+    AlgoControl(AlgoControlForm),
+    Algo(AlgoForm),
+    Oco(OcoForm),
+    Bracket(BracketForm),
+    Basket(BasketForm),
+    Place(OrderForm),
+}
+
 #[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct Orders {
+    pub order_id: i32,
     #[sqlx(rename = "product_symbol")]
     pub symbol: String,
     #[sqlx(rename = "product_name")]
     pub name: String,
     pub side: String,
-    pub price: i32,
-    pub lot: i32,
+    pub price: Price,
+    pub quantity: Quantity,
     pub expiry: String,
     pub created_at: DateTime<Utc>,
+    pub status: String,
+    pub filled_quantity: Quantity,
+    pub order_type: String,
+    pub avg_price: Option<Decimal>,
+    pub stop_price: Option<Price>,
+    pub parent_order_id: Option<i32>,
+    pub trail_amount: Option<Price>,
+    pub trail_percent: Option<Decimal>,
+    pub high_water_mark: Option<Price>,
+    pub triggered_price: Option<Price>,
+    pub link_type: String,
+    pub algo_id: Option<i32>,
+    #[sqlx(flatten)]
+    pub fees: Fees,
+    pub board: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderType {
+    #[default]
+    Limit,
+    Market,
+    /// Market order released once the last price reaches `stop_price`
+    Stop,
+    /// Limit order released once the last price reaches `stop_price`
+    StopLimit,
+    /// Market order released once the last price falls back from its best by the trail
+    TrailingStop,
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+impl OrderType {
+    pub fn is_stop(&self) -> bool {
+        matches!(
+            self,
+            OrderType::Stop | OrderType::StopLimit | OrderType::TrailingStop
+        )
+    }
+
+    /// Whether `price` is a limit the client chose
+    pub fn is_priced(&self) -> bool {
+        matches!(self, OrderType::Limit | OrderType::StopLimit)
+    }
+}
+
+impl TryFrom<String> for OrderType {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "LIMIT" => Ok(OrderType::Limit),
+            "MARKET" => Ok(OrderType::Market),
+            "STOP" => Ok(OrderType::Stop),
+            "STOP_LIMIT" => Ok(OrderType::StopLimit),
+            "TRAILING_STOP" => Ok(OrderType::TrailingStop),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for OrderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderType::Limit => write!(f, "LIMIT"),
+            OrderType::Market => write!(f, "MARKET"),
+            OrderType::Stop => write!(f, "STOP"),
+            OrderType::StopLimit => write!(f, "STOP_LIMIT"),
+            OrderType::TrailingStop => write!(f, "TRAILING_STOP"),
+        }
+    }
+}
+
+/// How an order is tied to the other orders of its group
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LinkType {
+    #[default]
+    Single,
+    Oco,
+    Bracket,
+}
+
+impl TryFrom<String> for LinkType {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "SINGLE" => Ok(LinkType::Single),
+            "OCO" => Ok(LinkType::Oco),
+            "BRACKET" => Ok(LinkType::Bracket),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for LinkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkType::Single => write!(f, "SINGLE"),
+            LinkType::Oco => write!(f, "OCO"),
+            LinkType::Bracket => write!(f, "BRACKET"),
+        }
+    }
+}
+
+/// Market an order trades on. Regular and odd-lot orders match on separate books, the
+/// negotiated board takes pre-arranged trades of any size.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Board {
+    /// Whole lots, every order type
+    #[default]
+    Regular,
+    /// Less than a lot, limit orders only
+    OddLot,
+    /// Any quantity at an agreed price, limit orders only
+    Negotiated,
+}
+
+impl Board {
+    pub const ALL: [Board; 3] = [Board::Regular, Board::OddLot, Board::Negotiated];
+
+    /// Board the last price, stops and the call auction go by
+    pub fn is_regular(&self) -> bool {
+        *self == Board::Regular
+    }
+}
+
+impl TryFrom<String> for Board {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "REGULAR" => Ok(Board::Regular),
+            "ODD_LOT" => Ok(Board::OddLot),
+            "NEGOTIATED" => Ok(Board::Negotiated),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Board::Regular => write!(f, "REGULAR"),
+            Board::OddLot => write!(f, "ODD_LOT"),
+            Board::Negotiated => write!(f, "NEGOTIATED"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl TryFrom<char> for Side {
+    type Error = OrderError;
+
+    fn try_from(value: char) -> Result<Self, OrderError> {
+        match value {
+            'B' => Ok(Side::Buy),
+            'S' => Ok(Side::Sell),
+            _ => Err(OrderError::InvalidSide(value)),
+        }
+    }
+}
+
+impl TryFrom<String> for Side {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(side), None) => side.try_into(),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Buy => write!(f, "B"),
+            Side::Sell => write!(f, "S"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
 pub enum Expiry {
+    /// Good till cancelled
     GTC,
+    /// Good for the trading day it was placed on
     GFD,
+    /// Good till the end of the given trading day, stored as `GTD:YYYY-MM-DD`
+    GTD(NaiveDate),
+}
+
+impl Expiry {
+    /// Whether an order placed on `placed_on` is no longer valid once `market_date` closes
+    pub fn expires_by(&self, placed_on: NaiveDate, market_date: NaiveDate) -> bool {
+        match self {
+            Expiry::GTC => false,
+            Expiry::GFD => placed_on <= market_date,
+            Expiry::GTD(date) => *date <= market_date,
+        }
+    }
 }
 
 impl TryFrom<&str> for Expiry {
@@ -82,17 +625,124 @@ impl TryFrom<&str> for Expiry {
     fn try_from(value: &str) -> Result<Self, anyhow::Error> {
         match value {
             "GTC" => Ok(Expiry::GTC),
-            "GFD" => Ok(Expiry::GTC),
-            _ => Err(anyhow::anyhow!("Expiry not found")),
+            "GFD" => Ok(Expiry::GFD),
+            _ => match value.strip_prefix("GTD:") {
+                Some(date) => Ok(Expiry::GTD(NaiveDate::parse_from_str(date, "%Y-%m-%d")?)),
+                None => Err(anyhow::anyhow!("Expiry not found")),
+            },
         }
     }
 }
 
-impl ToString for Expiry {
-    fn to_string(&self) -> String {
+impl TryFrom<String> for Expiry {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, anyhow::Error> {
+        value.as_str().try_into()
+    }
+}
+
+impl fmt::Display for Expiry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expiry::GTC => write!(f, "GTC"),
+            Expiry::GFD => write!(f, "GFD"),
+            Expiry::GTD(date) => write!(f, "GTD:{}", date.format("%Y-%m-%d")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStatus {
+    New,
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+    Expired,
+    /// Stop order waiting for its trigger price, nothing is on hold yet
+    PendingTrigger,
+    /// Stop order that reached its trigger price and released its child order
+    Triggered,
+    /// Bracket leg waiting for its entry to be done
+    Inactive,
+}
+
+impl OrderStatus {
+    /// Orders that still hold cash or shares and can be filled or cancelled
+    pub fn is_working(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::New | OrderStatus::Open | OrderStatus::PartiallyFilled
+        )
+    }
+
+    /// Orders that can still change: working, waiting for a trigger or for activation
+    pub fn is_live(&self) -> bool {
+        self.is_working() || matches!(self, OrderStatus::PendingTrigger | OrderStatus::Inactive)
+    }
+
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (New, Open | Rejected | Cancelled | Expired | PendingTrigger)
+                | (PendingTrigger, Triggered | Cancelled | Expired)
+                | (
+                    Inactive,
+                    New | PendingTrigger | Cancelled | Expired | Rejected
+                )
+                | (
+                    Open | PartiallyFilled,
+                    PartiallyFilled | Filled | Cancelled | Expired
+                )
+        )
+    }
+}
+
+impl TryFrom<&str> for OrderStatus {
+    type Error = OrderError;
+
+    fn try_from(value: &str) -> Result<Self, OrderError> {
+        match value {
+            "NEW" => Ok(OrderStatus::New),
+            "OPEN" => Ok(OrderStatus::Open),
+            "PARTIALLY_FILLED" => Ok(OrderStatus::PartiallyFilled),
+            "FILLED" => Ok(OrderStatus::Filled),
+            "CANCELLED" => Ok(OrderStatus::Cancelled),
+            "REJECTED" => Ok(OrderStatus::Rejected),
+            "EXPIRED" => Ok(OrderStatus::Expired),
+            "PENDING_TRIGGER" => Ok(OrderStatus::PendingTrigger),
+            "TRIGGERED" => Ok(OrderStatus::Triggered),
+            "INACTIVE" => Ok(OrderStatus::Inactive),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl TryFrom<String> for OrderStatus {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        value.as_str().try_into()
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expiry::GTC => "GTC".to_string(),
-            Expiry::GFD => "GFD".to_string(),
+            OrderStatus::New => write!(f, "NEW"),
+            OrderStatus::Open => write!(f, "OPEN"),
+            OrderStatus::PartiallyFilled => write!(f, "PARTIALLY_FILLED"),
+            OrderStatus::Filled => write!(f, "FILLED"),
+            OrderStatus::Cancelled => write!(f, "CANCELLED"),
+            OrderStatus::Rejected => write!(f, "REJECTED"),
+            OrderStatus::Expired => write!(f, "EXPIRED"),
+            OrderStatus::PendingTrigger => write!(f, "PENDING_TRIGGER"),
+            OrderStatus::Triggered => write!(f, "TRIGGERED"),
+            OrderStatus::Inactive => write!(f, "INACTIVE"),
         }
     }
 }
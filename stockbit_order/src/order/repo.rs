@@ -1,6 +1,12 @@
 use super::model::{Order, Orders};
-use anyhow::{Ok, Result};
-use sqlx::Postgres;
+use anyhow::Result;
+use sqlx::{Postgres, Transaction};
+
+/// Columns read into an `Order`
+const ORDER_COLUMNS: &str = r#"order_id, product_symbol, product_name, side, price, quantity,
+    expiry, created_at, user_id, product_id, status, filled_quantity, order_type, avg_price,
+    stop_price, parent_order_id, trail_amount, trail_percent, high_water_mark, triggered_price,
+    link_type, algo_id, commission, levy, vat, tax, board"#;
 
 #[derive(Clone)]
 pub struct OrderRepo {
@@ -12,33 +18,226 @@ impl OrderRepo {
         Self { pool }
     }
 
-    pub async fn insert(&self, order: &Order) -> Result<i32> {
+    pub async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<i32, sqlx::Error> {
         let row: (i32,) = sqlx::query_as(
             r#"INSERT INTO orders (product_symbol, product_name, side, 
-                price, lot, expiry, created_at, user_id, product_id)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) 
+                price, quantity, expiry, created_at, user_id, product_id, status,
+                filled_quantity, order_type, avg_price, stop_price, parent_order_id,
+                trail_amount, trail_percent, high_water_mark, link_type, algo_id, board)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
+                $16, $17, $18, $19, $20, $21)
                 RETURNING order_id"#,
         )
         .bind(&order.product_symbol)
         .bind(&order.product_name)
-        .bind(&order.side.to_string())
-        .bind(&order.price)
-        .bind(&order.lot)
-        .bind(&order.expiry.to_string())
-        .bind(&order.created_at)
-        .bind(&order.user_id)
-        .bind(&order.product_id)
+        .bind(order.side.to_string())
+        .bind(order.price)
+        .bind(order.quantity)
+        .bind(order.expiry.to_string())
+        .bind(order.created_at)
+        .bind(order.user_id)
+        .bind(order.product_id)
+        .bind(order.status.to_string())
+        .bind(order.filled_quantity)
+        .bind(order.order_type.to_string())
+        .bind(order.avg_price)
+        .bind(order.stop_price)
+        .bind(order.parent_order_id)
+        .bind(order.trail_amount)
+        .bind(order.trail_percent)
+        .bind(order.high_water_mark)
+        .bind(order.link_type.to_string())
+        .bind(order.algo_id)
+        .bind(order.board.to_string())
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok(row.0)
+    }
+
+    /// Reads the order and holds its row lock until the transaction ends
+    pub async fn lock_by_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order_id: i32,
+    ) -> Result<Order, sqlx::Error> {
+        sqlx::query_as::<_, Order>(&format!(
+            "SELECT {ORDER_COLUMNS} FROM orders WHERE order_id = $1 FOR UPDATE"
+        ))
+        .bind(order_id)
+        .fetch_one(&mut **tx)
+        .await
+    }
+
+    pub async fn get_by_id(&self, order_id: i32) -> Result<Order, sqlx::Error> {
+        sqlx::query_as::<_, Order>(&format!(
+            "SELECT {ORDER_COLUMNS} FROM orders WHERE order_id = $1"
+        ))
+        .bind(order_id)
         .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Locks every order of the group started by `root_id`, the root included
+    pub async fn lock_linked(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        root_id: i32,
+    ) -> Result<Vec<Order>, sqlx::Error> {
+        sqlx::query_as::<_, Order>(&format!(
+            "SELECT {ORDER_COLUMNS} FROM orders
+            WHERE (order_id = $1 OR parent_order_id = $1) AND link_type <> 'SINGLE'
+            ORDER BY order_id FOR UPDATE"
+        ))
+        .bind(root_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    /// Child orders of an algo, in arrival order
+    pub async fn get_by_algo_id(&self, algo_id: i32) -> Result<Vec<Order>, sqlx::Error> {
+        sqlx::query_as::<_, Order>(&format!(
+            "SELECT {ORDER_COLUMNS} FROM orders WHERE algo_id = $1 ORDER BY order_id"
+        ))
+        .bind(algo_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Orders still resting on the books, in arrival order
+    pub async fn get_working(&self) -> Result<Vec<Order>, sqlx::Error> {
+        self.get_by_status(&["OPEN", "PARTIALLY_FILLED"]).await
+    }
+
+    /// Orders accepted outside trading hours, waiting for the next session, in arrival order
+    pub async fn get_queued(&self) -> Result<Vec<Order>, sqlx::Error> {
+        self.get_by_status(&["NEW"]).await
+    }
+
+    /// Stop orders waiting for their trigger price, in arrival order
+    pub async fn get_pending_triggers(&self) -> Result<Vec<Order>, sqlx::Error> {
+        self.get_by_status(&["PENDING_TRIGGER"]).await
+    }
+
+    async fn get_by_status(&self, statuses: &[&str]) -> Result<Vec<Order>, sqlx::Error> {
+        sqlx::query_as::<_, Order>(&format!(
+            "SELECT {ORDER_COLUMNS} FROM orders WHERE status = ANY($1)
+            ORDER BY created_at, order_id"
+        ))
+        .bind(statuses)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Locks the orders of a product still waiting to trade or trading, in arrival order
+    pub async fn lock_live_by_product(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        product_id: i32,
+    ) -> Result<Vec<Order>, sqlx::Error> {
+        sqlx::query_as::<_, Order>(&format!(
+            "SELECT {ORDER_COLUMNS} FROM orders
+            WHERE product_id = $1
+                AND status IN ('NEW', 'OPEN', 'PARTIALLY_FILLED', 'PENDING_TRIGGER', 'INACTIVE')
+            ORDER BY created_at, order_id FOR UPDATE"
+        ))
+        .bind(product_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    /// Persists the prices and quantity a corporate action moved an order to
+    pub async fn adjust(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE orders SET status = $1, quantity = $2, price = $3, stop_price = $4,
+            trail_amount = $5, high_water_mark = $6
+            WHERE order_id = $7"#,
+        )
+        .bind(order.status.to_string())
+        .bind(order.quantity)
+        .bind(order.price)
+        .bind(order.stop_price)
+        .bind(order.trail_amount)
+        .bind(order.high_water_mark)
+        .bind(order.order_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Persists the status and filled quantity of an order
+    pub async fn update_progress(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE orders SET status = $1, filled_quantity = $2, avg_price = $3,
+            commission = $4, levy = $5, vat = $6, tax = $7
+            WHERE order_id = $8"#,
+        )
+        .bind(order.status.to_string())
+        .bind(order.filled_quantity)
+        .bind(order.avg_price)
+        .bind(order.fees.commission)
+        .bind(order.fees.levy)
+        .bind(order.fees.vat)
+        .bind(order.fees.tax)
+        .bind(order.order_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Persists an activated bracket leg with the quantity it was sized to
+    pub async fn activate(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE orders SET status = $1, quantity = $2 WHERE order_id = $3")
+            .bind(order.status.to_string())
+            .bind(order.quantity)
+            .bind(order.order_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Persists where a stop order triggers: the trailed stop price and mark while it
+    /// waits, the triggering price once it fired
+    pub async fn update_trigger(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE orders SET stop_price = $1, high_water_mark = $2, triggered_price = $3
+            WHERE order_id = $4"#,
+        )
+        .bind(order.stop_price)
+        .bind(order.high_water_mark)
+        .bind(order.triggered_price)
+        .bind(order.order_id)
+        .execute(&mut **tx)
         .await?;
-        Ok(row.0 as i32)
+        Ok(())
     }
 
     pub async fn get_all_by_user_id(&self, user_id: i32) -> Result<Vec<Orders>> {
-        // TODO
-        // price in database is decimal but in our rust its i32, consider 1 type
         let orders = sqlx::query_as::<_, Orders>(
-            r#"SELECT product_symbol, product_name, side, price::integer as price,
-                lot, expiry, created_at FROM orders WHERE user_id = $1"#,
+            r#"SELECT order_id, product_symbol, product_name, side, price, quantity, expiry,
+                created_at, status, filled_quantity, order_type, avg_price, stop_price,
+                parent_order_id, trail_amount, trail_percent, high_water_mark, triggered_price,
+                link_type, algo_id, commission, levy, vat, tax, board
+                FROM orders WHERE user_id = $1 ORDER BY order_id DESC"#,
         )
         .bind(user_id)
         .fetch_all(&self.pool)
@@ -0,0 +1,123 @@
+use rust_decimal::Decimal;
+
+use crate::cfg::MARKET_CONFIG;
+use crate::error::OrderError;
+use crate::order::model::{Board, Order, OrderType, Side};
+use crate::product::model::Product;
+use crate::types::{Price, Quantity};
+
+/// Tick size of the tier `price` falls in
+pub fn tick_size(price: Price) -> Price {
+    MARKET_CONFIG
+        .tick_sizes
+        .iter()
+        .rev()
+        .find(|t| t.from <= price)
+        .map_or(Price::new(1), |t| t.tick)
+}
+
+/// Whether `price` sits on the tick of its tier
+pub fn is_on_tick(price: Price) -> bool {
+    price.get() % tick_size(price).get() == 0
+}
+
+/// Lowest and highest price allowed around `reference` (ARB and ARA), both on a valid tick
+pub fn price_band(reference: Price) -> (Price, Price) {
+    let (ara_pct, arb_pct) = MARKET_CONFIG
+        .price_bands
+        .iter()
+        .rev()
+        .find(|b| b.from <= reference)
+        .map(|b| (b.ara_pct, b.arb_pct))
+        .unwrap_or((100, 100));
+    let reference = reference.get() as i64;
+    let upper = (reference + reference * ara_pct as i64 / 100).min(i32::MAX as i64);
+    let lower = reference - reference * arb_pct as i64 / 100;
+    (
+        round_to_tick(Price::new(lower as i32), Side::Sell).max(MARKET_CONFIG.min_price),
+        round_to_tick(Price::new(upper as i32), Side::Buy),
+    )
+}
+
+/// Moves `price` onto a tick: down for buys and up for sells, so the order never
+/// gets a worse limit than asked for
+pub fn round_to_tick(price: Price, side: Side) -> Price {
+    let tick = tick_size(price).get();
+    let off = price.get() % tick;
+    match side {
+        Side::Buy => Price::new(price.get() - off),
+        Side::Sell => Price::new(price.get().saturating_add((tick - off) % tick)),
+    }
+}
+
+/// Keeps a market order's protection price inside the product's trading band
+pub fn clamp_to_band(price: Price, side: Side, product: &Product) -> Price {
+    let price = round_to_tick(price, side);
+    match product.reference_price {
+        Some(reference) => {
+            let (lower, upper) = price_band(reference);
+            price.clamp(lower, upper)
+        }
+        None => price.max(MARKET_CONFIG.min_price),
+    }
+}
+
+/// Exchange rules every order has to pass before it reaches the risk check.
+/// Market and stop orders are priced later and only have their quantity and stop price
+/// checked here. Boards other than the regular one only take limit orders, and the
+/// negotiated board leaves the agreed price to the parties, off ticks and bands.
+pub fn validate(order: &Order, product: &Product) -> Result<(), OrderError> {
+    let (min, max) = quantity_range(order.board);
+    if order.quantity < min || order.quantity > max {
+        return Err(OrderError::InvalidQuantity(min, max));
+    }
+    if order.board.is_regular() && !order.quantity.is_round_lot() {
+        return Err(OrderError::NotRoundLot(MARKET_CONFIG.lot.size));
+    }
+    if !order.board.is_regular() && order.order_type != OrderType::Limit {
+        return Err(OrderError::LimitOnlyBoard(order.board));
+    }
+    match order.order_type {
+        OrderType::Stop | OrderType::StopLimit => match order.stop_price {
+            Some(stop) if stop >= MARKET_CONFIG.min_price && is_on_tick(stop) => {}
+            _ => return Err(OrderError::InvalidStopPrice),
+        },
+        OrderType::TrailingStop => match (order.trail_amount, order.trail_percent) {
+            (Some(amount), None) if amount > Price::ZERO => {}
+            (None, Some(percent)) if percent > Decimal::ZERO && percent < Decimal::ONE_HUNDRED => {}
+            (_, Some(_)) => return Err(OrderError::InvalidTrail("trail_percent")),
+            _ => return Err(OrderError::InvalidTrail("trail_amount")),
+        },
+        _ => {}
+    }
+    if !order.order_type.is_priced() {
+        return Ok(());
+    }
+    if order.price < MARKET_CONFIG.min_price {
+        return Err(OrderError::PriceTooLow(MARKET_CONFIG.min_price));
+    }
+    if order.board == Board::Negotiated {
+        return Ok(());
+    }
+    if !is_on_tick(order.price) {
+        return Err(OrderError::InvalidTick(tick_size(order.price)));
+    }
+    if let Some(reference) = product.reference_price {
+        let (lower, upper) = price_band(reference);
+        if order.price < lower || order.price > upper {
+            return Err(OrderError::PriceOutOfBand(lower, upper));
+        }
+    }
+    Ok(())
+}
+
+/// Fewest and most shares an order on `board` can be for
+pub fn quantity_range(board: Board) -> (Quantity, Quantity) {
+    let lot = &MARKET_CONFIG.lot;
+    let one = Quantity::new(1);
+    match board {
+        Board::Regular => (lot.min.shares(), lot.max.shares()),
+        Board::OddLot => (one, lot.size - one),
+        Board::Negotiated => (one, Quantity::MAX),
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::calendar;
+use crate::error::OrderError;
+use crate::trade::model::Trade;
+use crate::types::{Money, Quantity};
+
+/// How the cost of sold shares is taken out of a position
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PnlMethod {
+    /// Every share costs the position's average cost
+    #[default]
+    Average,
+    /// Sold shares come out of the oldest buys first
+    Fifo,
+}
+
+impl TryFrom<String> for PnlMethod {
+    type Error = OrderError;
+
+    fn try_from(value: String) -> Result<Self, OrderError> {
+        match value.as_str() {
+            "AVERAGE" => Ok(PnlMethod::Average),
+            "FIFO" => Ok(PnlMethod::Fifo),
+            _ => Err(OrderError::BadRequest),
+        }
+    }
+}
+
+impl fmt::Display for PnlMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PnlMethod::Average => write!(f, "AVERAGE"),
+            PnlMethod::Fifo => write!(f, "FIFO"),
+        }
+    }
+}
+
+/// Shares of one buy still held, with what they cost including fees
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct OpenLot {
+    pub lot_id: i32,
+    pub shares: Quantity,
+    pub cost: Money,
+}
+
+/// What a position held from before its trades were recorded stood at when its opening
+/// lot was taken, trades and splits up to `opened_at` included
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct OpeningLot {
+    pub product_id: i32,
+    pub shares: Quantity,
+    pub cost: Money,
+    pub avg_price: Decimal,
+    pub opened_at: DateTime<Utc>,
+}
+
+/// Gain or loss locked in by a sell fill: its proceeds net of fees against the cost of
+/// the shares it closed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RealizedPnl {
+    pub trade_id: i32,
+    pub user_id: i32,
+    pub product_id: i32,
+    pub product_symbol: String,
+    pub shares: Quantity,
+    pub proceeds: Money,
+    pub cost_basis: Money,
+    pub method: PnlMethod,
+    pub trade_date: NaiveDate,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RealizedPnl {
+    pub fn new(
+        trade_id: i32,
+        trade: &Trade,
+        symbol: &str,
+        proceeds: Money,
+        cost_basis: Money,
+        method: PnlMethod,
+    ) -> Self {
+        Self {
+            trade_id,
+            user_id: trade.user_id,
+            product_id: trade.product_id,
+            product_symbol: symbol.to_string(),
+            shares: trade.quantity,
+            proceeds,
+            cost_basis,
+            method,
+            trade_date: calendar::market_date(trade.created_at),
+            created_at: trade.created_at,
+        }
+    }
+
+    pub fn realized(&self) -> Money {
+        self.proceeds - self.cost_basis
+    }
+}
+
+/// P&L of one symbol on the summary
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct SymbolPnl {
+    pub symbol: String,
+    pub realized_pnl: Money,
+    /// Of the shares still held against the latest price, none without a price
+    #[sqlx(default)]
+    pub unrealized_pnl: Option<Money>,
+}
+
+/// Realized P&L of one trading date per symbol, with the unrealized P&L of what is held
+/// now
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DailyPnl {
+    pub date: NaiveDate,
+    pub realized_pnl: Money,
+    pub unrealized_pnl: Money,
+    pub symbols: Vec<SymbolPnl>,
+}
+
+impl DailyPnl {
+    pub fn new(date: NaiveDate, symbols: Vec<SymbolPnl>) -> Self {
+        Self {
+            date,
+            realized_pnl: symbols.iter().map(|s| s.realized_pnl).sum(),
+            unrealized_pnl: symbols.iter().filter_map(|s| s.unrealized_pnl).sum(),
+            symbols,
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+
+use super::model::{OpenLot, OpeningLot, RealizedPnl, SymbolPnl};
+use crate::types::{Money, Quantity};
+
+#[derive(Clone)]
+pub struct PnlRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl PnlRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Opens a lot for the shares a buy fill added to the position
+    pub async fn open_lot(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        trade_id: i32,
+        user_id: i32,
+        product_id: i32,
+        shares: Quantity,
+        cost: Money,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO position_lots (trade_id, user_id, product_id, shares, cost)
+            VALUES ($1, $2, $3, $4, $5)"#,
+        )
+        .bind(trade_id)
+        .bind(user_id)
+        .bind(product_id)
+        .bind(shares)
+        .bind(cost)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Locks the lots held of a product, oldest first
+    pub async fn lock_open_lots(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+        product_id: i32,
+    ) -> Result<Vec<OpenLot>, sqlx::Error> {
+        sqlx::query_as::<_, OpenLot>(
+            r#"SELECT lot_id, shares, cost FROM position_lots
+            WHERE user_id = $1 AND product_id = $2 AND shares > 0 ORDER BY lot_id FOR UPDATE"#,
+        )
+        .bind(user_id)
+        .bind(product_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    /// Leaves `shares` costing `cost` open in the lot, closing it once nothing is left.
+    /// An opening lot stays behind empty, reconciliation replays from what it opened with.
+    pub async fn reduce_lot(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        lot_id: i32,
+        shares: Quantity,
+        cost: Money,
+    ) -> Result<(), sqlx::Error> {
+        if shares.is_zero() {
+            sqlx::query(r#"DELETE FROM position_lots WHERE lot_id = $1 AND trade_id IS NOT NULL"#)
+                .bind(lot_id)
+                .execute(&mut **tx)
+                .await?;
+        }
+        sqlx::query(r#"UPDATE position_lots SET shares = $1, cost = $2 WHERE lot_id = $3"#)
+            .bind(shares)
+            .bind(cost)
+            .bind(lot_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    /// The opening lots of the user's positions held from before their trades were recorded
+    pub async fn get_opening_lots(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+    ) -> Result<Vec<OpeningLot>, sqlx::Error> {
+        sqlx::query_as::<_, OpeningLot>(
+            r#"SELECT product_id, opened_shares AS shares, opened_cost AS cost,
+                opened_avg_price AS avg_price, opened_at
+            FROM position_lots
+            WHERE user_id = $1 AND trade_id IS NULL AND opened_at IS NOT NULL
+            ORDER BY lot_id"#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    /// Moves the open lots of a product to a split ratio, their cost stays
+    pub async fn rescale_lots(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        product_id: i32,
+        ratio_from: i32,
+        ratio_to: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE position_lots SET shares = shares * $1 / $2 WHERE product_id = $3"#)
+            .bind(ratio_to)
+            .bind(ratio_from)
+            .bind(product_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_realized(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        pnl: &RealizedPnl,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO realized_pnl (trade_id, user_id, product_id, product_symbol, shares,
+                proceeds, cost_basis, realized, method, trade_date, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#,
+        )
+        .bind(pnl.trade_id)
+        .bind(pnl.user_id)
+        .bind(pnl.product_id)
+        .bind(&pnl.product_symbol)
+        .bind(pnl.shares)
+        .bind(pnl.proceeds)
+        .bind(pnl.cost_basis)
+        .bind(pnl.realized())
+        .bind(pnl.method.to_string())
+        .bind(pnl.trade_date)
+        .bind(pnl.created_at)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Realized P&L of the user's sells on `date`, per symbol
+    pub async fn get_daily(
+        &self,
+        user_id: i32,
+        date: NaiveDate,
+    ) -> Result<Vec<SymbolPnl>, sqlx::Error> {
+        sqlx::query_as::<_, SymbolPnl>(
+            r#"SELECT product_symbol AS symbol, SUM(realized)::bigint AS realized_pnl
+            FROM realized_pnl WHERE user_id = $1 AND trade_date = $2
+            GROUP BY product_symbol ORDER BY product_symbol"#,
+        )
+        .bind(user_id)
+        .bind(date)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
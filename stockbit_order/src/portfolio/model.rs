@@ -1,6 +1,18 @@
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
 
+use crate::types::{Money, Price, Quantity};
+
+/// What a position cost per share, fees included like in `invested_value`, so that
+/// marking it at this price breaks even. Rounded the way its NUMERIC(20, 5) column keeps it.
+pub fn avg_price(invested_value: Money, shares: Quantity) -> Decimal {
+    match shares.is_zero() {
+        true => Decimal::ZERO,
+        false => (Decimal::from(invested_value) / Decimal::from(shares))
+            .round_dp_with_strategy(5, RoundingStrategy::MidpointAwayFromZero),
+    }
+}
+
 #[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
 pub struct Portfolio {
     pub portfolio_id: Option<i32>,
@@ -8,8 +20,8 @@ pub struct Portfolio {
     pub product_id: i32,
     pub product_name: String,
     pub product_symbol: String,
-    pub lot: i32,
-    pub invested_value: i64,
+    pub shares: Quantity,
+    pub invested_value: Money,
     pub avg_price: Decimal,
 }
 
@@ -19,8 +31,8 @@ impl Portfolio {
         product_id: i32,
         product_name: String,
         product_symbol: String,
-        lot: i32,
-        invested_value: i64,
+        shares: Quantity,
+        invested_value: Money,
         avg_price: Decimal,
     ) -> Self {
         Self {
@@ -29,37 +41,75 @@ impl Portfolio {
             product_id,
             product_name,
             product_symbol,
-            lot,
+            shares,
             invested_value,
             avg_price,
         }
     }
 }
 
+/// Position with the shares open sell orders hold of it
+#[derive(sqlx::FromRow, Debug)]
+pub struct HeldPortfolio {
+    #[sqlx(flatten)]
+    pub portfolio: Portfolio,
+    pub reserved_shares: Quantity,
+}
+
 #[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
 pub struct GetPortfolio {
     pub portfolio_id: i32,
-    pub lot: i32,
-    pub invested_value: i64,
+    pub shares: Quantity,
+    pub reserved_shares: Quantity,
+    pub invested_value: Money,
     pub avg_price: Decimal,
 }
 
 impl GetPortfolio {
-    pub fn new(portfolio_id: i32, lot: i32, invested_value: i64, avg_price: Decimal) -> Self {
+    pub fn new(
+        portfolio_id: i32,
+        shares: Quantity,
+        reserved_shares: Quantity,
+        invested_value: Money,
+        avg_price: Decimal,
+    ) -> Self {
         Self {
             portfolio_id,
-            lot,
+            shares,
+            reserved_shares,
             invested_value,
             avg_price,
         }
     }
+
+    /// Shares not on hold for open sell orders
+    pub fn available_shares(&self) -> Quantity {
+        self.shares - self.reserved_shares
+    }
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
 pub struct Portfolios {
-    pub lot: i32,
-    pub invested_value: i64,
+    pub shares: Quantity,
+    /// Shares not waiting on a trade to settle
+    pub settled_shares: Quantity,
+    pub invested_value: Money,
     pub avg_price: Decimal,
     pub product_name: String,
     pub product_symbol: String,
+    /// Booked by the sells of this product so far
+    pub realized_pnl: Money,
+    /// Latest price from the market data feed, none when there is no price
+    #[sqlx(default)]
+    pub market_price: Option<Price>,
+    /// Market value at `market_price` less what the shares held cost
+    #[sqlx(default)]
+    pub unrealized_pnl: Option<Money>,
+}
+
+impl Portfolios {
+    pub fn mark(&mut self, price: Price) {
+        self.market_price = Some(price);
+        self.unrealized_pnl = Some(price * self.shares - self.invested_value);
+    }
 }
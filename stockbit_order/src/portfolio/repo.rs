@@ -1,6 +1,9 @@
-use super::model::{GetPortfolio, Portfolio, Portfolios};
+use super::model::{GetPortfolio, HeldPortfolio, Portfolio, Portfolios};
 use anyhow::Result;
-use sqlx::Postgres;
+use rust_decimal::Decimal;
+use sqlx::{Postgres, Transaction};
+
+use crate::types::{Money, Quantity};
 
 #[derive(Clone)]
 pub struct PortoRepo {
@@ -12,63 +15,158 @@ impl PortoRepo {
         Self { pool }
     }
 
-    pub async fn insert(&self, porto: &Portfolio) -> Result<i32> {
-        println!("{:?}", porto);
+    pub async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        porto: &Portfolio,
+    ) -> Result<i32, sqlx::Error> {
         let row: (i32,) = sqlx::query_as(
             r#"INSERT INTO portfolios (user_id, product_name, product_symbol, 
-                invested_value, lot, avg_price, product_id)
+                invested_value, shares, avg_price, product_id)
                 VALUES ($1, $2, $3, $4, $5, $6, $7) 
                 RETURNING portfolio_id"#,
         )
-        .bind(&porto.user_id)
+        .bind(porto.user_id)
         .bind(&porto.product_name)
         .bind(&porto.product_symbol)
-        .bind(&porto.invested_value)
-        .bind(&porto.lot)
-        .bind(&porto.avg_price)
-        .bind(&porto.product_id)
-        .fetch_one(&self.pool)
-        .await
-        .expect("error insert porto cukkk");
+        .bind(porto.invested_value)
+        .bind(porto.shares)
+        .bind(porto.avg_price)
+        .bind(porto.product_id)
+        .fetch_one(&mut **tx)
+        .await?;
         Ok(row.0)
     }
 
     pub async fn get_by_symbol(
         &self,
+        tx: &mut Transaction<'_, Postgres>,
         symbol: &str,
         user_id: i32,
     ) -> Result<GetPortfolio, sqlx::Error> {
         sqlx::query_as::<_, GetPortfolio>(
-            r#"SELECT portfolio_id, lot, invested_value, avg_price FROM portfolios WHERE product_symbol = $1 AND user_id = $2"#,
+            r#"SELECT portfolio_id, shares, reserved_shares, invested_value, avg_price FROM portfolios
+            WHERE product_symbol = $1 AND user_id = $2 FOR UPDATE"#,
         )
         .bind(symbol)
-            .bind(user_id)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .fetch_one(&mut **tx)
         .await
     }
 
-    pub async fn update(&self, new_porto: GetPortfolio) -> Result<i32, sqlx::Error> {
+    pub async fn update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        new_porto: GetPortfolio,
+    ) -> Result<i32, sqlx::Error> {
         let row: (i32,) = sqlx::query_as(
             r#"
             UPDATE portfolios
-            SET lot = $1, invested_value = $2, avg_price = $3
-            WHERE portfolio_id = $4
+            SET shares = $1, invested_value = $2, avg_price = $3, reserved_shares = $4
+            WHERE portfolio_id = $5
             RETURNING portfolio_id"#,
         )
-        .bind(&new_porto.lot)
-        .bind(&new_porto.invested_value)
-        .bind(&new_porto.avg_price)
-        .bind(&new_porto.portfolio_id)
-        .fetch_one(&self.pool)
-        .await
-        .expect("error update");
+        .bind(new_porto.shares)
+        .bind(new_porto.invested_value)
+        .bind(new_porto.avg_price)
+        .bind(new_porto.reserved_shares)
+        .bind(new_porto.portfolio_id)
+        .fetch_one(&mut **tx)
+        .await?;
         Ok(row.0)
     }
 
+    /// Locks every position held in a product
+    pub async fn lock_by_product(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        product_id: i32,
+    ) -> Result<Vec<Portfolio>, sqlx::Error> {
+        sqlx::query_as::<_, Portfolio>(
+            r#"SELECT portfolio_id, user_id, product_id, product_name, product_symbol, shares,
+            invested_value, avg_price FROM portfolios
+            WHERE product_id = $1 AND shares > 0 ORDER BY portfolio_id FOR UPDATE"#,
+        )
+        .bind(product_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    /// Locks every position of a user
+    pub async fn lock_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+    ) -> Result<Vec<HeldPortfolio>, sqlx::Error> {
+        sqlx::query_as::<_, HeldPortfolio>(
+            r#"SELECT portfolio_id, user_id, product_id, product_name, product_symbol, shares,
+            invested_value, avg_price, reserved_shares FROM portfolios
+            WHERE user_id = $1 ORDER BY product_id FOR UPDATE"#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    /// Rewrites a position after a corporate action, the shares on hold stay as they are
+    pub async fn adjust_position(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        portfolio_id: i32,
+        shares: Quantity,
+        invested_value: Money,
+        avg_price: Decimal,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE portfolios SET shares = $1, invested_value = $2, avg_price = $3
+            WHERE portfolio_id = $4"#,
+        )
+        .bind(shares)
+        .bind(invested_value)
+        .bind(avg_price)
+        .bind(portfolio_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn hold_shares(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        portfolio_id: i32,
+        shares: Quantity,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE portfolios SET reserved_shares = reserved_shares + $1 WHERE portfolio_id = $2"#,
+        )
+        .bind(shares)
+        .bind(portfolio_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        portfolio_id: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM portfolios WHERE portfolio_id = $1"#)
+            .bind(portfolio_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_all_by_user_id(&self, user_id: i32) -> Result<Vec<Portfolios>> {
         let portfolios = sqlx::query_as::<_, Portfolios>(
-            r#"SELECT lot, invested_value, avg_price, product_name, product_symbol 
-            FROM portfolios WHERE user_id = $1"#,
+            r#"SELECT shares, invested_value, avg_price, product_name, product_symbol,
+            shares - (SELECT COALESCE(SUM(s.shares), 0)::integer FROM settlements s
+                WHERE s.user_id = p.user_id AND s.product_id = p.product_id
+                AND s.status = 'PENDING') AS settled_shares,
+            (SELECT COALESCE(SUM(r.realized), 0)::bigint FROM realized_pnl r
+                WHERE r.user_id = p.user_id AND r.product_id = p.product_id) AS realized_pnl
+            FROM portfolios p WHERE user_id = $1"#,
         )
         .bind(user_id)
         .fetch_all(&self.pool)
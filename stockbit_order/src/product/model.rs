@@ -1,11 +1,16 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::types::Price;
+
 #[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
 pub struct Product {
     pub product_id: i32,
     pub name: String,
     pub symbol: String,
+    /// Previous close, the ARA/ARB bands are computed from it
+    #[serde(default)]
+    pub reference_price: Option<Price>,
 }
 
 #[derive(Serialize, Deserialize, sqlx::FromRow)]
@@ -1,7 +1,7 @@
-use anyhow::Result;
-use sqlx::Postgres;
+use sqlx::{Postgres, Transaction};
 
 use super::model::Product;
+use crate::types::Price;
 
 #[derive(Clone)]
 pub struct ProductRepository {
@@ -13,12 +13,35 @@ impl ProductRepository {
         Self { pool }
     }
 
-    pub async fn get_product_by_symbol(&self, symbol: &str) -> Result<Product> {
-        Ok(sqlx::query_as::<_, Product>(
-            "SELECT product_id, symbol, name FROM products WHERE symbol = $1",
+    pub async fn get_product_by_symbol(&self, symbol: &str) -> Result<Product, sqlx::Error> {
+        sqlx::query_as::<_, Product>(
+            "SELECT product_id, symbol, name, reference_price FROM products WHERE symbol = $1",
         )
         .bind(symbol)
         .fetch_one(&self.pool)
-        .await?)
+        .await
+    }
+
+    pub async fn get_product_by_id(&self, product_id: i32) -> Result<Product, sqlx::Error> {
+        sqlx::query_as::<_, Product>(
+            "SELECT product_id, symbol, name, reference_price FROM products WHERE product_id = $1",
+        )
+        .bind(product_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn set_reference_price(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        product_id: i32,
+        reference_price: Option<Price>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE products SET reference_price = $1 WHERE product_id = $2")
+            .bind(reference_price)
+            .bind(product_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
     }
 }
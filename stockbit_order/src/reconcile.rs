@@ -0,0 +1,362 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::corporate::model::{AppliedSplit, CorporateAction};
+use crate::error::OrderError;
+use crate::order::model::Side;
+use crate::pnl::model::{OpeningLot, PnlMethod};
+use crate::portfolio::model::{self as portfolio, HeldPortfolio};
+use crate::trade::model::Trade;
+use crate::types::{Money, Quantity};
+
+/// Position the way the trade history builds it, with the same arithmetic fills and
+/// splits use
+#[derive(Debug, Default, Clone)]
+pub struct Position {
+    pub shares: Quantity,
+    pub invested_value: Money,
+    pub avg_price: Decimal,
+    /// Open lots, oldest first: shares and what they cost
+    lots: VecDeque<(Quantity, Money)>,
+}
+
+impl Position {
+    fn opened(lot: &OpeningLot) -> Self {
+        Position {
+            shares: lot.shares,
+            invested_value: lot.cost,
+            avg_price: stored(lot.avg_price),
+            lots: VecDeque::from([(lot.shares, lot.cost)]),
+        }
+    }
+
+    fn buy(&mut self, trade: &Trade) -> Result<(), OrderError> {
+        let cost = trade
+            .fees
+            .checked_total()
+            .and_then(|fees| trade.value().checked_add(fees));
+        let shares = self.shares.checked_add(trade.quantity);
+        let invested_value = cost.and_then(|cost| self.invested_value.checked_add(cost));
+        let (Some(cost), Some(shares), Some(invested_value)) = (cost, shares, invested_value)
+        else {
+            return Err(OrderError::OVERFLOW);
+        };
+        self.avg_price = portfolio::avg_price(invested_value, shares);
+        self.shares = shares;
+        self.invested_value = invested_value;
+        self.lots.push_back((trade.quantity, cost));
+        Ok(())
+    }
+
+    /// Sells more than the position holds can only come from drift, they close it
+    fn sell(&mut self, trade: &Trade, method: PnlMethod) -> Result<(), OrderError> {
+        let shares = trade.quantity.min(self.shares);
+        let closing = shares == self.shares;
+        let mut left = shares;
+        let mut fifo_cost = Money::ZERO;
+        let mut kept = VecDeque::with_capacity(self.lots.len());
+        for (lot_shares, lot_cost) in self.lots.drain(..) {
+            if left.is_zero() && !closing {
+                kept.push_back((lot_shares, lot_cost));
+                continue;
+            }
+            let take = match closing {
+                true => lot_shares,
+                false => left.min(lot_shares),
+            };
+            let cost = lot_cost.pro_rata(take, lot_shares);
+            if take < lot_shares {
+                let kept_shares = lot_shares.checked_sub(take);
+                let kept_cost = lot_cost.checked_sub(cost);
+                kept.push_back(kept_shares.zip(kept_cost).ok_or(OrderError::OVERFLOW)?);
+            }
+            fifo_cost = fifo_cost.checked_add(cost).ok_or(OrderError::OVERFLOW)?;
+            left = left
+                .checked_sub(take.min(left))
+                .ok_or(OrderError::OVERFLOW)?;
+        }
+        self.lots = kept;
+        if closing {
+            *self = Position::default();
+            return Ok(());
+        }
+        let cost_basis = match method {
+            PnlMethod::Average => Some(self.invested_value.pro_rata(shares, self.shares)),
+            PnlMethod::Fifo => {
+                fifo_cost.checked_add(self.invested_value.pro_rata(left, self.shares))
+            }
+        };
+        let invested_value = cost_basis.and_then(|cost| self.invested_value.checked_sub(cost));
+        let (Some(shares), Some(invested_value)) =
+            (self.shares.checked_sub(shares), invested_value)
+        else {
+            return Err(OrderError::OVERFLOW);
+        };
+        self.shares = shares;
+        self.invested_value = invested_value;
+        self.avg_price = portfolio::avg_price(invested_value, shares);
+        Ok(())
+    }
+
+    fn split(&mut self, action: &CorporateAction) -> Result<(), OrderError> {
+        for (lot_shares, _) in self.lots.iter_mut() {
+            *lot_shares = action.rescale(*lot_shares)?;
+        }
+        if self.shares.is_zero() {
+            return Ok(());
+        }
+        let (shares, fraction) = action.adjust_shares(self.shares)?;
+        let held = Decimal::from(shares) + fraction;
+        let paid_out_cost = match held.is_zero() {
+            true => self.invested_value,
+            false => Money::from_decimal(Decimal::from(self.invested_value) * fraction / held)
+                .unwrap_or_default(),
+        };
+        if shares.is_zero() {
+            *self = Position {
+                lots: std::mem::take(&mut self.lots),
+                ..Default::default()
+            };
+            return Ok(());
+        }
+        self.shares = shares;
+        self.invested_value = self
+            .invested_value
+            .checked_sub(paid_out_cost)
+            .ok_or(OrderError::OVERFLOW)?;
+        self.avg_price = portfolio::avg_price(self.invested_value, shares);
+        Ok(())
+    }
+}
+
+/// `avg_price` as its NUMERIC(20, 5) column keeps it, the next fill reads it back from there
+fn stored(avg_price: Decimal) -> Decimal {
+    avg_price.round_dp_with_strategy(5, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Positions and cash of one user rebuilt from their history
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// By product, closed positions included
+    pub positions: BTreeMap<i32, Position>,
+    pub balance: Money,
+    /// When the positions held from before their trades opened, by product
+    opened_at: BTreeMap<i32, DateTime<Utc>>,
+}
+
+impl Replay {
+    /// Replays the trades and the splits that hit the user's positions in the order they
+    /// happened, positions held from before their trades from their opening lot on.
+    /// `cash_outside_trades` is what deposits, withdrawals, dividends and the like added
+    /// to the balance.
+    pub fn new(
+        openings: &[OpeningLot],
+        trades: &[Trade],
+        splits: &[AppliedSplit],
+        cash_outside_trades: Money,
+        method: PnlMethod,
+    ) -> Result<Self, OrderError> {
+        let mut replay = Replay {
+            positions: BTreeMap::new(),
+            balance: cash_outside_trades,
+            opened_at: BTreeMap::new(),
+        };
+        for lot in openings {
+            replay
+                .positions
+                .insert(lot.product_id, Position::opened(lot));
+            replay.opened_at.insert(lot.product_id, lot.opened_at);
+        }
+        let mut splits = splits.iter().peekable();
+        for trade in trades {
+            while let Some(split) = splits.next_if(|split| split.applied_at <= trade.created_at) {
+                replay.split(split)?;
+            }
+            let replayed = replay.after_opening(trade.product_id, trade.created_at);
+            let position = replay.positions.entry(trade.product_id).or_default();
+            let fees = trade.fees.checked_total().ok_or(OrderError::OVERFLOW)?;
+            let balance = match trade.side {
+                Side::Buy => {
+                    if replayed {
+                        position.buy(trade)?;
+                    }
+                    trade
+                        .value()
+                        .checked_add(fees)
+                        .and_then(|cost| replay.balance.checked_sub(cost))
+                }
+                Side::Sell => {
+                    if replayed {
+                        position.sell(trade, method)?;
+                    }
+                    trade
+                        .value()
+                        .checked_sub(fees)
+                        .and_then(|proceeds| replay.balance.checked_add(proceeds))
+                }
+            };
+            replay.balance = balance.ok_or(OrderError::OVERFLOW)?;
+        }
+        for split in splits {
+            replay.split(split)?;
+        }
+        Ok(replay)
+    }
+
+    /// Whether what happened to the product at `at` is not in its opening lot already
+    fn after_opening(&self, product_id: i32, at: DateTime<Utc>) -> bool {
+        self.opened_at
+            .get(&product_id)
+            .is_none_or(|opened_at| at > *opened_at)
+    }
+
+    fn split(&mut self, split: &AppliedSplit) -> Result<(), OrderError> {
+        let action = &split.action;
+        if !self.after_opening(action.product_id, split.applied_at) {
+            return Ok(());
+        }
+        match self.positions.get_mut(&action.product_id) {
+            Some(position) => position.split(action),
+            None => Ok(()),
+        }
+    }
+
+    /// Cost of everything held, what the account's `invested_value` should be
+    pub fn invested_value(&self) -> Result<Money, OrderError> {
+        self.positions
+            .values()
+            .try_fold(Money::ZERO, |sum, p| sum.checked_add(p.invested_value))
+            .ok_or(OrderError::OVERFLOW)
+    }
+
+    pub fn position(&self, product_id: i32) -> Option<&Position> {
+        self.positions
+            .get(&product_id)
+            .filter(|position| !position.shares.is_zero())
+    }
+}
+
+/// Stored value of a field next to the one the history gives, `null` for a position that
+/// is missing on either side
+#[derive(Serialize, Debug)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub actual: Value,
+    pub expected: Value,
+}
+
+impl FieldDiff {
+    fn of<T: Serialize + PartialEq>(
+        field: &'static str,
+        actual: Option<T>,
+        expected: Option<T>,
+    ) -> Option<Self> {
+        (actual != expected).then(|| FieldDiff {
+            field,
+            actual: json!(actual),
+            expected: json!(expected),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PositionDiff {
+    pub product_id: i32,
+    pub fields: Vec<FieldDiff>,
+    /// Left false by a dry run, and for a position holding shares for sells
+    pub repaired: bool,
+}
+
+impl PositionDiff {
+    /// Differences between a stored position and the replayed one
+    pub fn new(
+        product_id: i32,
+        actual: Option<&HeldPortfolio>,
+        expected: Option<&Position>,
+    ) -> Option<Self> {
+        let actual = actual.map(|held| &held.portfolio);
+        let fields: Vec<FieldDiff> = [
+            FieldDiff::of(
+                "shares",
+                actual.map(|p| p.shares),
+                expected.map(|p| p.shares),
+            ),
+            FieldDiff::of(
+                "invested_value",
+                actual.map(|p| p.invested_value),
+                expected.map(|p| p.invested_value),
+            ),
+            FieldDiff::of(
+                "avg_price",
+                actual.map(|p| stored(p.avg_price)),
+                expected.map(|p| stored(p.avg_price)),
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!fields.is_empty()).then_some(PositionDiff {
+            product_id,
+            fields,
+            repaired: false,
+        })
+    }
+}
+
+/// Drift found for one user
+#[derive(Serialize, Debug)]
+pub struct UserDiff {
+    pub user_id: i32,
+    pub account: Vec<FieldDiff>,
+    pub positions: Vec<PositionDiff>,
+}
+
+impl UserDiff {
+    pub fn new(
+        user_id: i32,
+        balance: Money,
+        invested_value: Money,
+        replay: &Replay,
+        positions: Vec<PositionDiff>,
+    ) -> Result<Option<Self>, OrderError> {
+        let account: Vec<FieldDiff> = [
+            FieldDiff::of("balance", Some(balance), Some(replay.balance)),
+            FieldDiff::of(
+                "invested_value",
+                Some(invested_value),
+                Some(replay.invested_value()?),
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        Ok(
+            (!account.is_empty() || !positions.is_empty()).then_some(UserDiff {
+                user_id,
+                account,
+                positions,
+            }),
+        )
+    }
+}
+
+/// User a run could not reconcile, nothing of theirs was repaired
+#[derive(Serialize, Debug)]
+pub struct UserError {
+    pub user_id: i32,
+    pub error: String,
+}
+
+/// Report of one reconciliation run
+#[derive(Serialize, Debug)]
+pub struct Reconciliation {
+    /// Whether the drift found was repaired, or only reported
+    pub applied: bool,
+    pub users_checked: usize,
+    pub users: Vec<UserDiff>,
+    /// Users the run went past after an error
+    pub errors: Vec<UserError>,
+}
@@ -0,0 +1,48 @@
+use crate::account::model::GetAccount;
+use crate::cfg::{CONFIG, FEE_SCHEDULE};
+use crate::error::OrderError;
+use crate::fee;
+use crate::order::model::{Order, Side};
+use crate::portfolio::model::GetPortfolio;
+use crate::types::{Money, Price};
+
+/// Pre-trade check run before anything is written for an order.
+/// Returns the cash that has to be put on hold for the order, fees included.
+pub fn check(
+    order: &Order,
+    account: &GetAccount,
+    porto: Option<&GetPortfolio>,
+) -> Result<Money, OrderError> {
+    match order.side {
+        Side::Buy => {
+            let required =
+                fee::buy_hold(order.price, order.quantity).ok_or(OrderError::OVERFLOW)?;
+            // the position the order buys into has to stay countable
+            if porto.is_some_and(|porto| porto.shares.checked_add(order.quantity).is_none()) {
+                return Err(OrderError::OVERFLOW);
+            }
+            // the minimum commission is not held, but has to be payable
+            let payable = required.checked_add(FEE_SCHEDULE.min_commission);
+            let available = account.balance.checked_sub(account.reserved);
+            match payable.zip(available) {
+                Some((payable, available)) if payable <= available => Ok(required),
+                _ => Err(OrderError::InsufficientFunds),
+            }
+        }
+        Side::Sell => match porto {
+            Some(porto) if porto.available_shares() >= order.quantity => Ok(Money::ZERO),
+            _ => Err(OrderError::InsufficientShares),
+        },
+    }
+}
+
+/// Worst price a market order may execute at: `market_protection_pct` above the
+/// reference for buys and below it for sells
+pub fn market_protection_price(side: Side, reference: Price) -> Price {
+    let band = reference.get() as i64 * CONFIG.market_protection_pct as i64 / 100;
+    let price = match side {
+        Side::Buy => reference.get() as i64 + band,
+        Side::Sell => (reference.get() as i64 - band).max(1),
+    };
+    Price::new(price.min(i32::MAX as i64) as i32)
+}
@@ -8,12 +8,20 @@ use tokio::sync::oneshot::Receiver;
 use tracing::info;
 
 use crate::account::repo::AccountRepo;
+use crate::algo::repo::AlgoRepo;
+use crate::corporate::repo::CorporateActionRepo;
+use crate::funding::model::CashKind;
+use crate::funding::repo::FundingRepo;
+use crate::ledger::repo::LedgerRepo;
 use crate::mdw::Middleware;
 use crate::order::repo::OrderRepo;
+use crate::pnl::repo::PnlRepo;
 use crate::portfolio::repo::PortoRepo;
 use crate::product::repo::ProductRepository;
 use crate::redis::RedisCache;
+use crate::settlement::repo::SettlementRepo;
 use crate::svc::Service;
+use crate::trade::repo::TradeRepo;
 use crate::{constant, socket};
 use std::sync::Arc;
 
@@ -25,15 +33,43 @@ impl Server {
     pub fn new(pool: Pool<Postgres>, redis_cache: RedisCache) -> Self {
         Self {
             svc: Arc::new(Service::new(
+                pool.clone(),
                 ProductRepository::new(pool.clone()),
                 OrderRepo::new(pool.clone()),
                 AccountRepo::new(pool.clone()),
                 PortoRepo::new(pool.clone()),
+                TradeRepo::new(pool.clone()),
+                AlgoRepo::new(pool.clone()),
+                SettlementRepo::new(pool.clone()),
+                PnlRepo::new(pool.clone()),
+                CorporateActionRepo::new(pool.clone()),
+                LedgerRepo::new(pool.clone()),
+                FundingRepo::new(pool.clone()),
                 redis_cache,
             )),
         }
     }
+
+    /// One reconciliation run for the `reconcile` command, its report as JSON
+    pub async fn reconcile(&self, apply: bool) -> anyhow::Result<String> {
+        let report = self
+            .svc
+            .reconcile(apply)
+            .await
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+
     pub async fn start(self, mut shutdown_rx: Receiver<()>) -> anyhow::Result<()> {
+        self.svc.restore_order_books().await?;
+        tokio::spawn(crate::jobs::run_end_of_day(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_session_open(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_call_auction(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_trigger_watch(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_algos(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_settlement(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_corporate_actions(Arc::clone(&self.svc)));
+        tokio::spawn(crate::jobs::run_reconciliation(Arc::clone(&self.svc)));
         let listener = TcpListener::bind("127.0.0.1:7878").await.unwrap();
         println!("Server running on http://127.0.0.1:7878");
 
@@ -71,8 +107,22 @@ impl Server {
         };
         let (_, mut writer) = stream.split();
 
+        let deleting = request
+            .headers
+            .get(constant::METHOD_OVERRIDE)
+            .is_some_and(|method| method == "DELETE");
+
         //Router
         match (&request.method, request.path.as_str()) {
+            (POST, path) if deleting && path.starts_with("/order/") => svc
+                .delete_order(request, user_id, &mut writer)
+                .await
+                .expect("error cancel order"),
+            (POST, _) if deleting => {
+                stream
+                    .write_all(format!("{}{}", constant::NOT_FOUND, "404 Not Found").as_bytes())
+                    .await?;
+            }
             (GET, "/order/ws") => socket::handle_websocket(request, user_id, svc, &mut stream)
                 .await
                 .expect("error handle ws"),
@@ -80,6 +130,10 @@ impl Server {
                 .create_internal_order(request, &mut writer)
                 .await
                 .expect("error create order nonws"),
+            (POST, "/order/basket") => svc
+                .create_basket_order(request, user_id, &mut writer)
+                .await
+                .expect("error create basket nonws"),
             (GET, "/order") => svc
                 .get_orders(user_id, &mut writer)
                 .await
@@ -88,10 +142,42 @@ impl Server {
                 .get_portfolios(request, user_id, &mut writer)
                 .await
                 .expect("error get portfolios"),
+            (GET, "/portfolio/pnl") => svc
+                .get_daily_pnl(request, user_id, &mut writer)
+                .await
+                .expect("error get daily pnl"),
             (GET, "/account") => svc
                 .get_account(request, user_id, &mut writer)
                 .await
                 .expect("error get account"),
+            (GET, "/account/ledger") => svc
+                .get_ledger(request, user_id, &mut writer)
+                .await
+                .expect("error get ledger"),
+            (POST, "/account/deposit") => svc
+                .create_cash_request(request, user_id, CashKind::Deposit, &mut writer)
+                .await
+                .expect("error create deposit"),
+            (POST, "/account/withdrawal") => svc
+                .create_cash_request(request, user_id, CashKind::Withdrawal, &mut writer)
+                .await
+                .expect("error create withdrawal"),
+            (GET, "/account/cash") => svc
+                .get_cash_requests(user_id, &mut writer)
+                .await
+                .expect("error get cash requests"),
+            (POST, "/internal/cash/review") => svc
+                .review_cash_request(request, &mut writer)
+                .await
+                .expect("error review cash request"),
+            (POST, "/gateway/callback") => svc
+                .gateway_callback(request, &mut writer)
+                .await
+                .expect("error gateway callback"),
+            (GET, "/market/status") => svc
+                .get_market_status(&mut writer)
+                .await
+                .expect("error get market status"),
 
             _ => {
                 stream
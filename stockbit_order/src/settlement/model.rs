@@ -0,0 +1,38 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar;
+use crate::order::model::Side;
+use crate::trade::model::Trade;
+use crate::types::{Money, Quantity};
+
+/// What a trade still owes its owner until its settlement date: cash, net of fees, and
+/// shares. Positive amounts come in, negative ones go out.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Settlement {
+    pub trade_id: i32,
+    pub user_id: i32,
+    pub product_id: i32,
+    pub cash: Money,
+    pub shares: Quantity,
+    pub trade_date: NaiveDate,
+    pub settle_date: NaiveDate,
+}
+
+impl Settlement {
+    pub fn new(trade_id: i32, trade: &Trade, cash: Money) -> Self {
+        let trade_date = calendar::market_date(trade.created_at);
+        Self {
+            trade_id,
+            user_id: trade.user_id,
+            product_id: trade.product_id,
+            cash,
+            shares: match trade.side {
+                Side::Buy => trade.quantity,
+                Side::Sell => -trade.quantity,
+            },
+            trade_date,
+            settle_date: calendar::settlement_date(trade_date),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+
+use super::model::Settlement;
+use crate::types::Money;
+
+#[derive(Clone)]
+pub struct SettlementRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl SettlementRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        settlement: &Settlement,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO settlements (trade_id, user_id, product_id, cash, shares, trade_date,
+                settle_date)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+        )
+        .bind(settlement.trade_id)
+        .bind(settlement.user_id)
+        .bind(settlement.product_id)
+        .bind(settlement.cash)
+        .bind(settlement.shares)
+        .bind(settlement.trade_date)
+        .bind(settlement.settle_date)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Settles every pending obligation due on or before `date`, returns how many
+    pub async fn settle_due(&self, date: NaiveDate) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"UPDATE settlements SET status = 'SETTLED', settled_at = CURRENT_TIMESTAMP
+            WHERE status = 'PENDING' AND settle_date <= $1"#,
+        )
+        .bind(date)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Moves the shares still to settle in a product to a split ratio
+    pub async fn rescale_pending(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        product_id: i32,
+        ratio_from: i32,
+        ratio_to: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE settlements SET shares = shares * $1 / $2
+            WHERE product_id = $3 AND status = 'PENDING'"#,
+        )
+        .bind(ratio_to)
+        .bind(ratio_from)
+        .bind(product_id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Net cash of the user's trades that have not settled yet
+    pub async fn pending_cash(&self, user_id: i32) -> Result<Money, sqlx::Error> {
+        let row: (Money,) = sqlx::query_as(
+            r#"SELECT COALESCE(SUM(cash), 0)::bigint FROM settlements
+            WHERE user_id = $1 AND status = 'PENDING'"#,
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.0)
+    }
+}
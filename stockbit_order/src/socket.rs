@@ -1,3 +1,4 @@
+use crate::cfg::CONFIG;
 use crate::constant::{LOGGING_HANDSHAKE, LOGGING_MESSAGE};
 use crate::logging::thread_logging;
 use crate::svc::Service;
@@ -9,12 +10,6 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tracing::info;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
-struct Response {
-    pub status: String,
-    pub message: String,
-}
-
 pub async fn handle_websocket(
     request: Request,
     user_id: i32,
@@ -49,21 +44,51 @@ pub async fn handle_websocket(
 }
 
 async fn handle_message(stream: &mut TcpStream, user_id: i32, svc: &Arc<Service>) {
+    let mut updates = svc.subscribe(user_id).await;
+    // a frame may arrive over several reads, and a read may carry several frames
+    let mut pending: Vec<u8> = Vec::new();
     loop {
         thread_logging(LOGGING_MESSAGE);
-        let mut buffer = [0; 1024];
-        if let Ok(bytes_read) = stream.read(&mut buffer).await {
-            if bytes_read == 0 {
-                info!("Client disconnected");
-                break;
-            }
+        let mut buffer = [0; 4096];
+        tokio::select! {
+            read = stream.read(&mut buffer) => {
+                if let Ok(bytes_read) = read {
+                    if bytes_read == 0 {
+                        info!("Client disconnected");
+                        break;
+                    }
+                    pending.extend_from_slice(&buffer[..bytes_read]);
 
-            if let Some(message) = utils::parse_websocket_framev2(&buffer[..bytes_read]) {
-                info!("Received WebSocket message: {}", message);
-                svc.create_order(&message, stream, user_id).await;
-            } else {
-                info!("WebSocket connection closing...");
-                break;
+                    let mut closing = false;
+                    while let Some(frame_len) = utils::websocket_frame_len(&pending) {
+                        if frame_len > CONFIG.max_request_bytes {
+                            info!("WebSocket frame of {} bytes too large", frame_len);
+                            closing = true;
+                            break;
+                        }
+                        if pending.len() < frame_len {
+                            break;
+                        }
+                        let frame: Vec<u8> = pending.drain(..frame_len).collect();
+                        if let Some(message) = utils::parse_websocket_framev2(&frame) {
+                            info!("Received WebSocket message: {}", message);
+                            svc.dispatch_message(&message, stream, user_id).await;
+                        } else {
+                            info!("WebSocket connection closing...");
+                            closing = true;
+                            break;
+                        }
+                    }
+                    if closing {
+                        break;
+                    }
+                }
+            }
+            Some(update) = updates.recv() => {
+                let frame: Vec<u8> = utils::create_websocket_frame(&update);
+                if stream.write_all(&frame).await.is_err() {
+                    break;
+                }
             }
         }
     }
@@ -1,32 +1,78 @@
-use crate::constant::{BAD_REQUEST, INTERNAL_ERROR};
+use crate::algo::{
+    model::{AlgoControlForm, AlgoForm, AlgoOrder, AlgoProgress, AlgoStatus},
+    repo::AlgoRepo,
+    schedule,
+};
+use crate::calendar::{self, MarketPhase};
+use crate::cfg::{self, CONFIG, MARKET_CONFIG};
+use crate::constant::{
+    BAD_REQUEST, INTERNAL_ERROR, LEDGER_MAX_PAGE_SIZE, LEDGER_PAGE_SIZE, UNPROCESSABLE_ENTITY,
+};
 use crate::error::OrderError;
+use crate::fee;
+use crate::funding::{
+    model::{
+        CashForm, CashKind, CashLog, CashRequest, CashStatus, GatewayCallback, GatewayStatus,
+        ReviewForm,
+    },
+    repo::FundingRepo,
+};
+use crate::gateway;
+use crate::matching::{
+    MatchingEngine, auction,
+    book::{OrderBook, RestingOrder},
+    trigger::TriggerStore,
+};
+use crate::notify::Notifier;
 use crate::order::model::OrderFormServer;
 use crate::product::model::Product;
+use crate::reconcile::{Position, PositionDiff, Reconciliation, Replay, UserDiff, UserError};
 use crate::redis::RedisCache;
 use crate::{
-    account::{
-        model::{GetAccount, GetAccountDTO},
-        repo::AccountRepo,
-    },
+    account::{model::GetAccountDTO, repo::AccountRepo},
     constant::{OK_RESPONSE, UNAUTHORIZED},
+    corporate::{
+        model::{ActionKind, ActionStatus, Adjustment, CorporateAction, Entitlement},
+        repo::CorporateActionRepo,
+    },
+    ledger::{
+        model::{JournalEntry, LedgerPage},
+        repo::LedgerRepo,
+    },
     order::{
-        model::{Order, OrderForm, Orders},
+        model::{
+            BasketForm, BasketLeg, Board, BracketForm, LinkType, OcoForm, Order, OrderForm,
+            OrderMessage, OrderStatus, OrderType, OrderUpdate, Orders, Side,
+        },
         repo::OrderRepo,
+        validation,
+    },
+    pnl::{
+        model::{DailyPnl, PnlMethod, RealizedPnl, SymbolPnl},
+        repo::PnlRepo,
     },
     portfolio::{
-        model::{GetPortfolio, Portfolio, Portfolios},
+        model::{self as portfolio, GetPortfolio, HeldPortfolio, Portfolio, Portfolios},
         repo::PortoRepo,
     },
     product::repo::ProductRepository,
+    risk,
+    settlement::{model::Settlement, repo::SettlementRepo},
+    trade::{model::Trade, repo::TradeRepo},
+    types::{Money, Price, Quantity},
     utils::{self, ser_to_str},
 };
 use anyhow::Result;
+use chrono::NaiveDate;
 use request_http_parser::parser::Request;
 use rust_decimal::Decimal;
+use sqlx::{Pool, Postgres, Transaction};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{Mutex, Notify};
 use tracing::info;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -35,63 +81,920 @@ struct Response<T> {
     pub message: T,
 }
 
+/// What settling a group of linked orders leaves for after its transaction committed
+#[derive(Default)]
+struct LinkEffects {
+    /// Stop legs to stop watching
+    disarmed: Vec<i32>,
+    /// Stop legs to start watching
+    armed: Vec<Order>,
+    /// Whether priced legs were queued for the next release
+    queued: bool,
+    /// Legs whose owners are told about the change
+    updated: Vec<Order>,
+}
+
 #[derive(Clone)]
 pub struct Service {
+    pool: Pool<Postgres>,
     product_repo: ProductRepository,
     order_repo: OrderRepo,
     account_repo: AccountRepo,
     porto_repo: PortoRepo,
+    trade_repo: TradeRepo,
+    algo_repo: AlgoRepo,
+    settlement_repo: SettlementRepo,
+    pnl_repo: PnlRepo,
+    action_repo: CorporateActionRepo,
+    ledger_repo: LedgerRepo,
+    funding_repo: FundingRepo,
     redis_cache: Arc<Mutex<RedisCache>>,
+    engine: Arc<MatchingEngine>,
+    notifier: Arc<Notifier>,
+    triggers: Arc<TriggerStore>,
+    queued: Arc<Notify>,
 }
 
 impl Service {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        pool: Pool<Postgres>,
         product_repo: ProductRepository,
         order_repo: OrderRepo,
         account_repo: AccountRepo,
         porto_repo: PortoRepo,
+        trade_repo: TradeRepo,
+        algo_repo: AlgoRepo,
+        settlement_repo: SettlementRepo,
+        pnl_repo: PnlRepo,
+        action_repo: CorporateActionRepo,
+        ledger_repo: LedgerRepo,
+        funding_repo: FundingRepo,
         redis_cache: RedisCache,
     ) -> Self {
         Self {
+            pool,
             product_repo,
             order_repo,
             account_repo,
             porto_repo,
+            trade_repo,
+            algo_repo,
+            settlement_repo,
+            pnl_repo,
+            action_repo,
+            ledger_repo,
+            funding_repo,
             redis_cache: Arc::new(Mutex::new(redis_cache)),
+            engine: Arc::new(MatchingEngine::new()),
+            notifier: Arc::new(Notifier::new()),
+            triggers: Arc::new(TriggerStore::new()),
+            queued: Arc::new(Notify::new()),
         }
     }
-    pub async fn create_order(&self, message: &str, stream: &mut TcpStream, user_id: i32) {
-        let order_form = match utils::des_from_str::<OrderForm>(message) {
-            Ok(order_form) => order_form,
-            Err(_) => {
-                let response = Response {
-                    status: String::from("error"),
-                    message: chrono::Utc::now().to_string(),
+
+    /// Messages pushed to the user's websocket outside of request/response
+    pub async fn subscribe(&self, user_id: i32) -> UnboundedReceiver<String> {
+        self.notifier.subscribe(user_id).await
+    }
+
+    async fn notify_order(&self, order: &Order) {
+        let response = Response {
+            status: String::from("update"),
+            message: OrderUpdate::from(order),
+        };
+        match ser_to_str(&response) {
+            Ok(message) => self.notifier.publish(order.user_id, &message).await,
+            Err(e) => info!("error serialize order update {}", e),
+        }
+    }
+
+    async fn notify_algo(&self, user_id: i32, progress: AlgoProgress) {
+        let response = Response {
+            status: String::from("algo"),
+            message: progress,
+        };
+        match ser_to_str(&response) {
+            Ok(message) => self.notifier.publish(user_id, &message).await,
+            Err(e) => info!("error serialize algo progress {}", e),
+        }
+    }
+
+    /// Settles the trades due by `date`, their cash becomes withdrawable. Returns how many.
+    pub async fn settle_trades(&self, date: NaiveDate) -> Result<u64, OrderError> {
+        self.settlement_repo
+            .settle_due(date)
+            .await
+            .map_err(db_error)
+    }
+
+    /// Rebuilds every user's positions and cash from their trades, the splits applied to
+    /// them and the ledger, and reports where the stored rows drifted. With `apply` the
+    /// rows are repaired to the history, a balance through a correction in the ledger.
+    /// A user that cannot be reconciled is reported and the run goes on.
+    pub async fn reconcile(&self, apply: bool) -> Result<Reconciliation, OrderError> {
+        let user_ids = self.account_repo.get_user_ids().await.map_err(db_error)?;
+        let mut users = Vec::new();
+        let mut errors = Vec::new();
+        for &user_id in &user_ids {
+            // one user failing leaves the others to be checked, their transactions are apart
+            match self.reconcile_user(user_id, apply).await {
+                Ok(Some(diff)) => users.push(diff),
+                Ok(None) => {}
+                Err(e) => {
+                    info!("error reconcile user {} {:?}", user_id, e);
+                    errors.push(UserError {
+                        user_id,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(Reconciliation {
+            applied: apply,
+            users_checked: user_ids.len(),
+            users,
+            errors,
+        })
+    }
+
+    async fn reconcile_user(
+        &self,
+        user_id: i32,
+        apply: bool,
+    ) -> Result<Option<UserDiff>, OrderError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        // fills take the account lock first, none of the user's can land mid-comparison
+        let account = self
+            .account_repo
+            .lock_by_user_id(&mut tx, user_id)
+            .await
+            .map_err(db_error)?;
+        let trades = self
+            .trade_repo
+            .get_by_user_id(&mut tx, user_id)
+            .await
+            .map_err(db_error)?;
+        let splits = self
+            .action_repo
+            .get_position_splits(&mut tx, user_id)
+            .await
+            .map_err(db_error)?;
+        let cash_outside_trades = self
+            .ledger_repo
+            .cash_outside_trades(&mut tx, account.account_id)
+            .await
+            .map_err(db_error)?;
+        let openings = self
+            .pnl_repo
+            .get_opening_lots(&mut tx, user_id)
+            .await
+            .map_err(db_error)?;
+        let replay = Replay::new(
+            &openings,
+            &trades,
+            &splits,
+            cash_outside_trades,
+            CONFIG.pnl_method,
+        )?;
+
+        let held: Vec<HeldPortfolio> = self
+            .porto_repo
+            .lock_by_user_id(&mut tx, user_id)
+            .await
+            .map_err(db_error)?
+            .into_iter()
+            .filter(|held| !held.portfolio.shares.is_zero())
+            .collect();
+        let product_ids: BTreeSet<i32> = held
+            .iter()
+            .map(|held| held.portfolio.product_id)
+            .chain(replay.positions.keys().copied())
+            .collect();
+        let mut positions = Vec::new();
+        // what the positions left as they are keep of the invested value
+        let mut unrepaired_invested = Money::ZERO;
+        for product_id in product_ids {
+            let actual = held.iter().find(|h| h.portfolio.product_id == product_id);
+            let expected = replay.position(product_id);
+            let Some(mut diff) = PositionDiff::new(product_id, actual, expected) else {
+                continue;
+            };
+            if apply {
+                diff.repaired = self
+                    .repair_position(&mut tx, user_id, product_id, actual, expected)
+                    .await?;
+                if !diff.repaired {
+                    let drift = actual
+                        .map_or(Money::ZERO, |held| held.portfolio.invested_value)
+                        .checked_sub(expected.map_or(Money::ZERO, |p| p.invested_value))
+                        .and_then(|drift| unrepaired_invested.checked_add(drift));
+                    unrepaired_invested = drift.ok_or(OrderError::OVERFLOW)?;
+                }
+            }
+            positions.push(diff);
+        }
+        let diff = UserDiff::new(
+            user_id,
+            account.balance,
+            account.invested_value,
+            &replay,
+            positions,
+        )?;
+        if apply && diff.is_some() {
+            let balance_delta = replay
+                .balance
+                .checked_sub(account.balance)
+                .ok_or(OrderError::OVERFLOW)?;
+            let invested_delta = replay
+                .invested_value()?
+                .checked_add(unrepaired_invested)
+                .and_then(|invested| invested.checked_sub(account.invested_value))
+                .ok_or(OrderError::OVERFLOW)?;
+            if !balance_delta.is_zero() {
+                self.post_entry(
+                    &mut tx,
+                    JournalEntry::correction(account.account_id, balance_delta),
+                )
+                .await?;
+            }
+            self.account_repo
+                .adjust_invested(&mut tx, account.account_id, invested_delta)
+                .await
+                .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+            info!("Reconciled user {}", user_id);
+        }
+        // a dry run rolls back with the dropped transaction, only the locks were taken
+        Ok(diff)
+    }
+
+    /// Sets a stored position to the replayed one. Positions open sell orders hold more
+    /// shares of than the history leaves are left for someone to look at, false for those.
+    async fn repair_position(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+        product_id: i32,
+        actual: Option<&HeldPortfolio>,
+        expected: Option<&Position>,
+    ) -> Result<bool, OrderError> {
+        let shares = expected.map_or(Quantity::ZERO, |position| position.shares);
+        if let Some(held) = actual.filter(|held| held.reserved_shares > shares) {
+            info!(
+                "Position {} of user {} holds {} shares for sells, not repaired",
+                product_id, user_id, held.reserved_shares
+            );
+            return Ok(false);
+        }
+        match (actual, expected) {
+            (Some(held), Some(position)) => {
+                self.porto_repo
+                    .update(
+                        tx,
+                        GetPortfolio::new(
+                            held.portfolio.portfolio_id.unwrap_or_default(),
+                            position.shares,
+                            held.reserved_shares,
+                            position.invested_value,
+                            position.avg_price,
+                        ),
+                    )
+                    .await
+                    .map_err(db_error)?;
+            }
+            (Some(held), None) => {
+                self.porto_repo
+                    .delete(tx, held.portfolio.portfolio_id.unwrap_or_default())
+                    .await
+                    .map_err(db_error)?;
+            }
+            (None, Some(position)) => {
+                let product = self
+                    .product_repo
+                    .get_product_by_id(product_id)
+                    .await
+                    .map_err(db_error)?;
+                let porto = Portfolio::new(
+                    user_id,
+                    product_id,
+                    product.name,
+                    product.symbol,
+                    position.shares,
+                    position.invested_value,
+                    position.avg_price,
+                );
+                self.porto_repo.insert(tx, &porto).await.map_err(db_error)?;
+            }
+            (None, None) => {}
+        }
+        Ok(true)
+    }
+
+    /// Picks up newly announced corporate actions and processes those due by `date`: on
+    /// the ex-date splits adjust positions and live orders, and dividends are recorded
+    /// for the holders, on the pay date dividends are credited net of tax. Returns how
+    /// many actions moved.
+    pub async fn run_corporate_actions(&self, date: NaiveDate) -> Result<usize, OrderError> {
+        match cfg::corporate_actions() {
+            Ok(announced) => {
+                for definition in announced.actions {
+                    let action = match self.get_product(&definition.symbol).await {
+                        Ok(product) => CorporateAction::new(&definition, &product),
+                        Err(e) => Err(e),
+                    };
+                    match action {
+                        Ok(action) => {
+                            if self.action_repo.insert(&action).await.map_err(db_error)? {
+                                info!(
+                                    "Announced {} {} ex {}",
+                                    action.kind, action.product_symbol, action.ex_date
+                                );
+                            }
+                        }
+                        Err(e) => info!("error corporate action {} {:?}", definition.symbol, e),
+                    }
+                }
+            }
+            Err(e) => info!("error load corporate actions {}", e),
+        }
+
+        let mut processed = 0;
+        for action in self.action_repo.get_due(date).await.map_err(db_error)? {
+            let result = match action.kind {
+                ActionKind::CashDividend => self.process_dividend(&action, date).await,
+                ActionKind::Split | ActionKind::ReverseSplit => self.apply_split(&action).await,
+            };
+            match result {
+                Ok(true) => processed += 1,
+                Ok(false) => {}
+                Err(e) => info!("error corporate action {:?} {:?}", action.action_id, e),
+            }
+        }
+        Ok(processed)
+    }
+
+    /// Moves every position, live order and the reference price of the product to the
+    /// split ratio, in one transaction under the locks of all its books. Fractions of a
+    /// share are paid out at the adjusted reference price. Returns false when another run
+    /// applied the action already.
+    async fn apply_split(&self, action: &CorporateAction) -> Result<bool, OrderError> {
+        let action_id = action.action_id.unwrap_or_default();
+        let mut product = self.get_product(&action.product_symbol).await?;
+        let mut books = Vec::with_capacity(Board::ALL.len());
+        for board in Board::ALL {
+            books.push(self.engine.book(action.product_id, board).await);
+        }
+        let mut locked = Vec::with_capacity(books.len());
+        for book in &books {
+            locked.push(book.lock().await);
+        }
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let action = self
+            .action_repo
+            .lock_by_id(&mut tx, action_id)
+            .await
+            .map_err(db_error)?;
+        if action.status != ActionStatus::Announced {
+            return Ok(false);
+        }
+
+        product.reference_price = product
+            .reference_price
+            .map(|price| action.adjust_rupiah(price));
+        self.product_repo
+            .set_reference_price(&mut tx, product.product_id, product.reference_price)
+            .await
+            .map_err(db_error)?;
+
+        // the books are rebuilt from the adjusted orders once committed
+        let mut next_book = OrderBook::default();
+        let mut links = LinkEffects::default();
+        let mut armed = Vec::new();
+        let mut disarmed = Vec::new();
+        for mut order in self
+            .order_repo
+            .lock_live_by_product(&mut tx, action.product_id)
+            .await
+            .map_err(db_error)?
+        {
+            let before = order.clone();
+            self.release_hold(&mut tx, &before).await?;
+            let remaining = action.adjust_quantity(order.remaining_quantity(), order.board)?;
+            order.quantity = order
+                .filled_quantity
+                .checked_add(remaining)
+                .ok_or(OrderError::OVERFLOW)?;
+            order.price = validation::round_to_tick(action.adjust_rupiah(order.price), order.side);
+            order.stop_price = order.stop_price.map(|price| action.adjust_rupiah(price));
+            order.trail_amount = order
+                .trail_amount
+                .map(|amount| action.adjust_rupiah(amount));
+            order.high_water_mark = order.high_water_mark.map(|mark| action.adjust_rupiah(mark));
+            if remaining.is_zero() {
+                order.transition(OrderStatus::Cancelled)?;
+            } else {
+                self.retake_hold(&mut tx, &order).await?;
+            }
+            self.order_repo
+                .adjust(&mut tx, &order)
+                .await
+                .map_err(db_error)?;
+            self.action_repo
+                .log(
+                    &mut tx,
+                    &Adjustment {
+                        action_id,
+                        user_id: order.user_id,
+                        order_id: order.order_id,
+                        target: "ORDER",
+                        quantity_before: Some(before.quantity),
+                        quantity_after: Some(order.quantity),
+                        price_before: Some(before.price.into()),
+                        price_after: Some(order.price.into()),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(db_error)?;
+            if before.status == OrderStatus::PendingTrigger {
+                disarmed.push(before.order_id.unwrap_or_default());
+                if order.status == OrderStatus::PendingTrigger {
+                    armed.push(order.clone());
+                }
+            }
+            if order.status == OrderStatus::Cancelled {
+                self.settle_links(&mut tx, &mut next_book, &order, &mut links)
+                    .await?;
+                links.updated.push(order);
+            }
+        }
+
+        for porto in self
+            .porto_repo
+            .lock_by_product(&mut tx, action.product_id)
+            .await
+            .map_err(db_error)?
+        {
+            let portfolio_id = porto.portfolio_id.unwrap_or_default();
+            let (shares, fraction) = action.adjust_shares(porto.shares)?;
+            let lieu_price = product
+                .reference_price
+                .map_or(action.adjust_price(porto.avg_price), Decimal::from);
+            let cash = Money::from_decimal(fraction * lieu_price).unwrap_or_default();
+            // the fraction takes its share of the cost with it
+            let held = Decimal::from(shares) + fraction;
+            let paid_out_cost = match held.is_zero() {
+                true => porto.invested_value,
+                false => Money::from_decimal(Decimal::from(porto.invested_value) * fraction / held)
+                    .unwrap_or_default(),
+            };
+            let invested_value = porto.invested_value - paid_out_cost;
+            let avg_price = portfolio::avg_price(invested_value, shares);
+            if shares.is_zero() {
+                self.porto_repo
+                    .delete(&mut tx, portfolio_id)
+                    .await
+                    .map_err(db_error)?;
+            } else {
+                self.porto_repo
+                    .adjust_position(&mut tx, portfolio_id, shares, invested_value, avg_price)
+                    .await
+                    .map_err(db_error)?;
+            }
+            if !cash.is_zero() || !paid_out_cost.is_zero() {
+                let account = self
+                    .account_repo
+                    .lock_by_user_id(&mut tx, porto.user_id)
+                    .await
+                    .map_err(db_error)?;
+                self.account_repo
+                    .adjust_invested(&mut tx, account.account_id, -paid_out_cost)
+                    .await
+                    .map_err(db_error)?;
+                if !cash.is_zero() {
+                    self.post_entry(
+                        &mut tx,
+                        JournalEntry::cash_in_lieu(account.account_id, action_id, cash),
+                    )
+                    .await?;
+                }
+            }
+            self.action_repo
+                .log(
+                    &mut tx,
+                    &Adjustment {
+                        action_id,
+                        user_id: porto.user_id,
+                        target: "PORTFOLIO",
+                        quantity_before: Some(porto.shares),
+                        quantity_after: Some(shares),
+                        price_before: Some(porto.avg_price),
+                        price_after: Some(avg_price),
+                        cash,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(db_error)?;
+        }
+        let (ratio_from, ratio_to) = (action.ratio_from.unwrap_or(1), action.ratio_to.unwrap_or(1));
+        self.pnl_repo
+            .rescale_lots(&mut tx, action.product_id, ratio_from, ratio_to)
+            .await
+            .map_err(db_error)?;
+        self.settlement_repo
+            .rescale_pending(&mut tx, action.product_id, ratio_from, ratio_to)
+            .await
+            .map_err(db_error)?;
+        self.action_repo
+            .set_status(&mut tx, action_id, ActionStatus::Applied)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        // orders keep their time priority at their new prices
+        let mut adjusted: HashMap<Board, OrderBook> = HashMap::new();
+        for order in self.order_repo.get_working().await.map_err(db_error)? {
+            if order.product_id == action.product_id {
+                adjusted
+                    .entry(order.board)
+                    .or_default()
+                    .rest(order.side, RestingOrder::from(&order));
+            }
+        }
+        for (board, book) in Board::ALL.iter().zip(&mut locked) {
+            **book = adjusted.remove(board).unwrap_or_default();
+        }
+        drop(locked);
+        let _ = self
+            .redis_cache
+            .lock()
+            .await
+            .set_cache(&format!("product:{}", product.symbol), &product)
+            .await;
+        for order_id in disarmed {
+            self.triggers.remove(order_id).await;
+        }
+        for order in armed {
+            self.triggers.arm(order).await;
+        }
+        self.apply_links(links).await;
+        info!("Applied {} {}", action.kind, action.product_symbol);
+        Ok(true)
+    }
+
+    /// Records what each holder is owed on the ex-date, and credits it on the pay date.
+    /// Returns false when there was nothing left to do for `date`.
+    async fn process_dividend(
+        &self,
+        action: &CorporateAction,
+        date: NaiveDate,
+    ) -> Result<bool, OrderError> {
+        let action_id = action.action_id.unwrap_or_default();
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut action = self
+            .action_repo
+            .lock_by_id(&mut tx, action_id)
+            .await
+            .map_err(db_error)?;
+        let status_before = action.status;
+        let amount = action.amount.unwrap_or_default();
+        if action.status == ActionStatus::Announced {
+            for porto in self
+                .porto_repo
+                .lock_by_product(&mut tx, action.product_id)
+                .await
+                .map_err(db_error)?
+            {
+                let gross =
+                    Money::from_decimal(amount * Decimal::from(porto.shares)).unwrap_or_default();
+                let tax = fee::dividend_tax(gross);
+                let entitlement = Entitlement {
+                    entitlement_id: None,
+                    action_id,
+                    user_id: porto.user_id,
+                    shares: porto.shares,
+                    gross,
+                    tax,
+                    net: gross - tax,
                 };
-                let response_json = ser_to_str(&response).expect("Error serialize response");
+                self.action_repo
+                    .insert_entitlement(&mut tx, &entitlement)
+                    .await
+                    .map_err(db_error)?;
+                self.action_repo
+                    .log(
+                        &mut tx,
+                        &Adjustment {
+                            action_id,
+                            user_id: porto.user_id,
+                            target: "ENTITLEMENT",
+                            quantity_before: Some(porto.shares),
+                            quantity_after: Some(porto.shares),
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .map_err(db_error)?;
+            }
+            action.status = ActionStatus::Applied;
+        }
+        if action.status == ActionStatus::Applied && action.pay_date.is_some_and(|d| d <= date) {
+            for entitlement in self
+                .action_repo
+                .lock_unpaid(&mut tx, action_id)
+                .await
+                .map_err(db_error)?
+            {
+                let account = self
+                    .account_repo
+                    .lock_by_user_id(&mut tx, entitlement.user_id)
+                    .await
+                    .map_err(db_error)?;
+                self.post_entry(
+                    &mut tx,
+                    JournalEntry::dividend(account.account_id, &entitlement),
+                )
+                .await?;
+                self.action_repo
+                    .mark_paid(&mut tx, entitlement.entitlement_id.unwrap_or_default())
+                    .await
+                    .map_err(db_error)?;
+                self.action_repo
+                    .log(
+                        &mut tx,
+                        &Adjustment {
+                            action_id,
+                            user_id: entitlement.user_id,
+                            target: "DIVIDEND",
+                            cash: entitlement.net,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .map_err(db_error)?;
+            }
+            action.status = ActionStatus::Paid;
+        }
+        if action.status == status_before {
+            return Ok(false);
+        }
+        self.action_repo
+            .set_status(&mut tx, action_id, action.status)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        info!("Dividend {} {}", action.product_symbol, action.status);
+        Ok(true)
+    }
+
+    /// Expires working orders whose validity ends with `market_date`, gives back their
+    /// holds and tells connected owners. Returns how many orders expired.
+    pub async fn expire_orders(&self, market_date: NaiveDate) -> Result<usize, OrderError> {
+        let mut orders = self.order_repo.get_working().await.map_err(db_error)?;
+        // queued orders never reached the book but expire with their trading day all the same
+        orders.extend(self.order_repo.get_queued().await.map_err(db_error)?);
+        orders.extend(
+            self.order_repo
+                .get_pending_triggers()
+                .await
+                .map_err(db_error)?,
+        );
+        let mut expired = 0;
+        for order in orders {
+            let placed_on = calendar::trading_date(order.created_at);
+            if !order.expiry.expires_by(placed_on, market_date) {
+                continue;
+            }
+            let book = self.engine.book(order.product_id, order.board).await;
+            let mut book = book.lock().await;
+            let mut tx = self.pool.begin().await.map_err(db_error)?;
+            let order_id = order.order_id.unwrap_or_default();
+            let mut order = self
+                .order_repo
+                .lock_by_id(&mut tx, order_id)
+                .await
+                .map_err(db_error)?;
+            // filled, cancelled or triggered since it was listed
+            if !order.status.is_working() && order.status != OrderStatus::PendingTrigger {
+                continue;
+            }
+            self.release_hold(&mut tx, &order).await?;
+            order.transition(OrderStatus::Expired)?;
+            self.order_repo
+                .update_progress(&mut tx, &order)
+                .await
+                .map_err(db_error)?;
+            let mut next_book = book.clone();
+            next_book.cancel(order_id);
+            let mut links = LinkEffects::default();
+            self.settle_links(&mut tx, &mut next_book, &order, &mut links)
+                .await?;
+            tx.commit().await.map_err(db_error)?;
+            *book = next_book;
+            self.triggers.remove(order_id).await;
+
+            self.notify_order(&order).await;
+            self.apply_links(links).await;
+            expired += 1;
+        }
+        Ok(expired)
+    }
+
+    /// Works the orders queued outside trading hours once a session opens, in arrival
+    /// order, and tells connected owners. Returns how many orders were released, one
+    /// that fails is logged and left queued without holding up the rest.
+    pub async fn release_queued_orders(&self) -> Result<usize, OrderError> {
+        let orders = self.order_repo.get_queued().await.map_err(db_error)?;
+        let mut released = 0;
+        for order in orders {
+            let order_id = order.order_id.unwrap_or_default();
+            match self.release_queued_order(order).await {
+                Ok(true) => released += 1,
+                Ok(false) => {}
+                Err(e) => info!("error release queued order {} {:?}", order_id, e),
+            }
+        }
+        Ok(released)
+    }
+
+    /// Opens one queued order and works it against its book, in a transaction of its own.
+    /// False when it was cancelled or expired since it was listed.
+    async fn release_queued_order(&self, order: Order) -> Result<bool, OrderError> {
+        let product = self.get_product(&order.product_symbol).await?;
+        let book = self.engine.book(order.product_id, order.board).await;
+        let mut book = book.lock().await;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut order = self
+            .order_repo
+            .lock_by_id(&mut tx, order.order_id.unwrap_or_default())
+            .await
+            .map_err(db_error)?;
+        if order.status != OrderStatus::New {
+            return Ok(false);
+        }
+        order.transition(OrderStatus::Open)?;
+        self.order_repo
+            .update_progress(&mut tx, &order)
+            .await
+            .map_err(db_error)?;
+        // the book only takes the fills once they are committed
+        let mut next_book = book.clone();
+        let mut links = LinkEffects::default();
+        let traded = self
+            .execute(&mut tx, &mut next_book, &mut order, &product, &mut links)
+            .await?;
+        tx.commit().await.map_err(db_error)?;
+        *book = next_book;
+        if let Some(price) = traded {
+            self.triggers.traded(product.product_id, price).await;
+        }
+
+        self.notify_order(&order).await;
+        self.apply_links(links).await;
+        Ok(true)
+    }
 
-                let frame: Vec<u8> = utils::create_websocket_frame(&response_json);
-                stream.write_all(&frame).await.expect("err write response");
-                return;
+    /// Uncrosses the call auction: orders queued for the open on the regular board join
+    /// their books without matching, then each book trades all crossing orders at its
+    /// equilibrium price. Orders of the other boards wait for the session to open.
+    /// Returns the shares matched over all products.
+    pub async fn run_call_auction(&self) -> Result<i64, OrderError> {
+        let mut by_product: BTreeMap<i32, Vec<Order>> = BTreeMap::new();
+        for order in self.order_repo.get_queued().await.map_err(db_error)? {
+            if order.board.is_regular() {
+                by_product.entry(order.product_id).or_default().push(order);
             }
+        }
+        let mut matched = 0;
+        for (product_id, orders) in by_product {
+            let product = self.get_product(&orders[0].product_symbol).await?;
+            let book = self.engine.book(product_id, Board::Regular).await;
+            let mut book = book.lock().await;
+            let mut tx = self.pool.begin().await.map_err(db_error)?;
+            let mut next_book = book.clone();
+            let mut touched = HashMap::new();
+            for order in orders {
+                let mut order = self
+                    .order_repo
+                    .lock_by_id(&mut tx, order.order_id.unwrap_or_default())
+                    .await
+                    .map_err(db_error)?;
+                // cancelled since it was listed
+                if order.status != OrderStatus::New {
+                    continue;
+                }
+                order.transition(OrderStatus::Open)?;
+                self.order_repo
+                    .update_progress(&mut tx, &order)
+                    .await
+                    .map_err(db_error)?;
+                next_book.rest(order.side, RestingOrder::from(&order));
+                touched.insert(order.order_id, order);
+            }
+
+            let reference = product.reference_price.or(next_book.last_price());
+            let iep = auction::equilibrium(&next_book, reference);
+            if let Some(iep) = &iep {
+                info!(
+                    "{} uncrosses at {} for {} shares",
+                    product.symbol, iep.price, iep.volume
+                );
+                for cross in next_book.uncross(iep.price) {
+                    for order_id in [cross.buy_order_id, cross.sell_order_id] {
+                        let mut order = self
+                            .order_repo
+                            .lock_by_id(&mut tx, order_id)
+                            .await
+                            .map_err(db_error)?;
+                        self.apply_fill(&mut tx, &mut order, &product, cross.price, cross.quantity)
+                            .await?;
+                        touched.insert(order.order_id, order);
+                    }
+                    matched += i64::from(cross.quantity.get());
+                }
+            }
+            let mut links = LinkEffects::default();
+            for order in touched.values() {
+                self.settle_links(&mut tx, &mut next_book, order, &mut links)
+                    .await?;
+            }
+            tx.commit().await.map_err(db_error)?;
+            *book = next_book;
+            if let Some(iep) = iep {
+                self.triggers.traded(product_id, iep.price).await;
+            }
+
+            for order in touched.values() {
+                self.notify_order(order).await;
+            }
+            self.apply_links(links).await;
+        }
+        Ok(matched)
+    }
+
+    /// Rebuilds the in-memory order books from working orders in the database
+    pub async fn restore_order_books(&self) -> Result<(), sqlx::Error> {
+        let orders = self.order_repo.get_working().await?;
+        info!("Restoring {} working orders", orders.len());
+        self.engine.restore(&orders).await;
+        let stops = self.order_repo.get_pending_triggers().await?;
+        info!("Arming {} stop orders", stops.len());
+        for stop in stops {
+            self.triggers.arm(stop).await;
+        }
+        Ok(())
+    }
+    pub async fn dispatch_message(&self, message: &str, stream: &mut TcpStream, user_id: i32) {
+        let result = match utils::des_from_str::<OrderMessage>(message) {
+            Ok(message) => self.handle_message(message, user_id).await,
+            Err(_) => Err(OrderError::Serde),
         };
+        Self::write_ws_result(result, stream).await;
+    }
 
-        match self.handle_order(order_form, user_id).await {
-            Ok(res) => {
-                let frame: Vec<u8> = utils::create_websocket_frame(&res);
-                stream.write_all(&frame).await.expect("err write response");
+    /// Handles an order message from the owner's websocket
+    pub async fn handle_message(
+        &self,
+        message: OrderMessage,
+        user_id: i32,
+    ) -> Result<String, OrderError> {
+        match message {
+            OrderMessage::Place(order_form) => self.handle_order(order_form, user_id).await,
+            OrderMessage::Oco(oco_form) => self.handle_oco(oco_form, user_id).await,
+            OrderMessage::Bracket(bracket_form) => self.handle_bracket(bracket_form, user_id).await,
+            OrderMessage::Basket(basket_form) => self.handle_basket(basket_form, user_id).await,
+            OrderMessage::Algo(algo_form) => self.handle_algo(algo_form, user_id).await,
+            OrderMessage::AlgoControl(control_form) => {
+                self.control_algo(control_form, user_id).await
+            }
+            OrderMessage::Cancel(cancel_form) => {
+                self.cancel_order(cancel_form.cancel, user_id).await
+            }
+        }
+    }
+
+    async fn write_ws_result(result: Result<String, OrderError>, stream: &mut TcpStream) {
+        let response_json = match result {
+            Ok(res) => res,
+            Err(why) if why.is_rejection() => {
+                let response = Response {
+                    status: String::from("rejected"),
+                    message: why.rejection(),
+                };
+                ser_to_str(&response).expect("Error serialize response")
             }
             Err(_) => {
                 let response = Response {
                     status: String::from("error"),
                     message: chrono::Utc::now().to_string(),
                 };
-                let response_json = ser_to_str(&response).expect("Error serialize response");
-
-                let frame: Vec<u8> = utils::create_websocket_frame(&response_json);
-                stream.write_all(&frame).await.expect("err write response");
+                ser_to_str(&response).expect("Error serialize response")
             }
-        }
+        };
+        let frame: Vec<u8> = utils::create_websocket_frame(&response_json);
+        stream.write_all(&frame).await.expect("err write response");
     }
 
     pub async fn get_orders(
@@ -104,14 +1007,7 @@ impl Service {
             Err(e) => {
                 info!("error {}", e);
                 writer
-                    .write_all(
-                        format!(
-                            "{}{}",
-                            UNAUTHORIZED.to_string(),
-                            "401 unathorized".to_string()
-                        )
-                        .as_bytes(),
-                    )
+                    .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
                     .await?;
                 return Ok(());
             }
@@ -122,7 +1018,7 @@ impl Service {
         };
         let response_json = ser_to_str(&response).expect("Error serialize response");
         writer
-            .write_all(format!("{}{}", OK_RESPONSE.to_string(), response_json).as_bytes())
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
             .await?;
 
         Ok(())
@@ -134,30 +1030,111 @@ impl Service {
         user_id: i32,
         mut writer: impl AsyncWrite + Unpin,
     ) -> Result<()> {
-        let portfolios: Vec<Portfolios> = match self.porto_repo.get_all_by_user_id(user_id).await {
-            Ok(portfolios) => portfolios,
-            Err(e) => {
+        let mut portfolios: Vec<Portfolios> =
+            match self.porto_repo.get_all_by_user_id(user_id).await {
+                Ok(portfolios) => portfolios,
+                Err(e) => {
+                    info!("error {}", e);
+                    writer
+                        .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
+                        .await?;
+                    return Ok(());
+                }
+            };
+        self.mark_to_market(&mut portfolios).await;
+        let response = Response {
+            status: String::from("ok"),
+            message: portfolios,
+        };
+        let response_json = ser_to_str(&response).expect("Error serialize response");
+        writer
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Realized P&L of a trading date, `?date=YYYY-MM-DD` or today, with the unrealized
+    /// P&L of the positions held now
+    pub async fn get_daily_pnl(
+        &self,
+        request: Request,
+        user_id: i32,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let date = match request
+            .params
+            .as_ref()
+            .and_then(|params| params.get("date"))
+        {
+            Some(date) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                Ok(date) => date,
+                Err(_) => {
+                    writer
+                        .write_all(format!("{}{}", BAD_REQUEST, "invalid date").as_bytes())
+                        .await?;
+                    return Ok(());
+                }
+            },
+            None => calendar::today(),
+        };
+        let held = self.porto_repo.get_all_by_user_id(user_id).await;
+        let realized = self.pnl_repo.get_daily(user_id, date).await;
+        let (mut portfolios, mut symbols) = match (held, realized.map_err(anyhow::Error::from)) {
+            (Ok(portfolios), Ok(symbols)) => (portfolios, symbols),
+            (Err(e), _) | (_, Err(e)) => {
                 info!("error {}", e);
                 writer
-                    .write_all(
-                        format!(
-                            "{}{}",
-                            UNAUTHORIZED.to_string(),
-                            "401 unathorized".to_string()
-                        )
-                        .as_bytes(),
-                    )
+                    .write_all(format!("{}{}", INTERNAL_ERROR, "").as_bytes())
                     .await?;
                 return Ok(());
             }
         };
+        self.mark_to_market(&mut portfolios).await;
+        for porto in portfolios {
+            match symbols
+                .iter_mut()
+                .find(|s| s.symbol == porto.product_symbol)
+            {
+                Some(symbol) => symbol.unrealized_pnl = porto.unrealized_pnl,
+                None => symbols.push(SymbolPnl {
+                    symbol: porto.product_symbol,
+                    realized_pnl: Money::ZERO,
+                    unrealized_pnl: porto.unrealized_pnl,
+                }),
+            }
+        }
         let response = Response {
             status: String::from("ok"),
-            message: portfolios,
+            message: DailyPnl::new(date, symbols),
+        };
+        let response_json = ser_to_str(&response).expect("Error serialize response");
+        writer
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Prices positions at the latest price of the market data feed
+    async fn mark_to_market(&self, portfolios: &mut [Portfolios]) {
+        for porto in portfolios {
+            match self.feed_price(&porto.product_symbol).await {
+                Ok(Some(price)) => porto.mark(price),
+                Ok(None) => {}
+                Err(e) => info!("error price {} {:?}", porto.product_symbol, e),
+            }
+        }
+    }
+
+    pub async fn get_market_status(&self, mut writer: impl AsyncWrite + Unpin) -> Result<()> {
+        let response = Response {
+            status: String::from("ok"),
+            message: calendar::MarketStatus::now(),
         };
         let response_json = ser_to_str(&response).expect("Error serialize response");
         writer
-            .write_all(format!("{}{}", OK_RESPONSE.to_string(), response_json).as_bytes())
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
             .await?;
 
         Ok(())
@@ -170,21 +1147,20 @@ impl Service {
         mut writer: impl AsyncWrite + Unpin,
     ) -> Result<()> {
         let account = match self.account_repo.get_account_by_user_id(user_id).await {
-            Ok(account) => GetAccountDTO {
-                balance: account.balance,
-                invested_value: account.invested_value,
+            Ok(account) => match self.settlement_repo.pending_cash(user_id).await {
+                Ok(pending_cash) => GetAccountDTO::new(account, pending_cash),
+                Err(e) => {
+                    info!("error {}", e);
+                    writer
+                        .write_all(format!("{}{}", INTERNAL_ERROR, "500 internal error").as_bytes())
+                        .await?;
+                    return Ok(());
+                }
             },
             Err(e) => {
                 info!("error {}", e);
                 writer
-                    .write_all(
-                        format!(
-                            "{}{}",
-                            UNAUTHORIZED.to_string(),
-                            "401 unathorized".to_string()
-                        )
-                        .as_bytes(),
-                    )
+                    .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
                     .await?;
                 return Ok(());
             }
@@ -195,76 +1171,262 @@ impl Service {
         };
         let response_json = ser_to_str(&response).expect("Error serialize response");
         writer
-            .write_all(format!("{}{}", OK_RESPONSE.to_string(), response_json).as_bytes())
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
             .await?;
 
         Ok(())
     }
 
-    pub async fn create_internal_order(
+    /// Ledger entries of the account, newest first, paged with `?before=<entry_id>` and
+    /// `?limit=`
+    pub async fn get_ledger(
         &self,
         request: Request,
+        user_id: i32,
         mut writer: impl AsyncWrite + Unpin,
     ) -> Result<()> {
-        let body = match request.body {
-            Some(body) => body,
-            None => {
+        let params = request.params.as_ref();
+        let before = params.and_then(|params| params.get("before"));
+        let limit = params.and_then(|params| params.get("limit"));
+        let (before, limit) = match (
+            before.map(|before| before.parse::<i32>()).transpose(),
+            limit.map(|limit| limit.parse::<i64>()).transpose(),
+        ) {
+            (Ok(before), Ok(limit)) => (
+                before,
+                limit
+                    .unwrap_or(LEDGER_PAGE_SIZE)
+                    .clamp(1, LEDGER_MAX_PAGE_SIZE),
+            ),
+            _ => {
                 writer
-                    .write_all(format!("{}{}", BAD_REQUEST.to_string(), "".to_string()).as_bytes())
+                    .write_all(format!("{}{}", BAD_REQUEST, "invalid before or limit").as_bytes())
                     .await?;
                 return Ok(());
             }
         };
-        let order_form_server = match utils::des_from_str::<OrderFormServer>(&body) {
-            Ok(order_form) => order_form,
-            Err(_) => {
+        let account = match self.account_repo.get_account_by_user_id(user_id).await {
+            Ok(account) => account,
+            Err(e) => {
+                info!("error {}", e);
+                writer
+                    .write_all(format!("{}{}", UNAUTHORIZED, "401 unathorized").as_bytes())
+                    .await?;
+                return Ok(());
+            }
+        };
+        let entries = self
+            .ledger_repo
+            .get_page(account.account_id, before, limit)
+            .await;
+        let ledger_balance = self.ledger_repo.cash_balance(account.account_id).await;
+        let page = match (entries, ledger_balance) {
+            (Ok(entries), Ok(ledger_balance)) => LedgerPage {
+                balance: account.balance,
+                ledger_balance,
+                next_before: match entries.len() as i64 == limit {
+                    true => entries.last().and_then(|entry| entry.entry_id),
+                    false => None,
+                },
+                entries,
+            },
+            (Err(e), _) | (_, Err(e)) => {
+                info!("error {}", e);
+                writer
+                    .write_all(format!("{}{}", INTERNAL_ERROR, "500 internal error").as_bytes())
+                    .await?;
+                return Ok(());
+            }
+        };
+        if page.ledger_balance != page.balance {
+            info!(
+                "ledger of account {} sums to {}, balance is {}",
+                account.account_id, page.ledger_balance, page.balance
+            );
+        }
+        let response = Response {
+            status: String::from("ok"),
+            message: page,
+        };
+        let response_json = ser_to_str(&response).expect("Error serialize response");
+        writer
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn create_internal_order(
+        &self,
+        request: Request,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let body = match request.body {
+            Some(body) => body,
+            None => {
                 writer
-                    .write_all(format!("{}{}", BAD_REQUEST.to_string(), "".to_string()).as_bytes())
+                    .write_all(format!("{}{}", BAD_REQUEST, "").as_bytes())
                     .await?;
                 return Ok(());
             }
         };
-        let order_form = OrderForm {
-            symbol: order_form_server.symbol,
-            side: order_form_server.side,
-            price: order_form_server.price,
-            lot: order_form_server.lot,
-            expiry: order_form_server.expiry,
+        let order_form_server = match utils::des_from_str::<OrderFormServer>(&body) {
+            Ok(order_form) => order_form,
+            Err(_) => {
+                writer
+                    .write_all(format!("{}{}", BAD_REQUEST, "").as_bytes())
+                    .await?;
+                return Ok(());
+            }
         };
         let user_id = order_form_server.user_id as i32;
-        match self.handle_order(order_form, user_id).await {
+        let result = self.handle_order(order_form_server.order, user_id).await;
+        Self::write_http_result(result, writer).await
+    }
+
+    pub async fn create_basket_order(
+        &self,
+        request: Request,
+        user_id: i32,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let basket_form = match request
+            .body
+            .as_deref()
+            .map(utils::des_from_str::<BasketForm>)
+        {
+            Some(Ok(basket_form)) => basket_form,
+            _ => {
+                writer
+                    .write_all(format!("{}{}", BAD_REQUEST, "").as_bytes())
+                    .await?;
+                return Ok(());
+            }
+        };
+        let result = self.handle_basket(basket_form, user_id).await;
+        Self::write_http_result(result, writer).await
+    }
+
+    pub async fn delete_order(
+        &self,
+        request: Request,
+        user_id: i32,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let result = match request.path.trim_start_matches("/order/").parse::<i32>() {
+            Ok(order_id) => self.cancel_order(order_id, user_id).await,
+            Err(_) => Err(OrderError::BadRequest),
+        };
+        Self::write_http_result(result, writer).await
+    }
+
+    /// `POST /account/deposit` and `POST /account/withdrawal`
+    pub async fn create_cash_request(
+        &self,
+        request: Request,
+        user_id: i32,
+        kind: CashKind,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let result = match request.body.as_deref().map(utils::des_from_str::<CashForm>) {
+            Some(Ok(cash_form)) => self.open_cash_request(kind, cash_form, user_id).await,
+            _ => Err(OrderError::BadRequest),
+        };
+        Self::write_http_result(result, writer).await
+    }
+
+    /// Deposits and withdrawals of the user, newest first
+    pub async fn get_cash_requests(
+        &self,
+        user_id: i32,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let requests = match self.funding_repo.get_all_by_user_id(user_id).await {
+            Ok(requests) => requests,
+            Err(e) => {
+                info!("error {}", e);
+                writer
+                    .write_all(format!("{}{}", INTERNAL_ERROR, "500 internal error").as_bytes())
+                    .await?;
+                return Ok(());
+            }
+        };
+        let response = Response {
+            status: String::from("ok"),
+            message: requests,
+        };
+        let response_json = ser_to_str(&response).expect("Error serialize response");
+        writer
+            .write_all(format!("{}{}", OK_RESPONSE, response_json).as_bytes())
+            .await?;
+
+        Ok(())
+    }
+
+    /// `POST /internal/cash/review`, approves a pending request and hands it to the
+    /// payment gateway, or rejects it
+    pub async fn review_cash_request(
+        &self,
+        request: Request,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let result = match request
+            .body
+            .as_deref()
+            .map(utils::des_from_str::<ReviewForm>)
+        {
+            Some(Ok(review_form)) => self.review_cash(review_form).await,
+            _ => Err(OrderError::BadRequest),
+        };
+        Self::write_http_result(result, writer).await
+    }
+
+    /// `POST /gateway/callback`, the payment gateway reporting on an approved request
+    pub async fn gateway_callback(
+        &self,
+        request: Request,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let result = match request
+            .body
+            .as_deref()
+            .map(utils::des_from_str::<GatewayCallback>)
+        {
+            Some(Ok(callback)) => self.complete_cash_request(callback).await,
+            _ => Err(OrderError::BadRequest),
+        };
+        Self::write_http_result(result, writer).await
+    }
+
+    async fn write_http_result(
+        result: Result<String, OrderError>,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        match result {
             Ok(res) => {
                 writer
-                    .write_all(format!("{}{}", OK_RESPONSE.to_string(), res).as_bytes())
+                    .write_all(format!("{}{}", OK_RESPONSE, res).as_bytes())
+                    .await?;
+            }
+            Err(why) if why.is_rejection() => {
+                let response = Response {
+                    status: String::from("rejected"),
+                    message: why.rejection(),
+                };
+                let response_json = ser_to_str(&response).expect("Error serialize response");
+                writer
+                    .write_all(format!("{}{}", UNPROCESSABLE_ENTITY, response_json).as_bytes())
                     .await?;
             }
             Err(why) => match why {
-                OrderError::BadRequest => {
-                    writer
-                        .write_all(
-                            format!("{}{}", BAD_REQUEST.to_string(), "".to_string()).as_bytes(),
-                        )
-                        .await?;
-                }
-                OrderError::Serde => {
-                    writer
-                        .write_all(
-                            format!("{}{}", BAD_REQUEST.to_string(), "".to_string()).as_bytes(),
-                        )
-                        .await?;
-                }
-                OrderError::Redis => {
+                OrderError::Redis | OrderError::Database => {
                     writer
-                        .write_all(
-                            format!("{}{}", INTERNAL_ERROR.to_string(), "".to_string()).as_bytes(),
-                        )
+                        .write_all(format!("{}{}", INTERNAL_ERROR, "").as_bytes())
                         .await?;
                 }
-                OrderError::Database => {
+                _ => {
                     writer
-                        .write_all(
-                            format!("{}{}", INTERNAL_ERROR.to_string(), "".to_string()).as_bytes(),
-                        )
+                        .write_all(format!("{}{}", BAD_REQUEST, "").as_bytes())
                         .await?;
                 }
             },
@@ -277,123 +1439,1642 @@ impl Service {
         order_form: OrderForm,
         user_id: i32,
     ) -> Result<String, OrderError> {
-        let mut format = format!("product:{}", &order_form.symbol);
-        let mut cache = self.redis_cache.lock().await;
-        let product = match cache.get_cached(&format).await {
-            Ok(product) => match product {
-                Some(product) => {
-                    info!("Hit cache {}", &format);
-                    product
-                }
-                None => {
-                    let product = self
-                        .product_repo
-                        .get_product_by_symbol(&order_form.symbol)
-                        .await
-                        .expect("query product error");
-                    let _ = cache.set_cache::<Product>(&format, &product).await;
-                    product
-                }
-            },
-            Err(_) => {
-                return Err(OrderError::Redis);
-            }
+        let order_id = self.submit_form(&order_form, user_id).await?;
+        let response = Response {
+            status: String::from("ok"),
+            message: order_id.to_string(),
         };
-        let order = Order::new(&order_form, user_id, product.product_id, &product.name)
-            .expect("parse order");
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    async fn submit_form(&self, order_form: &OrderForm, user_id: i32) -> Result<i32, OrderError> {
+        let product = self.get_product(&order_form.symbol).await?;
+        let mut order = Order::new(order_form, user_id, product.product_id, &product.name)?;
         info!("{:?}", order);
+        self.submit_order(&mut order, &product).await
+    }
 
-        format = format!("account:{}", user_id);
-        let account = match cache.get_cached(&format).await {
-            Ok(account) => match account {
-                Some(account) => {
-                    info!("Hit cache {}", &format);
-                    account
-                }
-                None => {
-                    let account = self
-                        .account_repo
-                        .get_account_by_user_id(user_id as i32)
-                        .await
-                        .expect("query account error");
-                    let _ = cache.set_cache::<GetAccount>(&format, &account).await;
-                    account
-                }
-            },
-            Err(_) => {
-                return Err(OrderError::Redis);
+    /// Places a basket and reports on every order of it. Without `all_or_none` the
+    /// orders go in one after the other, each checked against what the previous ones
+    /// left of the buying power.
+    async fn handle_basket(
+        &self,
+        basket_form: BasketForm,
+        user_id: i32,
+    ) -> Result<String, OrderError> {
+        let count = basket_form.basket.len();
+        if count == 0 || count > CONFIG.basket_max_orders {
+            return Err(OrderError::InvalidBasket(CONFIG.basket_max_orders));
+        }
+        let results = if basket_form.all_or_none {
+            self.place_basket(&basket_form.basket, user_id).await?
+        } else {
+            let mut results = Vec::with_capacity(count);
+            for order_form in &basket_form.basket {
+                results.push(self.submit_form(order_form, user_id).await);
             }
+            results
         };
-        let exist_porto = match self
-            .porto_repo
-            .get_by_symbol(&order_form.symbol, user_id)
+        let legs: Vec<BasketLeg> = basket_form
+            .basket
+            .iter()
+            .zip(results)
+            .map(|(order_form, result)| BasketLeg::new(&order_form.symbol, result))
+            .collect();
+        let placed = legs.iter().filter(|leg| leg.order_id.is_some()).count();
+        let status = match placed {
+            0 => "rejected",
+            placed if placed == count => "ok",
+            _ => "partial",
+        };
+        info!("Basket of {} orders {}, {} placed", count, status, placed);
+        let response = Response {
+            status: String::from(status),
+            message: legs,
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Places a one-cancels-other pair. The second leg is only placed once the first one
+    /// was accepted, and is refused along with it when the first filled as it was placed.
+    async fn handle_oco(&self, oco_form: OcoForm, user_id: i32) -> Result<String, OrderError> {
+        let [first_form, second_form] = oco_form.oco;
+        if first_form.symbol != second_form.symbol {
+            return Err(OrderError::InvalidLink("must share one symbol"));
+        }
+        if first_form.board != second_form.board {
+            return Err(OrderError::InvalidLink("must share one board"));
+        }
+        let product = self.get_product(&first_form.symbol).await?;
+        let mut first = Order::new(&first_form, user_id, product.product_id, &product.name)?;
+        let mut second = Order::new(&second_form, user_id, product.product_id, &product.name)?;
+        first.link_type = LinkType::Oco;
+        second.link_type = LinkType::Oco;
+        // both legs are checked before either of them works
+        if let Err(why) = validation::validate(&second, &product) {
+            self.reject_order(&mut second).await;
+            return Err(why);
+        }
+        info!("{:?} {:?}", first, second);
+
+        let first_id = self.submit_order(&mut first, &product).await?;
+        second.parent_order_id = Some(first_id);
+        let first = self
+            .order_repo
+            .get_by_id(first_id)
             .await
-        {
-            Ok(porto) => Some(porto),
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => None,
-                e => {
-                    println!("{:?}", e);
-                    return Err(OrderError::Database);
+            .map_err(db_error)?;
+        let second_id = if first.filled_quantity > Quantity::ZERO || !first.status.is_live() {
+            // the first leg already traded, the second one never works
+            second.transition(OrderStatus::Cancelled)?;
+            let mut tx = self.pool.begin().await.map_err(db_error)?;
+            let second_id = self
+                .order_repo
+                .insert(&mut tx, &second)
+                .await
+                .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+            second_id
+        } else {
+            let second_id = match self.submit_order(&mut second, &product).await {
+                Ok(second_id) => second_id,
+                Err(why) => {
+                    if let Err(e) = self.cancel_order(first_id, user_id).await {
+                        info!("error cancel oco leg {} {:?}", first_id, e);
+                    }
+                    return Err(why);
                 }
-            },
+            };
+            // either leg may have traded before the other one was recorded
+            self.settle_order(first_id).await?;
+            self.settle_order(second_id).await?;
+            second_id
         };
-        let total_order_form = order_form.price * (order_form.lot * 100);
-        match exist_porto {
-            Some(porto) => {
-                let new_lot = porto.lot + order_form.lot as i32;
-                let new_invested_port = porto.invested_value + total_order_form as i64;
-                let order_price: Decimal = order_form.price.into();
-                let order_lot: Decimal = order_form.lot.into();
-                let current_lot: Decimal = porto.lot.into();
-                let new_lot_dec: Decimal = new_lot.into();
-                let order_value = order_price * order_lot;
-                let existing_value = porto.avg_price * current_lot;
-                let total_value = order_value + existing_value;
-                let new_avg_price = total_value / new_lot_dec;
 
-                self.porto_repo
-                    .update(GetPortfolio::new(
-                        porto.portfolio_id,
-                        new_lot,
-                        new_invested_port,
-                        new_avg_price,
-                    ))
-                    .await
-                    .expect("error update porto");
+        let response = Response {
+            status: String::from("ok"),
+            message: vec![first_id, second_id],
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Places a bracket: the entry works like any new order, its take-profit and
+    /// stop-loss are recorded INACTIVE and activate once the entry is done
+    async fn handle_bracket(
+        &self,
+        bracket_form: BracketForm,
+        user_id: i32,
+    ) -> Result<String, OrderError> {
+        let product = self.get_product(&bracket_form.bracket.symbol).await?;
+        let mut entry = Order::new(
+            &bracket_form.bracket,
+            user_id,
+            product.product_id,
+            &product.name,
+        )?;
+        entry.link_type = LinkType::Bracket;
+        let mut take_profit = entry.bracket_leg(OrderType::Limit, bracket_form.take_profit, None);
+        let mut stop_loss =
+            entry.bracket_leg(OrderType::Stop, Price::ZERO, Some(bracket_form.stop_loss));
+        let checked = if entry.order_type.is_stop() {
+            Err(OrderError::InvalidLink(
+                "must enter with a limit or market order",
+            ))
+        } else if match entry.side {
+            Side::Buy => bracket_form.take_profit <= bracket_form.stop_loss,
+            Side::Sell => bracket_form.take_profit >= bracket_form.stop_loss,
+        } {
+            Err(OrderError::InvalidLink(
+                "need the take-profit on the profit side of the stop-loss",
+            ))
+        } else {
+            validation::validate(&take_profit, &product)
+                .and_then(|_| validation::validate(&stop_loss, &product))
+        };
+        if let Err(why) = checked {
+            self.reject_order(&mut entry).await;
+            return Err(why);
+        }
+        info!("{:?}", entry);
+
+        let entry_id = self.submit_order(&mut entry, &product).await?;
+        take_profit.parent_order_id = Some(entry_id);
+        stop_loss.parent_order_id = Some(entry_id);
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let take_profit_id = self
+            .order_repo
+            .insert(&mut tx, &take_profit)
+            .await
+            .map_err(db_error)?;
+        let stop_loss_id = self
+            .order_repo
+            .insert(&mut tx, &stop_loss)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        // the entry may be done before its exits were recorded
+        self.settle_order(entry_id).await?;
+
+        let response = Response {
+            status: String::from("ok"),
+            message: vec![entry_id, take_profit_id, stop_loss_id],
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Routes a new order by type and market phase. Refused orders are recorded as
+    /// REJECTED and `order` is left as recorded.
+    async fn submit_order(&self, order: &mut Order, product: &Product) -> Result<i32, OrderError> {
+        // placed on a copy so a rejection is recorded as the order was submitted
+        let placed = match validation::validate(order, product) {
+            Err(why) => Err(why),
+            Ok(()) => self.place_order(&mut order.clone(), product).await,
+        };
+        match placed {
+            Ok(order_id) => Ok(order_id),
+            Err(why) if why.is_rejection() => {
+                self.reject_order(order).await;
+                Err(why)
+            }
+            Err(why) => Err(why),
+        }
+    }
+
+    /// Places a basket in one transaction with every book it touches locked, so the
+    /// orders are checked together against buying power and either all of them are
+    /// placed or none. Returns the result of each order, in basket order.
+    async fn place_basket(
+        &self,
+        order_forms: &[OrderForm],
+        user_id: i32,
+    ) -> Result<Vec<Result<i32, OrderError>>, OrderError> {
+        let mut orders = Vec::with_capacity(order_forms.len());
+        let mut products = HashMap::new();
+        let mut refused = None;
+        for (index, order_form) in order_forms.iter().enumerate() {
+            let built = async {
+                let product = self.get_product(&order_form.symbol).await?;
+                let order = Order::new(order_form, user_id, product.product_id, &product.name)?;
+                validation::validate(&order, &product)?;
+                Ok::<_, OrderError>((order, product))
             }
+            .await;
+            match built {
+                Ok((order, product)) => {
+                    products.insert(product.product_id, product);
+                    orders.push(order);
+                }
+                Err(why) if refused.is_none() => refused = Some((index, why)),
+                Err(_) => {}
+            }
+        }
+        let refused = match refused {
+            Some(refused) => refused,
             None => {
-                let new_avg_price: Decimal = order_form.price.into();
-                let new = Portfolio::new(
-                    user_id,
-                    product.product_id,
-                    product.name,
-                    product.symbol,
-                    order_form.lot as i32,
-                    total_order_form as i64,
-                    new_avg_price,
-                );
-                self.porto_repo
-                    .insert(&new)
-                    .await
-                    .expect("error insert porto");
+                let mut placed = orders.clone();
+                match self.place_all(&mut placed, &products).await {
+                    Ok(()) => {
+                        return Ok(placed
+                            .iter()
+                            .map(|order| Ok(order.order_id.unwrap_or_default()))
+                            .collect());
+                    }
+                    Err(refused) => refused,
+                }
             }
+        };
+        let (refused_index, why) = refused;
+        if !why.is_rejection() {
+            return Err(why);
         }
-        // send kafka -> prevent error when do order
-        let order_id = self.order_repo.insert(&order).await.expect("error insert");
-        let total = order_form.price * (order_form.lot * 100);
-        let new_balance = &account.balance - total as i64;
-        let new_invested = &account.invested_value + total as i64;
-        let updated = GetAccount::new(new_balance, new_invested, account.account_id);
-        self.account_repo
-            .update_account(&updated)
+        for order in &mut orders {
+            self.reject_order(order).await;
+        }
+        let mut why = Some(why);
+        Ok((0..order_forms.len())
+            .map(|index| match why.take_if(|_| index == refused_index) {
+                Some(why) => Err(why),
+                None => Err(OrderError::BasketRejected),
+            })
+            .collect())
+    }
+
+    /// Routes `orders` in one transaction. Every book involved is locked up front, in
+    /// product and board order so concurrent baskets cannot deadlock. Fails with the index
+    /// of the order that could not be placed, nothing is kept then.
+    async fn place_all(
+        &self,
+        orders: &mut [Order],
+        products: &HashMap<i32, Product>,
+    ) -> Result<(), (usize, OrderError)> {
+        let phase = calendar::phase();
+        let keys: BTreeSet<(i32, Board)> = orders
+            .iter()
+            .map(|order| (order.product_id, order.board))
+            .collect();
+        let mut books = Vec::with_capacity(keys.len());
+        for (product_id, board) in keys {
+            books.push((
+                (product_id, board),
+                self.engine.book(product_id, board).await,
+            ));
+        }
+        let mut locked = Vec::with_capacity(books.len());
+        for (key, book) in &books {
+            locked.push((*key, book.lock().await));
+        }
+        let mut next_books: HashMap<(i32, Board), OrderBook> = locked
+            .iter()
+            .map(|(key, book)| (*key, (**book).clone()))
+            .collect();
+
+        let mut tx = self.pool.begin().await.map_err(|e| (0, db_error(e)))?;
+        let mut links = LinkEffects::default();
+        let mut traded = Vec::new();
+        for (index, order) in orders.iter_mut().enumerate() {
+            let product = &products[&order.product_id];
+            let book = next_books
+                .get_mut(&(order.product_id, order.board))
+                .expect("book locked for every product and board");
+            match self
+                .route(&mut tx, book, order, product, phase, &mut links)
+                .await
+            {
+                Ok(Some(price)) => traded.push((order.product_id, price)),
+                Ok(None) => {}
+                Err(why) => return Err((index, why)),
+            }
+        }
+        tx.commit().await.map_err(|e| (0, db_error(e)))?;
+        for (key, book) in &mut locked {
+            if let Some(next_book) = next_books.remove(key) {
+                **book = next_book;
+            }
+        }
+        drop(locked);
+
+        for (product_id, price) in traded {
+            self.triggers.traded(product_id, price).await;
+        }
+        for order in orders.iter() {
+            if order.status == OrderStatus::PendingTrigger {
+                self.triggers.arm(order.clone()).await;
+            }
+        }
+        self.apply_links(links).await;
+        Ok(())
+    }
+
+    /// Takes an order in by type and market phase, in `tx` and on the locked `book` of
+    /// its product and board. Returns the price of its last fill on the regular board, if
+    /// it traded there.
+    async fn route(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        book: &mut OrderBook,
+        order: &mut Order,
+        product: &Product,
+        phase: calendar::MarketPhase,
+        links: &mut LinkEffects,
+    ) -> Result<Option<Price>, OrderError> {
+        if order.order_type.is_stop() {
+            let last_price = match book.last_price() {
+                Some(price) => Some(price),
+                None => self.feed_price(&product.symbol).await?,
+            };
+            self.arm_stop(tx, order, last_price).await?;
+            return Ok(None);
+        }
+        if phase.is_continuous() {
+            return self.open_order(tx, book, order, product, links).await;
+        }
+        // outside the sessions limit orders wait for the next one,
+        // market orders have no price to wait with
+        if order.order_type == OrderType::Market {
+            return Err(OrderError::MarketClosed(phase));
+        }
+        self.queue_order(tx, order, product).await?;
+        Ok(None)
+    }
+
+    /// Persists a stop order as PENDING_TRIGGER, the caller starts watching it once
+    /// committed. Nothing is held until it triggers, the pre-trade check runs on the
+    /// order it releases. A trailing stop starts trailing from `last_price`.
+    async fn arm_stop(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &mut Order,
+        last_price: Option<Price>,
+    ) -> Result<(), OrderError> {
+        if order.order_type == OrderType::TrailingStop
+            && let Some(price) = last_price
+        {
+            order.trail(price);
+        }
+        order.transition(OrderStatus::PendingTrigger)?;
+        let order_id = self.order_repo.insert(tx, order).await.map_err(db_error)?;
+        order.order_id = Some(order_id);
+        Ok(())
+    }
+
+    /// Starts an algo order. Its window runs within today's trading day, and every slice
+    /// is checked like a new order when it is sent, a probe slice right away.
+    async fn handle_algo(&self, algo_form: AlgoForm, user_id: i32) -> Result<String, OrderError> {
+        let algo_params = algo_form.algo;
+        let product = self.get_product(&algo_params.symbol).await?;
+        let now = calendar::now();
+        let today = now.date_naive();
+        if !calendar::is_trading_day(today) {
+            return Err(OrderError::InvalidAlgo("only run on trading days"));
+        }
+        let start_at = algo_params
+            .start_time
+            .map(|start_time| calendar::at_exchange_time(today, start_time))
+            .map_or(now, |start_at| start_at.max(now));
+        let end_at = calendar::at_exchange_time(today, algo_params.end_time);
+        let mut algo = AlgoOrder::new(
+            &algo_params,
+            user_id,
+            &product,
+            start_at.to_utc(),
+            end_at.to_utc(),
+        )?;
+        let probe = Order::new(
+            &algo.child_form(MARKET_CONFIG.lot.size),
+            user_id,
+            product.product_id,
+            &product.name,
+        )?;
+        validation::validate(&probe, &product)?;
+
+        let algo_id = self.algo_repo.insert(&algo).await.map_err(db_error)?;
+        algo.algo_id = Some(algo_id);
+        info!("{:?}", algo);
+        self.notify_algo(user_id, AlgoProgress::new(&algo, &[]))
+            .await;
+        let response = Response {
+            status: String::from("ok"),
+            message: algo_id.to_string(),
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Pauses, resumes or cancels an algo, cancelling also pulls its working children
+    async fn control_algo(
+        &self,
+        control_form: AlgoControlForm,
+        user_id: i32,
+    ) -> Result<String, OrderError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut algo = match self
+            .algo_repo
+            .lock_by_id(&mut tx, control_form.algo_id)
+            .await
+        {
+            Ok(algo) if algo.user_id == user_id => algo,
+            Ok(_) | Err(sqlx::Error::RowNotFound) => return Err(OrderError::AlgoNotFound),
+            Err(e) => return Err(db_error(e)),
+        };
+        algo.transition(control_form.action.status())?;
+        self.algo_repo
+            .update(&mut tx, &algo)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        if algo.status == AlgoStatus::Cancelled {
+            self.cancel_children(&algo).await?;
+        }
+        info!("Algo {} {}", control_form.algo_id, algo.status);
+
+        let children = self
+            .order_repo
+            .get_by_algo_id(control_form.algo_id)
             .await
-            .expect("failed update account");
+            .map_err(db_error)?;
+        self.notify_algo(user_id, AlgoProgress::new(&algo, &children))
+            .await;
         let response = Response {
             status: String::from("ok"),
-            message: order_id.to_string(),
+            message: control_form.algo_id.to_string(),
         };
-        let response_json = ser_to_str(&response).expect("Error serialize response");
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
         Ok(response_json)
     }
+
+    /// Works every running or paused algo once. Returns the shares sent.
+    pub async fn work_algos(&self) -> Result<i64, OrderError> {
+        let phase = calendar::phase();
+        let mut sent = 0;
+        for algo in self.algo_repo.get_active().await.map_err(db_error)? {
+            let algo_id = algo.algo_id.unwrap_or_default();
+            match self.work_algo(algo_id, phase).await {
+                Ok(quantity) => sent += i64::from(quantity.get()),
+                Err(e) => info!("error work algo {} {:?}", algo_id, e),
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Moves an algo along its schedule: while the market trades it tops its children up
+    /// to the target quantity, and it finishes once filled or past its window. A refused
+    /// slice pauses the algo, except a market slice finding no liquidity which is sent
+    /// again next time. Returns the shares sent.
+    async fn work_algo(&self, algo_id: i32, phase: MarketPhase) -> Result<Quantity, OrderError> {
+        let now = chrono::Utc::now();
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        // held while the slice goes out, a pause or cancel waits for it
+        let mut algo = self
+            .algo_repo
+            .lock_by_id(&mut tx, algo_id)
+            .await
+            .map_err(db_error)?;
+        if !algo.status.is_active() {
+            return Ok(Quantity::ZERO);
+        }
+        let status = algo.status;
+        let children = self
+            .order_repo
+            .get_by_algo_id(algo_id)
+            .await
+            .map_err(db_error)?;
+        let progress = AlgoProgress::new(&algo, &children);
+        let mut rejection = None;
+        let mut sent = Quantity::ZERO;
+        if progress.filled_quantity >= algo.quantity {
+            algo.transition(AlgoStatus::Completed)?;
+        } else if now >= algo.end_at {
+            algo.transition(AlgoStatus::Expired)?;
+        } else if algo.status == AlgoStatus::Running
+            && now >= algo.start_at
+            && phase.is_continuous()
+        {
+            let quantity = (schedule::target_quantity(&algo, now)
+                - progress.filled_quantity
+                - progress.working_quantity)
+                .min(MARKET_CONFIG.lot.max.shares());
+            if quantity > Quantity::ZERO {
+                match self.send_slice(&algo, quantity).await {
+                    Ok(_) => sent = quantity,
+                    Err(OrderError::NoLiquidity) => {}
+                    Err(why) if why.is_rejection() => {
+                        algo.transition(AlgoStatus::Paused)?;
+                        rejection = Some(why.rejection());
+                    }
+                    Err(why) => return Err(why),
+                }
+            }
+        }
+        let changed = sent > Quantity::ZERO
+            || algo.status != status
+            || progress.filled_quantity != algo.filled_quantity;
+        if !changed {
+            return Ok(Quantity::ZERO);
+        }
+        algo.filled_quantity = progress.filled_quantity;
+        algo.avg_price = progress.avg_price;
+        self.algo_repo
+            .update(&mut tx, &algo)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        if algo.status == AlgoStatus::Expired {
+            self.cancel_children(&algo).await?;
+        }
+        if algo.status != status {
+            info!("Algo {} {}", algo_id, algo.status);
+        }
+
+        let children = self
+            .order_repo
+            .get_by_algo_id(algo_id)
+            .await
+            .map_err(db_error)?;
+        let mut progress = AlgoProgress::new(&algo, &children);
+        progress.rejection = rejection;
+        self.notify_algo(algo.user_id, progress).await;
+        Ok(sent)
+    }
+
+    /// Sends one slice of an algo through the same checks and routing as any new order
+    async fn send_slice(&self, algo: &AlgoOrder, quantity: Quantity) -> Result<i32, OrderError> {
+        let product = self.get_product(&algo.product_symbol).await?;
+        let mut order = Order::new(
+            &algo.child_form(quantity),
+            algo.user_id,
+            product.product_id,
+            &product.name,
+        )?;
+        order.algo_id = algo.algo_id;
+        info!("{:?}", order);
+        self.submit_order(&mut order, &product).await
+    }
+
+    /// Cancels the children of an algo that are still working
+    async fn cancel_children(&self, algo: &AlgoOrder) -> Result<(), OrderError> {
+        let children = self
+            .order_repo
+            .get_by_algo_id(algo.algo_id.unwrap_or_default())
+            .await
+            .map_err(db_error)?;
+        for child in children.iter().filter(|child| child.status.is_working()) {
+            let child_id = child.order_id.unwrap_or_default();
+            if let Err(e) = self.cancel_order(child_id, algo.user_id).await {
+                info!("error cancel algo child {} {:?}", child_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn wait_for_trade(&self) {
+        self.triggers.wait_for_trade().await
+    }
+
+    /// Releases stop orders reached by the last price, taken from trades of the internal
+    /// matcher or, for products that did not trade since, from the Redis price feed.
+    /// Stops only trigger during continuous trading. Returns how many were released.
+    pub async fn check_triggers(&self) -> Result<usize, OrderError> {
+        let mut prices = self.triggers.take_traded().await;
+        if !calendar::phase().is_continuous() {
+            return Ok(0);
+        }
+        for (product_id, symbol) in self.triggers.watched().await {
+            if prices.contains_key(&product_id) {
+                continue;
+            }
+            if let Some(price) = self.feed_price(&symbol).await? {
+                prices.insert(product_id, price);
+            }
+        }
+        let mut released = 0;
+        for (product_id, price) in prices {
+            let trailed = self.triggers.trail(product_id, price).await;
+            if !trailed.is_empty() {
+                // persisted so a restart picks the trail up where it was
+                let mut tx = self.pool.begin().await.map_err(db_error)?;
+                for stop in &trailed {
+                    self.order_repo
+                        .update_trigger(&mut tx, stop)
+                        .await
+                        .map_err(db_error)?;
+                }
+                tx.commit().await.map_err(db_error)?;
+            }
+            for stop in self.triggers.take_triggered(product_id, price).await {
+                let stop_id = stop.order_id.unwrap_or_default();
+                match self.trigger_stop(stop_id, price).await {
+                    Ok(()) => released += 1,
+                    Err(e) => info!("error trigger stop order {} {:?}", stop_id, e),
+                }
+            }
+        }
+        Ok(released)
+    }
+
+    /// Marks the stop TRIGGERED and submits the order it releases like any new order
+    async fn trigger_stop(&self, stop_id: i32, price: Price) -> Result<(), OrderError> {
+        let stop = self.order_repo.get_by_id(stop_id).await.map_err(db_error)?;
+        let product = self.get_product(&stop.product_symbol).await?;
+        // siblings of a linked stop come off the book along with the trigger
+        let book = self.engine.book(product.product_id, stop.board).await;
+        let mut book = book.lock().await;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut stop = self
+            .order_repo
+            .lock_by_id(&mut tx, stop_id)
+            .await
+            .map_err(db_error)?;
+        // cancelled or expired since it was armed
+        if stop.status != OrderStatus::PendingTrigger {
+            return Ok(());
+        }
+        stop.transition(OrderStatus::Triggered)?;
+        stop.triggered_price = Some(price);
+        self.order_repo
+            .update_progress(&mut tx, &stop)
+            .await
+            .map_err(db_error)?;
+        self.order_repo
+            .update_trigger(&mut tx, &stop)
+            .await
+            .map_err(db_error)?;
+        let mut next_book = book.clone();
+        let mut links = LinkEffects::default();
+        self.settle_links(&mut tx, &mut next_book, &stop, &mut links)
+            .await?;
+        tx.commit().await.map_err(db_error)?;
+        *book = next_book;
+        // the released order takes the book lock again
+        drop(book);
+        info!("Stop order {} triggered at {}", stop_id, price);
+        self.notify_order(&stop).await;
+        self.apply_links(links).await;
+
+        let mut order = stop.triggered();
+        match self.submit_order(&mut order, &product).await {
+            Ok(order_id) => {
+                let order = self
+                    .order_repo
+                    .get_by_id(order_id)
+                    .await
+                    .map_err(db_error)?;
+                self.notify_order(&order).await;
+            }
+            Err(why) if why.is_rejection() => self.notify_order(&order).await,
+            Err(why) => return Err(why),
+        }
+        Ok(())
+    }
+
+    /// Price published to `last_price:{symbol}` by the market data feed
+    async fn feed_price(&self, symbol: &str) -> Result<Option<Price>, OrderError> {
+        self.redis_cache
+            .lock()
+            .await
+            .get_cached::<Price>(&format!("last_price:{}", symbol))
+            .await
+            .map_err(|_| OrderError::Redis)
+    }
+
+    async fn get_product(&self, symbol: &str) -> Result<Product, OrderError> {
+        let format = format!("product:{}", symbol);
+        let mut cache = self.redis_cache.lock().await;
+        match cache.get_cached(&format).await {
+            Ok(Some(product)) => {
+                info!("Hit cache {}", &format);
+                Ok(product)
+            }
+            Ok(None) => {
+                let product = match self.product_repo.get_product_by_symbol(symbol).await {
+                    Ok(product) => product,
+                    Err(sqlx::Error::RowNotFound) => return Err(OrderError::BadRequest),
+                    Err(e) => return Err(db_error(e)),
+                };
+                let _ = cache.set_cache::<Product>(&format, &product).await;
+                Ok(product)
+            }
+            Err(_) => Err(OrderError::Redis),
+        }
+    }
+
+    /// Takes a validated order in, in its own transaction. Hold, order and fills of both
+    /// sides commit or roll back together.
+    async fn place_order(&self, order: &mut Order, product: &Product) -> Result<i32, OrderError> {
+        // the book lock is taken before any row lock and held until commit
+        let book = self.engine.book(product.product_id, order.board).await;
+        let mut book = book.lock().await;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        // match on a copy, the live book only changes once the fills are committed
+        let mut next_book = book.clone();
+        let mut links = LinkEffects::default();
+        let traded = self
+            .route(
+                &mut tx,
+                &mut next_book,
+                order,
+                product,
+                calendar::phase(),
+                &mut links,
+            )
+            .await?;
+
+        tx.commit().await.map_err(db_error)?;
+        *book = next_book;
+        if let Some(price) = traded {
+            self.triggers.traded(product.product_id, price).await;
+        }
+        drop(book);
+        if order.status == OrderStatus::PendingTrigger {
+            self.triggers.arm(order.clone()).await;
+        }
+        self.apply_links(links).await;
+        Ok(order.order_id.unwrap_or_default())
+    }
+
+    /// Accepts the order during continuous trading: puts its cash or shares on hold,
+    /// persists it as OPEN and matches it against `book`
+    async fn open_order(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        book: &mut OrderBook,
+        order: &mut Order,
+        product: &Product,
+        links: &mut LinkEffects,
+    ) -> Result<Option<Price>, OrderError> {
+        if order.order_type == OrderType::Market {
+            // priced at the edge of the protection band around the last trade. The book
+            // forgets it on a restart, the persisted reference price stands in until the
+            // next trade, and the best opposite quote for a product without either
+            let best_opposite = match order.side {
+                Side::Buy => book.best_ask(),
+                Side::Sell => book.best_bid(),
+            };
+            let reference = book
+                .last_price()
+                .or(product.reference_price)
+                .or(best_opposite)
+                .ok_or(OrderError::NoLiquidity)?;
+            let protection = risk::market_protection_price(order.side, reference);
+            order.price = validation::clamp_to_band(protection, order.side, product);
+        }
+        self.hold_for(tx, order, product).await?;
+        order.transition(OrderStatus::Open)?;
+        // send kafka -> prevent error when do order
+        let order_id = self.order_repo.insert(tx, order).await.map_err(db_error)?;
+        order.order_id = Some(order_id);
+        self.execute(tx, book, order, product, links).await
+    }
+
+    /// Takes an order in while the market is not matching: its cash or shares go on hold
+    /// right away and it waits as NEW for the next session
+    async fn queue_order(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &mut Order,
+        product: &Product,
+    ) -> Result<(), OrderError> {
+        self.hold_for(tx, order, product).await?;
+        let order_id = self.order_repo.insert(tx, order).await.map_err(db_error)?;
+        order.order_id = Some(order_id);
+        Ok(())
+    }
+
+    /// Runs the pre-trade check against the locked account and portfolio rows and puts
+    /// the order's cash or shares on hold
+    async fn hold_for(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+        product: &Product,
+    ) -> Result<(), OrderError> {
+        // buying power is checked against the locked row, not the cache
+        let account = self
+            .account_repo
+            .lock_by_user_id(tx, order.user_id)
+            .await
+            .map_err(db_error)?;
+        let exist_porto = self
+            .porto_repo
+            .get_by_symbol(tx, &product.symbol, order.user_id)
+            .await
+            .map(Some)
+            .or_else(|e| match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                e => Err(db_error(e)),
+            })?;
+        let on_hold = risk::check(order, &account, exist_porto.as_ref())?;
+        match (order.side, exist_porto) {
+            (Side::Buy, _) => self
+                .account_repo
+                .reserve(tx, account.account_id, on_hold)
+                .await
+                .map_err(db_error)?,
+            (Side::Sell, Some(porto)) => self
+                .porto_repo
+                .hold_shares(tx, porto.portfolio_id, order.quantity)
+                .await
+                .map_err(db_error)?,
+            (Side::Sell, None) => return Err(OrderError::InsufficientShares),
+        }
+        Ok(())
+    }
+
+    /// Matches an accepted order against `book` and applies the fills of both sides.
+    /// Whatever a market order cannot fill is cancelled, a limit order rests.
+    /// Linked orders of either side are settled along. Returns the price of the last
+    /// fill on the regular board, the only one stops are triggered by, if there was one.
+    async fn execute(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        book: &mut OrderBook,
+        order: &mut Order,
+        product: &Product,
+        links: &mut LinkEffects,
+    ) -> Result<Option<Price>, OrderError> {
+        let fills = if order.order_type.is_priced() {
+            book.submit(order.side, RestingOrder::from(&*order))
+        } else {
+            let mut incoming = RestingOrder::from(&*order);
+            book.match_order(order.side, &mut incoming)
+        };
+        let traded = fills
+            .last()
+            .map(|fill| fill.price)
+            .filter(|_| order.board.is_regular());
+        let mut makers = Vec::new();
+        for fill in fills {
+            self.apply_fill(tx, order, product, fill.price, fill.quantity)
+                .await?;
+            let mut maker = self
+                .order_repo
+                .lock_by_id(tx, fill.maker_order_id)
+                .await
+                .map_err(db_error)?;
+            self.apply_fill(tx, &mut maker, product, fill.price, fill.quantity)
+                .await?;
+            if !makers.contains(&fill.maker_order_id) {
+                makers.push(fill.maker_order_id);
+            }
+        }
+
+        if order.order_type == OrderType::Market && order.remaining_quantity() > Quantity::ZERO {
+            // market orders never rest, whatever the band did not reach is cancelled
+            if order.filled_quantity.is_zero() {
+                return Err(OrderError::NoLiquidity);
+            }
+            self.release_hold(tx, order).await?;
+            order.transition(OrderStatus::Cancelled)?;
+            self.order_repo
+                .update_progress(tx, order)
+                .await
+                .map_err(db_error)?;
+        }
+
+        self.settle_links(tx, book, order, links).await?;
+        for maker_id in makers {
+            let maker = self
+                .order_repo
+                .lock_by_id(tx, maker_id)
+                .await
+                .map_err(db_error)?;
+            self.settle_links(tx, book, &maker, links).await?;
+        }
+        Ok(traded)
+    }
+
+    /// Keeps a record of orders refused by the pre-trade checks
+    async fn reject_order(&self, order: &mut Order) {
+        if order.transition(OrderStatus::Rejected).is_err() {
+            return;
+        }
+        let result = async {
+            let mut tx = self.pool.begin().await?;
+            let order_id = self.order_repo.insert(&mut tx, order).await?;
+            tx.commit().await?;
+            Ok::<_, sqlx::Error>(order_id)
+        }
+        .await;
+        match result {
+            Ok(order_id) => order.order_id = Some(order_id),
+            Err(e) => info!("error record rejected order {}", e),
+        }
+    }
+
+    /// Records a trade against the order and moves cash and shares for it.
+    /// This is the only place where portfolio and balance change for an order.
+    async fn apply_fill(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &mut Order,
+        product: &Product,
+        price: Price,
+        quantity: Quantity,
+    ) -> Result<(), OrderError> {
+        order.fill(price, quantity)?;
+        let mut trade = Trade::new(order, price, quantity);
+        trade.fees = fee::for_fill(order.side, trade.value(), order.fees.commission);
+        order.fees = order
+            .fees
+            .checked_add(trade.fees)
+            .ok_or(OrderError::OVERFLOW)?;
+        let trade_id = self.trade_repo.insert(tx, &trade).await.map_err(db_error)?;
+        self.order_repo
+            .update_progress(tx, order)
+            .await
+            .map_err(db_error)?;
+
+        let account = self
+            .account_repo
+            .lock_by_user_id(tx, order.user_id)
+            .await
+            .map_err(db_error)?;
+        let exist_porto = self
+            .porto_repo
+            .get_by_symbol(tx, &product.symbol, order.user_id)
+            .await
+            .map(Some)
+            .or_else(|e| match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                e => Err(db_error(e)),
+            })?;
+        let total = trade.value();
+        let fees = trade.fees.checked_total().ok_or(OrderError::OVERFLOW)?;
+        let (balance_delta, invested_delta) = match order.side {
+            Side::Buy => {
+                // fees are part of what the position cost
+                let cost = total.checked_add(fees).ok_or(OrderError::OVERFLOW)?;
+                match exist_porto {
+                    Some(porto) => {
+                        // open buys can add up past what one position holds
+                        let new_shares = porto
+                            .shares
+                            .checked_add(quantity)
+                            .ok_or(OrderError::OVERFLOW)?;
+                        let new_invested_port = porto
+                            .invested_value
+                            .checked_add(cost)
+                            .ok_or(OrderError::OVERFLOW)?;
+                        let new_avg_price = portfolio::avg_price(new_invested_port, new_shares);
+
+                        self.porto_repo
+                            .update(
+                                tx,
+                                GetPortfolio::new(
+                                    porto.portfolio_id,
+                                    new_shares,
+                                    porto.reserved_shares,
+                                    new_invested_port,
+                                    new_avg_price,
+                                ),
+                            )
+                            .await
+                            .map_err(db_error)?;
+                    }
+                    None => {
+                        let new_avg_price = portfolio::avg_price(cost, quantity);
+                        let new = Portfolio::new(
+                            order.user_id,
+                            product.product_id,
+                            product.name.clone(),
+                            product.symbol.clone(),
+                            quantity,
+                            cost,
+                            new_avg_price,
+                        );
+                        self.porto_repo.insert(tx, &new).await.map_err(db_error)?;
+                    }
+                }
+                // the hold was taken at the limit price
+                self.account_repo
+                    .release(tx, account.account_id, cash_hold(order.price, quantity)?)
+                    .await
+                    .map_err(db_error)?;
+                self.pnl_repo
+                    .open_lot(
+                        tx,
+                        trade_id,
+                        order.user_id,
+                        product.product_id,
+                        quantity,
+                        cost,
+                    )
+                    .await
+                    .map_err(db_error)?;
+                (-cost, cost)
+            }
+            Side::Sell => {
+                let porto = exist_porto.ok_or(OrderError::InsufficientShares)?;
+                // release the sold shares at their share of the cost basis
+                let new_shares = porto
+                    .shares
+                    .checked_sub(quantity)
+                    .ok_or(OrderError::OVERFLOW)?;
+                let reserved_shares = porto
+                    .reserved_shares
+                    .checked_sub(quantity)
+                    .ok_or(OrderError::OVERFLOW)?;
+                let cost_basis = self
+                    .close_lots(tx, order.user_id, product.product_id, &porto, quantity)
+                    .await?;
+                let invested_value = porto
+                    .invested_value
+                    .checked_sub(cost_basis)
+                    .ok_or(OrderError::OVERFLOW)?;
+                let proceeds = total.checked_sub(fees).ok_or(OrderError::OVERFLOW)?;
+                let pnl = RealizedPnl::new(
+                    trade_id,
+                    &trade,
+                    &product.symbol,
+                    proceeds,
+                    cost_basis,
+                    CONFIG.pnl_method,
+                );
+                self.pnl_repo
+                    .insert_realized(tx, &pnl)
+                    .await
+                    .map_err(db_error)?;
+                if new_shares.is_zero() {
+                    self.porto_repo
+                        .delete(tx, porto.portfolio_id)
+                        .await
+                        .map_err(db_error)?;
+                } else {
+                    self.porto_repo
+                        .update(
+                            tx,
+                            GetPortfolio::new(
+                                porto.portfolio_id,
+                                new_shares,
+                                reserved_shares,
+                                invested_value,
+                                portfolio::avg_price(invested_value, new_shares),
+                            ),
+                        )
+                        .await
+                        .map_err(db_error)?;
+                }
+                (proceeds, -cost_basis)
+            }
+        };
+        self.account_repo
+            .adjust_invested(tx, account.account_id, invested_delta)
+            .await
+            .map_err(db_error)?;
+        for entry in JournalEntry::for_trade(account.account_id, trade_id, &trade) {
+            self.post_entry(tx, entry).await?;
+        }
+        // the balance moves on trade date, the obligation settles it later
+        self.settlement_repo
+            .insert(tx, &Settlement::new(trade_id, &trade, balance_delta))
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Opens a deposit or withdrawal. A withdrawal may only take settled cash that no open
+    /// order needs and holds it from here on.
+    async fn open_cash_request(
+        &self,
+        kind: CashKind,
+        cash_form: CashForm,
+        user_id: i32,
+    ) -> Result<String, OrderError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let account = self
+            .account_repo
+            .lock_by_user_id(&mut tx, user_id)
+            .await
+            .map_err(db_error)?;
+        let account_id = account.account_id;
+        let mut cash = CashRequest::new(kind, cash_form.amount, user_id, account_id)?;
+        if kind == CashKind::Withdrawal {
+            let pending_cash = self
+                .settlement_repo
+                .pending_cash(user_id)
+                .await
+                .map_err(db_error)?;
+            let withdrawable = GetAccountDTO::new(account, pending_cash).withdrawable;
+            if cash.amount > withdrawable {
+                return Err(OrderError::WithdrawalLimit(withdrawable));
+            }
+            self.account_repo
+                .reserve(&mut tx, account_id, cash.amount)
+                .await
+                .map_err(db_error)?;
+        }
+        let request_id = self
+            .funding_repo
+            .insert(&mut tx, &cash)
+            .await
+            .map_err(db_error)?;
+        cash.request_id = Some(request_id);
+        self.funding_repo
+            .log(
+                &mut tx,
+                &CashLog {
+                    request_id,
+                    from_status: None,
+                    to_status: cash.status,
+                    actor: format!("user:{}", user_id),
+                    note: None,
+                },
+            )
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        info!(
+            "Cash request {} {} of {} opened",
+            request_id, kind, cash.amount
+        );
+
+        let response = Response {
+            status: String::from("ok"),
+            message: request_id.to_string(),
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    async fn review_cash(&self, review_form: ReviewForm) -> Result<String, OrderError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut cash = match self
+            .funding_repo
+            .lock_by_id(&mut tx, review_form.request_id)
+            .await
+        {
+            Ok(cash) => cash,
+            Err(sqlx::Error::RowNotFound) => return Err(OrderError::CashRequestNotFound),
+            Err(e) => return Err(db_error(e)),
+        };
+        let next = match review_form.approve {
+            true => CashStatus::Approved,
+            false => CashStatus::Rejected,
+        };
+        if next == CashStatus::Approved {
+            cash.gateway_ref = Some(gateway::reference(&cash));
+        }
+        self.move_cash_request(
+            &mut tx,
+            &mut cash,
+            next,
+            &review_form.reviewer,
+            review_form.note,
+        )
+        .await?;
+        tx.commit().await.map_err(db_error)?;
+        if next == CashStatus::Approved {
+            gateway::submit(self.clone(), &cash);
+        }
+
+        let response = Response {
+            status: String::from("ok"),
+            message: review_form.request_id.to_string(),
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Completes or rejects an approved request as the payment gateway reports it
+    pub async fn complete_cash_request(
+        &self,
+        callback: GatewayCallback,
+    ) -> Result<String, OrderError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut cash = match self
+            .funding_repo
+            .lock_by_id(&mut tx, callback.request_id)
+            .await
+        {
+            // a reference the gateway was never given is as good as an unknown request
+            Ok(cash) if cash.gateway_ref.as_deref() == Some(callback.gateway_ref.as_str()) => cash,
+            Ok(_) | Err(sqlx::Error::RowNotFound) => return Err(OrderError::CashRequestNotFound),
+            Err(e) => return Err(db_error(e)),
+        };
+        let next = match callback.status {
+            GatewayStatus::Success => CashStatus::Completed,
+            GatewayStatus::Failed => CashStatus::Rejected,
+        };
+        self.move_cash_request(&mut tx, &mut cash, next, "gateway", callback.reason)
+            .await?;
+        tx.commit().await.map_err(db_error)?;
+
+        let response = Response {
+            status: String::from("ok"),
+            message: callback.request_id.to_string(),
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Moves a cash request to `next` and the account with it: a withdrawal gives back
+    /// its hold once it is rejected or completed, completing books the money in the ledger
+    async fn move_cash_request(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        cash: &mut CashRequest,
+        next: CashStatus,
+        actor: &str,
+        note: Option<String>,
+    ) -> Result<(), OrderError> {
+        let from = cash.status;
+        cash.transition(next)?;
+        let account = self
+            .account_repo
+            .lock_by_user_id(tx, cash.user_id)
+            .await
+            .map_err(db_error)?;
+        if cash.kind == CashKind::Withdrawal
+            && matches!(next, CashStatus::Rejected | CashStatus::Completed)
+        {
+            self.account_repo
+                .release(tx, account.account_id, cash.amount)
+                .await
+                .map_err(db_error)?;
+        }
+        if next == CashStatus::Completed {
+            self.post_entry(tx, JournalEntry::for_cash(cash)).await?;
+        }
+        self.funding_repo.update(tx, cash).await.map_err(db_error)?;
+        self.funding_repo
+            .log(
+                tx,
+                &CashLog {
+                    request_id: cash.request_id.unwrap_or_default(),
+                    from_status: Some(from),
+                    to_status: next,
+                    actor: actor.to_string(),
+                    note,
+                },
+            )
+            .await
+            .map_err(db_error)?;
+        info!(
+            "Cash request {} {} -> {} by {}",
+            cash.request_id.unwrap_or_default(),
+            from,
+            next,
+            actor
+        );
+        Ok(())
+    }
+
+    /// Books the entry and moves the balance by its cash. Every balance change goes
+    /// through here, the account row must already be locked.
+    async fn post_entry(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        mut entry: JournalEntry,
+    ) -> Result<(), OrderError> {
+        entry.balance_after = self
+            .account_repo
+            .post_cash(tx, entry.account_id, entry.cash())
+            .await
+            .map_err(db_error)?;
+        self.ledger_repo
+            .insert(tx, &entry)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Takes `shares` sold out of the open lots of the position, oldest first, and returns
+    /// what they cost under the configured P&L method. Closing the position takes all of it.
+    async fn close_lots(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+        product_id: i32,
+        porto: &GetPortfolio,
+        shares: Quantity,
+    ) -> Result<Money, OrderError> {
+        let closing = shares == porto.shares;
+        let open_lots = self
+            .pnl_repo
+            .lock_open_lots(tx, user_id, product_id)
+            .await
+            .map_err(db_error)?;
+        let mut left = shares;
+        let mut fifo_cost = Money::ZERO;
+        for open in open_lots {
+            if left.is_zero() && !closing {
+                break;
+            }
+            let take = if closing {
+                open.shares
+            } else {
+                left.min(open.shares)
+            };
+            let cost = open.cost.pro_rata(take, open.shares);
+            let kept_shares = open.shares.checked_sub(take);
+            let kept_cost = open.cost.checked_sub(cost);
+            let (kept_shares, kept_cost) =
+                kept_shares.zip(kept_cost).ok_or(OrderError::OVERFLOW)?;
+            self.pnl_repo
+                .reduce_lot(tx, open.lot_id, kept_shares, kept_cost)
+                .await
+                .map_err(db_error)?;
+            fifo_cost = fifo_cost.checked_add(cost).ok_or(OrderError::OVERFLOW)?;
+            left = left
+                .checked_sub(take.min(left))
+                .ok_or(OrderError::OVERFLOW)?;
+        }
+        if closing {
+            return Ok(porto.invested_value);
+        }
+        match CONFIG.pnl_method {
+            PnlMethod::Average => Ok(porto.invested_value.pro_rata(shares, porto.shares)),
+            // shares held from before lots were tracked go at the average cost
+            PnlMethod::Fifo => fifo_cost
+                .checked_add(porto.invested_value.pro_rata(left, porto.shares))
+                .ok_or(OrderError::OVERFLOW),
+        }
+    }
+
+    /// Pulls a working order and gives back whatever it still holds
+    async fn cancel_order(&self, order_id: i32, user_id: i32) -> Result<String, OrderError> {
+        let (product_id, board) = match self.order_repo.get_by_id(order_id).await {
+            Ok(order) => (order.product_id, order.board),
+            Err(sqlx::Error::RowNotFound) => return Err(OrderError::OrderNotFound),
+            Err(e) => return Err(db_error(e)),
+        };
+        // same lock order as placing: book first, then rows
+        let book = self.engine.book(product_id, board).await;
+        let mut book = book.lock().await;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut order = match self.order_repo.lock_by_id(&mut tx, order_id).await {
+            Ok(order) if order.user_id == user_id => order,
+            Ok(_) | Err(sqlx::Error::RowNotFound) => return Err(OrderError::OrderNotFound),
+            Err(e) => return Err(db_error(e)),
+        };
+        self.release_hold(&mut tx, &order).await?;
+        order.transition(OrderStatus::Cancelled)?;
+        self.order_repo
+            .update_progress(&mut tx, &order)
+            .await
+            .map_err(db_error)?;
+        let mut next_book = book.clone();
+        next_book.cancel(order_id);
+        let mut links = LinkEffects::default();
+        self.settle_links(&mut tx, &mut next_book, &order, &mut links)
+            .await?;
+        tx.commit().await.map_err(db_error)?;
+        *book = next_book;
+        drop(book);
+        self.triggers.remove(order_id).await;
+        self.apply_links(links).await;
+
+        let response = Response {
+            status: String::from("ok"),
+            message: order_id.to_string(),
+        };
+        let response_json = ser_to_str(&response).map_err(|_| OrderError::Serde)?;
+        Ok(response_json)
+    }
+
+    /// Keeps the group of `order` consistent after it moved, in the transaction that moved
+    /// it and on the book of its product. A bracket entry that is done activates its
+    /// exits for the quantity it filled, or cancels them when nothing filled. Any other leg
+    /// that traded, triggered or was cancelled cancels its live siblings.
+    async fn settle_links(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        book: &mut OrderBook,
+        order: &Order,
+        links: &mut LinkEffects,
+    ) -> Result<(), OrderError> {
+        let Some(order_id) = order.order_id else {
+            return Ok(());
+        };
+        if order.link_type == LinkType::Single {
+            return Ok(());
+        }
+        let root_id = order.parent_order_id.unwrap_or(order_id);
+        let group = self
+            .order_repo
+            .lock_linked(tx, root_id)
+            .await
+            .map_err(db_error)?;
+
+        if order.is_bracket_entry() {
+            if order.status.is_live() {
+                return Ok(());
+            }
+            let product = self.get_product(&order.product_symbol).await?;
+            for mut leg in group {
+                if leg.status != OrderStatus::Inactive {
+                    continue;
+                }
+                if order.filled_quantity > Quantity::ZERO {
+                    self.activate_leg(tx, &mut leg, order.filled_quantity, &product, links)
+                        .await?;
+                } else {
+                    self.cancel_leg(tx, book, &mut leg, links).await?;
+                }
+            }
+            return Ok(());
+        }
+
+        let done = order.filled_quantity > Quantity::ZERO
+            || matches!(
+                order.status,
+                OrderStatus::Cancelled | OrderStatus::Triggered
+            );
+        if !done {
+            return Ok(());
+        }
+        for mut sibling in group {
+            // a bracket exit leaves the entry it closes alone
+            if sibling.order_id == Some(order_id)
+                || sibling.is_bracket_entry()
+                || !sibling.status.is_live()
+            {
+                continue;
+            }
+            self.cancel_leg(tx, book, &mut sibling, links).await?;
+        }
+        Ok(())
+    }
+
+    /// Sizes an INACTIVE bracket exit to `quantity` and lets it work: a stop waits for its
+    /// trigger, a limit takes its hold and waits as NEW for the next release. An exit
+    /// whose hold does not pass the pre-trade check is rejected.
+    async fn activate_leg(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        leg: &mut Order,
+        quantity: Quantity,
+        product: &Product,
+        links: &mut LinkEffects,
+    ) -> Result<(), OrderError> {
+        leg.quantity = quantity;
+        let status = if leg.order_type.is_stop() {
+            OrderStatus::PendingTrigger
+        } else {
+            match self.hold_for(tx, leg, product).await {
+                Ok(()) => OrderStatus::New,
+                Err(why) if why.is_rejection() => {
+                    info!("Bracket leg {:?} rejected {:?}", leg.order_id, why);
+                    OrderStatus::Rejected
+                }
+                Err(why) => return Err(why),
+            }
+        };
+        leg.transition(status)?;
+        self.order_repo.activate(tx, leg).await.map_err(db_error)?;
+        match leg.status {
+            OrderStatus::PendingTrigger => links.armed.push(leg.clone()),
+            OrderStatus::New => links.queued = true,
+            _ => {}
+        }
+        links.updated.push(leg.clone());
+        Ok(())
+    }
+
+    /// Cancels a live leg of a group being settled and gives back its hold
+    async fn cancel_leg(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        book: &mut OrderBook,
+        leg: &mut Order,
+        links: &mut LinkEffects,
+    ) -> Result<(), OrderError> {
+        self.release_hold(tx, leg).await?;
+        leg.transition(OrderStatus::Cancelled)?;
+        self.order_repo
+            .update_progress(tx, leg)
+            .await
+            .map_err(db_error)?;
+        let leg_id = leg.order_id.unwrap_or_default();
+        book.cancel(leg_id);
+        links.disarmed.push(leg_id);
+        links.updated.push(leg.clone());
+        Ok(())
+    }
+
+    /// Settles the group of an order that may have moved before the rest of its group
+    /// was recorded
+    async fn settle_order(&self, order_id: i32) -> Result<(), OrderError> {
+        let order = self
+            .order_repo
+            .get_by_id(order_id)
+            .await
+            .map_err(db_error)?;
+        let book = self.engine.book(order.product_id, order.board).await;
+        let mut book = book.lock().await;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let order = self
+            .order_repo
+            .lock_by_id(&mut tx, order_id)
+            .await
+            .map_err(db_error)?;
+        let mut next_book = book.clone();
+        let mut links = LinkEffects::default();
+        self.settle_links(&mut tx, &mut next_book, &order, &mut links)
+            .await?;
+        tx.commit().await.map_err(db_error)?;
+        *book = next_book;
+        drop(book);
+        self.apply_links(links).await;
+        Ok(())
+    }
+
+    /// Carries out what settling linked orders left for after the commit
+    async fn apply_links(&self, links: LinkEffects) {
+        for order_id in links.disarmed {
+            self.triggers.remove(order_id).await;
+        }
+        for stop in links.armed {
+            self.triggers.arm(stop).await;
+        }
+        if links.queued {
+            self.queued.notify_one();
+        }
+        for order in &links.updated {
+            self.notify_order(order).await;
+        }
+    }
+
+    /// Resolves once activated bracket exits were queued for release
+    pub async fn wait_for_queued(&self) {
+        self.queued.notified().await
+    }
+
+    /// Gives back the cash or shares held for the unfilled part of a working order
+    async fn release_hold(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<(), OrderError> {
+        self.shift_hold(tx, order, -1).await
+    }
+
+    /// Holds again for the unfilled part of a working order whose quantity or price changed
+    async fn retake_hold(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+    ) -> Result<(), OrderError> {
+        self.shift_hold(tx, order, 1).await
+    }
+
+    async fn shift_hold(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order: &Order,
+        sign: i32,
+    ) -> Result<(), OrderError> {
+        if !order.status.is_working() {
+            return Ok(());
+        }
+        let remaining = order.remaining_quantity();
+        match order.side {
+            Side::Buy => {
+                let account = self
+                    .account_repo
+                    .lock_by_user_id(tx, order.user_id)
+                    .await
+                    .map_err(db_error)?;
+                self.account_repo
+                    .reserve(
+                        tx,
+                        account.account_id,
+                        cash_hold(order.price, remaining)?
+                            .checked_times(sign.into())
+                            .ok_or(OrderError::OVERFLOW)?,
+                    )
+                    .await
+                    .map_err(db_error)?;
+            }
+            Side::Sell => {
+                let porto = self
+                    .porto_repo
+                    .get_by_symbol(tx, &order.product_symbol, order.user_id)
+                    .await
+                    .map_err(db_error)?;
+                self.porto_repo
+                    .hold_shares(
+                        tx,
+                        porto.portfolio_id,
+                        remaining.checked_times(sign).ok_or(OrderError::OVERFLOW)?,
+                    )
+                    .await
+                    .map_err(db_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cash a buy holds, too large to hold is a rejection
+fn cash_hold(price: Price, quantity: Quantity) -> Result<Money, OrderError> {
+    fee::buy_hold(price, quantity).ok_or(OrderError::OVERFLOW)
+}
+
+fn db_error(e: sqlx::Error) -> OrderError {
+    info!("error query {}", e);
+    OrderError::Database
 }
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::fee::Fees;
+use crate::order::model::{Order, Side};
+use crate::types::{Money, Price, Quantity};
+
+/// One execution against an order, cash and portfolio only move on trades
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct Trade {
+    pub trade_id: Option<i32>,
+    pub order_id: i32,
+    pub user_id: i32,
+    pub product_id: i32,
+    #[sqlx(try_from = "String")]
+    pub side: Side,
+    pub price: Price,
+    /// Shares traded
+    pub quantity: Quantity,
+    /// Charged on top of the value, set once the fill is priced
+    #[sqlx(flatten)]
+    pub fees: Fees,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Trade {
+    pub fn new(order: &Order, price: Price, quantity: Quantity) -> Self {
+        Self {
+            trade_id: None,
+            order_id: order.order_id.unwrap_or_default(),
+            user_id: order.user_id,
+            product_id: order.product_id,
+            side: order.side,
+            price,
+            quantity,
+            fees: Fees::default(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn value(&self) -> Money {
+        self.price * self.quantity
+    }
+}
@@ -0,0 +1,57 @@
+use sqlx::{Postgres, Transaction};
+
+use super::model::Trade;
+
+#[derive(Clone)]
+pub struct TradeRepo {
+    pub pool: sqlx::Pool<Postgres>,
+}
+
+impl TradeRepo {
+    pub fn new(pool: sqlx::Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        trade: &Trade,
+    ) -> Result<i32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as(
+            r#"INSERT INTO trades (order_id, user_id, product_id, side, price, quantity, created_at,
+                commission, levy, vat, tax)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING trade_id"#,
+        )
+        .bind(trade.order_id)
+        .bind(trade.user_id)
+        .bind(trade.product_id)
+        .bind(trade.side.to_string())
+        .bind(trade.price)
+        .bind(trade.quantity)
+        .bind(trade.created_at)
+        .bind(trade.fees.commission)
+        .bind(trade.fees.levy)
+        .bind(trade.fees.vat)
+        .bind(trade.fees.tax)
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok(row.0)
+    }
+
+    /// Every trade of the user, in the order they happened
+    pub async fn get_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+    ) -> Result<Vec<Trade>, sqlx::Error> {
+        sqlx::query_as::<_, Trade>(
+            r#"SELECT trade_id, order_id, user_id, product_id, side, price, quantity, commission,
+            levy, vat, tax, created_at FROM trades
+            WHERE user_id = $1 ORDER BY created_at, trade_id"#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+}
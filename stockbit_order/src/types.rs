@@ -0,0 +1,270 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use crate::cfg::MARKET_CONFIG;
+
+/// Arithmetic shared by the amount types. `checked_*` gives `None` when the result does
+/// not fit and is what fills, holds and replays go through. The operators panic instead
+/// and are left to amounts bounded by one that was already checked, such as a tax taken
+/// off its gross: an overflow there is a bug, never a wrap.
+macro_rules! amount {
+    ($name:ident, $inner:ty, $what:literal) => {
+        impl $name {
+            pub const ZERO: $name = $name(0);
+
+            pub const fn new(value: $inner) -> Self {
+                Self(value)
+            }
+
+            pub const fn get(self) -> $inner {
+                self.0
+            }
+
+            pub fn is_zero(self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn checked_add(self, other: Self) -> Option<Self> {
+                self.0.checked_add(other.0).map(Self)
+            }
+
+            pub fn checked_sub(self, other: Self) -> Option<Self> {
+                self.0.checked_sub(other.0).map(Self)
+            }
+
+            pub fn checked_times(self, factor: $inner) -> Option<Self> {
+                self.0.checked_mul(factor).map(Self)
+            }
+
+            /// The amount times a whole factor, e.g. a sign
+            pub fn times(self, factor: $inner) -> Self {
+                self.checked_times(factor)
+                    .expect(concat!($what, " overflow"))
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                self.checked_add(other).expect(concat!($what, " overflow"))
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                self.checked_sub(other).expect(concat!($what, " overflow"))
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(self.0.checked_neg().expect(concat!($what, " overflow")))
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                *self = *self - other;
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::ZERO, Add::add)
+            }
+        }
+
+        impl<'a> Sum<&'a $name> for $name {
+            fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+                iter.copied().sum()
+            }
+        }
+
+        impl From<$name> for Decimal {
+            fn from(value: $name) -> Decimal {
+                Decimal::from(value.0)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+/// Rupiah per share, on the exchange's whole-rupiah ticks
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Price(i32);
+
+amount!(Price, i32, "price");
+
+impl Price {
+    /// Rounds a computed price to the rupiah, `None` when it is out of range
+    pub fn from_decimal(value: Decimal) -> Option<Price> {
+        value.round().to_i32().map(Price)
+    }
+
+    pub fn abs_diff(self, other: Price) -> i64 {
+        (self.0 as i64 - other.0 as i64).abs()
+    }
+}
+
+/// Value of `quantity` shares at the price, which always fits in rupiah
+impl Mul<Quantity> for Price {
+    type Output = Money;
+
+    fn mul(self, quantity: Quantity) -> Money {
+        Money(self.0 as i64 * quantity.0 as i64)
+    }
+}
+
+/// Number of shares. Orders, positions and trades all count shares, lots only describe
+/// what the regular board takes.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Quantity(i32);
+
+amount!(Quantity, i32, "quantity");
+
+impl Quantity {
+    pub const MAX: Quantity = Quantity(i32::MAX);
+
+    /// Whole lots in the quantity, odd shares left out
+    pub fn lots(self) -> Lot {
+        Lot(self.0 / MARKET_CONFIG.lot.size.0)
+    }
+
+    /// Whether the quantity is whole lots
+    pub fn is_round_lot(self) -> bool {
+        self.0 % MARKET_CONFIG.lot.size.0 == 0
+    }
+
+    /// Cut down to whole lots
+    pub fn round_lots(self) -> Quantity {
+        self.lots().shares()
+    }
+
+    /// Whole shares of a computed quantity, the fraction cut off
+    pub fn from_decimal(value: Decimal) -> Option<Quantity> {
+        value.floor().to_i32().map(Quantity)
+    }
+}
+
+/// Round lot of the regular board, `MARKET_CONFIG.lot.size` shares each
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Lot(i32);
+
+amount!(Lot, i32, "lot");
+
+impl Lot {
+    pub fn shares(self) -> Quantity {
+        self.0
+            .checked_mul(MARKET_CONFIG.lot.size.0)
+            .map(Quantity)
+            .expect("lot overflow")
+    }
+}
+
+/// Amount of rupiah: cash, values, costs and fees
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Money(i64);
+
+amount!(Money, i64, "money");
+
+impl Money {
+    /// Rounds a computed amount to the rupiah, `None` when it is out of range
+    pub fn from_decimal(value: Decimal) -> Option<Money> {
+        value.round().to_i64().map(Money)
+    }
+
+    /// The part of the amount that `part` of `whole` shares take, rounded down
+    pub fn pro_rata(self, part: Quantity, whole: Quantity) -> Money {
+        if whole.is_zero() {
+            return Money::ZERO;
+        }
+        let share = self.0 as i128 * part.0 as i128 / whole.0 as i128;
+        Money(i64::try_from(share).expect("money overflow"))
+    }
+
+    /// `pct` percent of the amount, rounded to the rupiah
+    pub fn percent(self, pct: Decimal) -> Money {
+        Money::from_decimal(Decimal::from(self.0) * pct / Decimal::ONE_HUNDRED)
+            .expect("money overflow")
+    }
+}
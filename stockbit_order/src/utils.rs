@@ -2,8 +2,10 @@ use std::collections::HashMap;
 
 use base64::Engine;
 use base64::engine::general_purpose;
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
+use sha2::Sha256;
 
 pub fn des_from_str<T: for<'a> Deserialize<'a> + Serialize>(
     string: &str,
@@ -38,8 +40,19 @@ pub fn generate_accept_key(key: &str) -> String {
     hasher.update(combined.as_bytes());
     let result = hasher.finalize();
 
-    let base64 = general_purpose::STANDARD.encode(&result);
-    base64
+    general_purpose::STANDARD.encode(result)
+}
+
+/// Whether `signature` is the hex HMAC-SHA256 of `body` under `secret`, compared in
+/// constant time
+pub fn verify_signature(secret: &str, body: &str, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+    mac.verify_slice(&signature).is_ok()
 }
 
 // Parse WebSocket frame and unmask the message
@@ -119,6 +132,24 @@ pub fn parse_websocket_framev2(buffer: &[u8]) -> Option<String> {
     }
 }
 
+/// Length of the frame at the start of `buffer`, once its header is in
+pub fn websocket_frame_len(buffer: &[u8]) -> Option<usize> {
+    let masked = (*buffer.get(1)? & 0b10000000) != 0;
+    let (header_len, payload_length) = match buffer[1] & 0b01111111 {
+        126 => (
+            4,
+            u16::from_be_bytes([*buffer.get(2)?, *buffer.get(3)?]) as usize,
+        ),
+        127 => {
+            let length: [u8; 8] = buffer.get(2..10)?.try_into().ok()?;
+            (10, usize::try_from(u64::from_be_bytes(length)).ok()?)
+        }
+        length => (2, length as usize),
+    };
+    let mask_len: usize = if masked { 4 } else { 0 };
+    (header_len + mask_len).checked_add(payload_length)
+}
+
 // Create WebSocket frame to send messages
 pub fn create_websocket_frame(message: &str) -> Vec<u8> {
     let mut frame = vec![0x81]; // FIN + Text frame opcode
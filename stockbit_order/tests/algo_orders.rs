@@ -0,0 +1,86 @@
+//! Algo orders send slices on their schedule through the same checks as any order, and
+//! finish once filled
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::error::OrderError;
+
+const ALGO: &str = r#"{"algo":{"symbol":"BBCA","side":"B","quantity":1000,"strategy":"TWAP","price":13500,"end_time":"23:59:59"}}"#;
+
+/// Moves the algo's window to have started an hour ago and to end within its last slice,
+/// so the schedule is due in full
+async fn due_in_full(db: &TestDb) {
+    sqlx::query(
+        "UPDATE algo_orders SET start_at = NOW() - INTERVAL '1 hour',
+            end_at = NOW() + INTERVAL '30 seconds'",
+    )
+    .execute(&db.pool)
+    .await
+    .expect("move window");
+}
+
+async fn algo(db: &TestDb) -> (String, i32) {
+    sqlx::query_as("SELECT status, filled_quantity FROM algo_orders")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn algo_slices_fill_and_complete_it() {
+    let Some(db) = common::setup("algo_complete").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    let ask = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":1000,"expiry":"GTC"}"#;
+    db.send(SELLER, ask).await.expect("ask rests");
+    db.send(BUYER, ALGO).await.expect("algo starts");
+    assert_eq!(algo(&db).await, ("RUNNING".to_string(), 0));
+
+    due_in_full(&db).await;
+    assert_eq!(db.svc.work_algos().await.unwrap(), 1000);
+    let children: Vec<(i32, String)> =
+        sqlx::query_as("SELECT filled_quantity, status FROM orders WHERE algo_id IS NOT NULL")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(children, vec![(1000, "FILLED".to_string())]);
+
+    // nothing left to send, the next pass finishes the algo
+    assert_eq!(db.svc.work_algos().await.unwrap(), 0);
+    assert_eq!(algo(&db).await, ("COMPLETED".to_string(), 1000));
+}
+
+#[tokio::test]
+async fn algo_in_odd_shares_is_refused() {
+    let Some(db) = common::setup("algo_odd").await else {
+        return;
+    };
+    let odd = r#"{"algo":{"symbol":"BBCA","side":"B","quantity":150,"strategy":"TWAP","end_time":"23:59:59"}}"#;
+    let sent = db.send(BUYER, odd).await;
+    assert!(
+        matches!(sent, Err(OrderError::InvalidAlgo(_))),
+        "{:?}",
+        sent
+    );
+    assert_eq!(db.count("algo_orders").await, 0);
+}
+
+#[tokio::test]
+async fn algo_of_another_user_cannot_be_controlled() {
+    let Some(db) = common::setup("algo_owner").await else {
+        return;
+    };
+    db.send(BUYER, ALGO).await.expect("algo starts");
+    let algo_id: i32 = sqlx::query_scalar("SELECT algo_id FROM algo_orders")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    let pause = format!(r#"{{"algo_id":{},"action":"PAUSE"}}"#, algo_id);
+
+    let sent = db.send(SELLER, &pause).await;
+    assert!(matches!(sent, Err(OrderError::AlgoNotFound)), "{:?}", sent);
+    assert_eq!(algo(&db).await.0, "RUNNING");
+    db.send(BUYER, &pause).await.expect("owner pauses");
+    assert_eq!(algo(&db).await.0, "PAUSED");
+}
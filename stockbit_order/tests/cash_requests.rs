@@ -0,0 +1,203 @@
+//! Deposits and withdrawals go through review and the payment gateway before the balance
+//! moves, and the gateway is only listened to when it signs what it reports
+mod common;
+
+use common::{BUYER, GATEWAY_SECRET, TestDb};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use stockbit_order_ws::error::OrderError;
+use stockbit_order_ws::funding::model::{CashKind, GatewayCallback, GatewayStatus};
+use stockbit_order_ws::mdw::Middleware;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+const DEPOSIT: i64 = 5_000_000;
+
+/// Asks for a deposit of `DEPOSIT` as `user_id`, the id of the request it opened
+async fn deposit(db: &TestDb, user_id: i32) -> i32 {
+    let mut written = Vec::new();
+    db.svc
+        .create_cash_request(
+            common::request(
+                "POST /account/deposit",
+                &format!(r#"{{"amount":{}}}"#, DEPOSIT),
+            ),
+            user_id,
+            CashKind::Deposit,
+            &mut written,
+        )
+        .await
+        .expect("write response");
+    let (status, body) = common::response(&written);
+    assert_eq!(status, 200, "{}", body);
+    sqlx::query_scalar("SELECT MAX(request_id) FROM cash_requests")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+/// Reviews `request_id` as the back office, the status code and body it is answered with
+async fn review(db: &TestDb, request_id: i32, approve: bool) -> (u16, String) {
+    let form = format!(
+        r#"{{"request_id":{},"approve":{},"reviewer":"ops"}}"#,
+        request_id, approve
+    );
+    let mut written = Vec::new();
+    db.svc
+        .review_cash_request(
+            common::request("POST /internal/cash/review", &form),
+            &mut written,
+        )
+        .await
+        .expect("write response");
+    common::response(&written)
+}
+
+async fn cash_request(db: &TestDb, request_id: i32) -> (String, Option<String>) {
+    sqlx::query_as("SELECT status, gateway_ref FROM cash_requests WHERE request_id = $1")
+        .bind(request_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+async fn balance(db: &TestDb, user_id: i32) -> i64 {
+    sqlx::query_scalar("SELECT balance FROM accounts WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+fn sign(body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(GATEWAY_SECRET.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Puts `POST /gateway/callback` with `body` on the wire and through the middleware,
+/// signed with `signature` when there is one
+async fn through_middleware(
+    body: &str,
+    signature: Option<&str>,
+) -> anyhow::Result<request_http_parser::parser::Request> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let signature = signature
+        .map(|signature| format!("x-signature: {}\r\n", signature))
+        .unwrap_or_default();
+    let wire = format!(
+        "POST /gateway/callback HTTP/1.1\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\n\r\n{}",
+        signature,
+        body.len(),
+        body
+    );
+    client.write_all(wire.as_bytes()).await.unwrap();
+    Middleware::new(&mut stream)
+        .await
+        .map(|(request, _)| request)
+}
+
+#[tokio::test]
+async fn deposit_moves_the_balance_once_completed() {
+    let Some(db) = common::setup("cash_deposit").await else {
+        return;
+    };
+    let before = balance(&db, BUYER).await;
+    let request_id = deposit(&db, BUYER).await;
+    assert_eq!(
+        cash_request(&db, request_id).await,
+        ("PENDING".to_string(), None)
+    );
+
+    let (status, body) = review(&db, request_id, true).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, gateway_ref) = cash_request(&db, request_id).await;
+    assert_eq!(status, "APPROVED");
+    assert_eq!(balance(&db, BUYER).await, before);
+
+    let callback = GatewayCallback {
+        request_id,
+        gateway_ref: gateway_ref.expect("sent to the gateway"),
+        status: GatewayStatus::Success,
+        reason: None,
+    };
+    db.svc
+        .complete_cash_request(callback)
+        .await
+        .expect("completes");
+    assert_eq!(cash_request(&db, request_id).await.0, "COMPLETED");
+    assert_eq!(balance(&db, BUYER).await, before + DEPOSIT);
+    assert_eq!(db.count("cash_request_log").await, 3);
+
+    // a completed request is done with, reviewing it again moves nothing
+    let (status, body) = review(&db, request_id, false).await;
+    assert_eq!(status, 422);
+    assert!(body.contains("INVALID_CASH_TRANSITION"), "{}", body);
+    assert_eq!(cash_request(&db, request_id).await.0, "COMPLETED");
+    assert_eq!(balance(&db, BUYER).await, before + DEPOSIT);
+}
+
+#[tokio::test]
+async fn callback_with_a_reference_the_gateway_was_not_given_is_refused() {
+    let Some(db) = common::setup("cash_reference").await else {
+        return;
+    };
+    let before = balance(&db, BUYER).await;
+    let request_id = deposit(&db, BUYER).await;
+    assert_eq!(review(&db, request_id, true).await.0, 200);
+
+    let callback = GatewayCallback {
+        request_id,
+        gateway_ref: "MOCK-0-0".to_string(),
+        status: GatewayStatus::Success,
+        reason: None,
+    };
+    let completed = db.svc.complete_cash_request(callback).await;
+    assert!(
+        matches!(completed, Err(OrderError::CashRequestNotFound)),
+        "{:?}",
+        completed
+    );
+    assert_eq!(cash_request(&db, request_id).await.0, "APPROVED");
+    assert_eq!(balance(&db, BUYER).await, before);
+}
+
+#[tokio::test]
+async fn only_signed_callbacks_reach_the_gateway_handler() {
+    let Some(db) = common::setup("cash_signature").await else {
+        return;
+    };
+    let before = balance(&db, BUYER).await;
+    let request_id = deposit(&db, BUYER).await;
+    assert_eq!(review(&db, request_id, true).await.0, 200);
+    let gateway_ref = cash_request(&db, request_id).await.1.unwrap();
+    let body = format!(
+        r#"{{"request_id":{},"gateway_ref":"{}","status":"SUCCESS"}}"#,
+        request_id, gateway_ref
+    );
+
+    assert!(through_middleware(&body, None).await.is_err());
+    let tampered = body.replace("SUCCESS", "FAILED");
+    assert!(
+        through_middleware(&tampered, Some(&sign(&body)))
+            .await
+            .is_err()
+    );
+    assert_eq!(cash_request(&db, request_id).await.0, "APPROVED");
+
+    let request = through_middleware(&body, Some(&sign(&body)))
+        .await
+        .expect("signed callback passes");
+    let mut written = Vec::new();
+    db.svc
+        .gateway_callback(request, &mut written)
+        .await
+        .expect("write response");
+    assert_eq!(common::response(&written).0, 200);
+    assert_eq!(cash_request(&db, request_id).await.0, "COMPLETED");
+    assert_eq!(balance(&db, BUYER).await, before + DEPOSIT);
+}
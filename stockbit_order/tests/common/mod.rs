@@ -0,0 +1,198 @@
+//! Database backed harness of the integration tests. Every test runs against a database
+//! of its own, created on the server `TEST_DATABASE_URL` points at. Without it the tests
+//! are skipped.
+#![allow(dead_code)]
+
+use std::sync::{Arc, Once};
+
+use request_http_parser::parser::Request;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres};
+use stockbit_order_ws::account::repo::AccountRepo;
+use stockbit_order_ws::algo::repo::AlgoRepo;
+use stockbit_order_ws::cfg::CONFIG;
+use stockbit_order_ws::corporate::repo::CorporateActionRepo;
+use stockbit_order_ws::error::OrderError;
+use stockbit_order_ws::funding::repo::FundingRepo;
+use stockbit_order_ws::ledger::repo::LedgerRepo;
+use stockbit_order_ws::order::model::OrderMessage;
+use stockbit_order_ws::order::repo::OrderRepo;
+use stockbit_order_ws::pnl::repo::PnlRepo;
+use stockbit_order_ws::portfolio::repo::PortoRepo;
+use stockbit_order_ws::product::model::Product;
+use stockbit_order_ws::product::repo::ProductRepository;
+use stockbit_order_ws::redis::RedisCache;
+use stockbit_order_ws::settlement::repo::SettlementRepo;
+use stockbit_order_ws::svc::Service;
+use stockbit_order_ws::trade::repo::TradeRepo;
+use stockbit_order_ws::utils;
+
+pub const BUYER: i32 = 1;
+pub const SELLER: i32 = 2;
+/// Key the payment gateway signs its callbacks with in the tests
+pub const GATEWAY_SECRET: &str = "test-gateway-secret";
+
+static CONFIGURE: Once = Once::new();
+
+pub struct TestDb {
+    pub pool: Pool<Postgres>,
+    pub svc: Arc<Service>,
+}
+
+/// Fresh database named after the test, with the schema migrated and two funded users.
+/// Left behind for a look after a failure, dropped by the next run.
+pub async fn setup(name: &str) -> Option<TestDb> {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL not set, skipping {}", name);
+        return None;
+    };
+    CONFIGURE.call_once(|| {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+        std::env::set_current_dir(root).expect("workspace root");
+        // SAFETY: set once, before anything reads the market config or the app config
+        unsafe {
+            std::env::set_var(
+                "MARKET_CONFIG",
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/market.toml"),
+            );
+            std::env::set_var("GATEWAY_SECRET", GATEWAY_SECRET);
+        }
+    });
+
+    let database = format!("order_test_{}", name);
+    let admin = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&url)
+        .await
+        .expect("test database server");
+    for statement in [
+        "DROP DATABASE IF EXISTS {} WITH (FORCE)",
+        "CREATE DATABASE {}",
+    ] {
+        sqlx::raw_sql(&statement.replace("{}", &database))
+            .execute(&admin)
+            .await
+            .expect("create test database");
+    }
+    admin.close().await;
+
+    let base = url.rsplit_once('/').map_or(url.as_str(), |(base, _)| base);
+    let pool = PgPoolOptions::new()
+        .max_connections(20)
+        .connect(&format!("{}/{}", base, database))
+        .await
+        .expect("test database");
+    sqlx::raw_sql(include_str!("../schema.sql"))
+        .execute(&pool)
+        .await
+        .expect("schema");
+    sqlx::raw_sql(include_str!("../../../migrate/migration.sql"))
+        .execute(&pool)
+        .await
+        .expect("migration");
+
+    // the product cache outlives the databases, it starts over from the fresh products
+    let mut redis_cache = RedisCache::new(&CONFIG.redis_url).await.expect("redis");
+    let products: Vec<Product> =
+        sqlx::query_as("SELECT product_id, symbol, name, reference_price FROM products")
+            .fetch_all(&pool)
+            .await
+            .expect("products");
+    for product in products {
+        redis_cache
+            .set_cache(&format!("product:{}", product.symbol), &product)
+            .await
+            .expect("cache product");
+    }
+    let svc = Service::new(
+        pool.clone(),
+        ProductRepository::new(pool.clone()),
+        OrderRepo::new(pool.clone()),
+        AccountRepo::new(pool.clone()),
+        PortoRepo::new(pool.clone()),
+        TradeRepo::new(pool.clone()),
+        AlgoRepo::new(pool.clone()),
+        SettlementRepo::new(pool.clone()),
+        PnlRepo::new(pool.clone()),
+        CorporateActionRepo::new(pool.clone()),
+        LedgerRepo::new(pool.clone()),
+        FundingRepo::new(pool.clone()),
+        redis_cache,
+    );
+    Some(TestDb {
+        pool,
+        svc: Arc::new(svc),
+    })
+}
+
+/// Request as the server parses it off the wire, `head` being e.g. `POST /account/deposit`
+pub fn request(head: &str, body: &str) -> Request {
+    Request::new(&format!(
+        "{} HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{}",
+        head, body
+    ))
+    .expect("request")
+}
+
+/// Status code and body of what a handler wrote back
+pub fn response(written: &[u8]) -> (u16, String) {
+    let written = String::from_utf8_lossy(written);
+    let (head, body) = written.split_once("\r\n\r\n").expect("response");
+    let status = head.split(' ').nth(1).and_then(|code| code.parse().ok());
+    (status.expect("status code"), body.to_string())
+}
+
+impl TestDb {
+    /// Sends an order message as `user_id` would over the websocket
+    pub async fn send(&self, user_id: i32, message: &str) -> Result<String, OrderError> {
+        let message = utils::des_from_str::<OrderMessage>(message).expect("order message");
+        self.svc.handle_message(message, user_id).await
+    }
+
+    /// Gives `user_id` a position of `shares` bought at `price`, as if held from before
+    /// its trades were recorded: with an opening lot and counted in the account
+    pub async fn hold_shares(&self, user_id: i32, symbol: &str, shares: i32, price: i32) {
+        sqlx::query(
+            r#"WITH held AS (
+                INSERT INTO portfolios (user_id, product_id, product_name, product_symbol,
+                    avg_price, shares, invested_value)
+                SELECT $1, product_id, name, symbol, $3, $4, $3::bigint * $4
+                FROM products WHERE symbol = $2
+                RETURNING user_id, product_id, shares, invested_value, avg_price
+            ), opened AS (
+                INSERT INTO position_lots (user_id, product_id, shares, cost,
+                    opened_shares, opened_cost, opened_avg_price, opened_at)
+                SELECT user_id, product_id, shares, invested_value,
+                    shares, invested_value, avg_price, NOW()
+                FROM held
+            )
+            UPDATE accounts a SET invested_value = a.invested_value + held.invested_value
+            FROM held WHERE a.user_id = held.user_id"#,
+        )
+        .bind(user_id)
+        .bind(symbol)
+        .bind(price)
+        .bind(shares)
+        .execute(&self.pool)
+        .await
+        .expect("seed position");
+    }
+
+    /// Rows of `table` as JSON, in a stable order
+    pub async fn snapshot(&self, table: &str) -> Vec<String> {
+        sqlx::query_scalar(&format!(
+            "SELECT row_to_json(t)::text FROM {0} t ORDER BY 1",
+            table
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .expect("snapshot")
+    }
+
+    pub async fn count(&self, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(&self.pool)
+            .await
+            .expect("count")
+    }
+}
@@ -0,0 +1,105 @@
+//! Orders of one account placed all at once never spend the same cash or shares twice
+mod common;
+
+use std::sync::Arc;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::error::OrderError;
+use stockbit_order_ws::fee;
+use stockbit_order_ws::types::{Money, Price, Quantity};
+
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":100,"expiry":"GTC"}"#;
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":100,"expiry":"GTC"}"#;
+
+/// Sends `count` copies of `message` as `user_id` at once, the number accepted
+async fn fire(db: &Arc<TestDb>, user_id: i32, message: &'static str, count: usize) -> usize {
+    let sends: Vec<_> = (0..count)
+        .map(|_| {
+            let db = Arc::clone(db);
+            tokio::spawn(async move { db.send(user_id, message).await })
+        })
+        .collect();
+    let mut accepted = 0;
+    for send in sends {
+        match send.await.expect("order task") {
+            Ok(_) => accepted += 1,
+            Err(why) => assert!(
+                matches!(
+                    why,
+                    OrderError::InsufficientFunds | OrderError::InsufficientShares
+                ),
+                "{:?}",
+                why
+            ),
+        }
+    }
+    accepted
+}
+
+/// Balance, cash on hold and sum of the cash legs of the ledger
+async fn cash(db: &TestDb, user_id: i32) -> (Money, Money, Money) {
+    sqlx::query_as(
+        r#"SELECT a.balance, a.reserved, (SELECT COALESCE(SUM(l.debit - l.credit), 0)::bigint
+            FROM journal_legs l JOIN journal_entries e ON e.entry_id = l.entry_id
+            WHERE e.account_id = a.account_id AND l.book = 'CASH')
+        FROM accounts a WHERE a.user_id = $1"#,
+    )
+    .bind(user_id)
+    .fetch_one(&db.pool)
+    .await
+    .expect("account")
+}
+
+/// Shares and shares on hold of the BBCA position
+async fn shares(db: &TestDb, user_id: i32) -> (Quantity, Quantity) {
+    sqlx::query_as(
+        r#"SELECT COALESCE(SUM(shares), 0)::int, COALESCE(SUM(reserved_shares), 0)::int
+        FROM portfolios WHERE user_id = $1 AND product_symbol = 'BBCA'"#,
+    )
+    .bind(user_id)
+    .fetch_one(&db.pool)
+    .await
+    .expect("position")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn parallel_orders_never_overspend() {
+    let Some(db) = common::setup("parallel_orders").await else {
+        return;
+    };
+    let db = Arc::new(db);
+    let (opening, _, _) = cash(&db, BUYER).await;
+    let hold = fee::buy_hold(Price::new(13500), Quantity::new(100)).expect("hold fits");
+    let affordable = (opening.get() / hold.get()) as usize;
+
+    // twice the buys the cash covers, exactly the ones it covers rest
+    let bought = fire(&db, BUYER, BUY, affordable * 2).await;
+    assert_eq!(bought, affordable);
+    let (balance, reserved, ledger) = cash(&db, BUYER).await;
+    assert_eq!(balance, opening);
+    assert_eq!(reserved, hold.times(affordable as i64));
+    assert!(reserved <= balance);
+    assert_eq!(ledger, balance);
+
+    // twice the sells the position covers, each one fills a resting buy
+    let held = Quantity::new(100 * affordable as i32);
+    db.hold_shares(SELLER, "BBCA", held.get(), 12000).await;
+    let sold = fire(&db, SELLER, SELL, affordable * 2).await;
+    assert_eq!(sold, affordable);
+    assert_eq!(shares(&db, SELLER).await, (Quantity::ZERO, Quantity::ZERO));
+    assert_eq!(shares(&db, BUYER).await, (held, Quantity::ZERO));
+
+    let spent: Money = sqlx::query_scalar(
+        r#"SELECT SUM(price::bigint * quantity + commission + levy + vat + tax)::bigint
+        FROM trades WHERE user_id = $1"#,
+    )
+    .bind(BUYER)
+    .fetch_one(&db.pool)
+    .await
+    .expect("trades");
+    let (balance, reserved, ledger) = cash(&db, BUYER).await;
+    assert_eq!(balance, opening - spent);
+    assert_eq!(reserved, Money::ZERO);
+    assert!(balance >= Money::ZERO);
+    assert_eq!(ledger, balance);
+}
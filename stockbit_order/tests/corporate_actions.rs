@@ -0,0 +1,162 @@
+//! Corporate actions move every holding of the product in one go, or none of them, and
+//! dividends pay whoever held on the ex-date
+mod common;
+
+use chrono::{Days, NaiveDate};
+use common::{BUYER, SELLER, TestDb};
+use rust_decimal::Decimal;
+use stockbit_order_ws::corporate::model::{ActionDefinition, ActionKind, CorporateAction};
+use stockbit_order_ws::error::OrderError;
+use stockbit_order_ws::product::model::Product;
+
+const EX_DATE: &str = "2026-11-02";
+
+fn ex_date() -> NaiveDate {
+    EX_DATE.parse().unwrap()
+}
+
+fn pay_date() -> NaiveDate {
+    ex_date() + Days::new(14)
+}
+
+/// Announces a BBCA split of `from` shares into `to` on the ex-date
+async fn announce_split(db: &TestDb, from: i32, to: i32) {
+    sqlx::query(
+        r#"INSERT INTO corporate_actions (product_id, product_symbol, kind, ex_date,
+            ratio_from, ratio_to)
+        SELECT product_id, symbol, 'SPLIT', $1, $2, $3 FROM products WHERE symbol = 'BBCA'"#,
+    )
+    .bind(ex_date())
+    .bind(from)
+    .bind(to)
+    .execute(&db.pool)
+    .await
+    .expect("announce split");
+}
+
+/// Announces a BBCA dividend of 150 rupiah a share, paid two weeks after the ex-date
+async fn announce_dividend(db: &TestDb) {
+    sqlx::query(
+        r#"INSERT INTO corporate_actions (product_id, product_symbol, kind, ex_date,
+            pay_date, amount)
+        SELECT product_id, symbol, 'CASH_DIVIDEND', $1, $2, 150 FROM products
+        WHERE symbol = 'BBCA'"#,
+    )
+    .bind(ex_date())
+    .bind(pay_date())
+    .execute(&db.pool)
+    .await
+    .expect("announce dividend");
+}
+
+async fn balance(db: &TestDb, user_id: i32) -> i64 {
+    sqlx::query_scalar("SELECT balance FROM accounts WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+async fn action_status(db: &TestDb) -> String {
+    sqlx::query_scalar("SELECT status FROM corporate_actions")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn split_moves_positions_to_the_ratio() {
+    let Some(db) = common::setup("split").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    announce_split(&db, 1, 2).await;
+
+    assert_eq!(db.svc.run_corporate_actions(ex_date()).await.unwrap(), 1);
+    assert_eq!(action_status(&db).await, "APPLIED");
+    let (shares, invested_value): (i32, i64) =
+        sqlx::query_as("SELECT shares, invested_value FROM portfolios")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!((shares, invested_value), (2000, 12_000_000));
+    let report = db.svc.reconcile(false).await.expect("reconcile");
+    assert!(report.users.is_empty());
+}
+
+#[tokio::test]
+async fn split_past_the_largest_holding_is_refused() {
+    let Some(db) = common::setup("split_too_large").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 500_000_000, 1).await;
+    announce_split(&db, 1, 5).await;
+    let before = db.snapshot("portfolios").await;
+
+    assert_eq!(db.svc.run_corporate_actions(ex_date()).await.unwrap(), 0);
+    assert_eq!(action_status(&db).await, "ANNOUNCED");
+    assert_eq!(db.snapshot("portfolios").await, before);
+}
+
+#[tokio::test]
+async fn dividend_pays_the_holders_of_the_ex_date_on_the_pay_date() {
+    let Some(db) = common::setup("dividend").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    announce_dividend(&db).await;
+
+    assert_eq!(db.svc.run_corporate_actions(ex_date()).await.unwrap(), 1);
+    assert_eq!(action_status(&db).await, "APPLIED");
+    let (user_id, shares, gross, tax, net): (i32, i32, i64, i64, i64) =
+        sqlx::query_as("SELECT user_id, shares, gross, tax, net FROM dividend_entitlements")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!((user_id, shares, gross), (SELLER, 1000, 150_000));
+    assert_eq!(net, gross - tax);
+
+    // shares bought once the holders of the ex-date are recorded are not entitled
+    let sell = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC"}"#;
+    let buy = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"}"#;
+    db.send(SELLER, sell).await.expect("sell rests");
+    db.send(BUYER, buy).await.expect("buy fills");
+    let before = (balance(&db, BUYER).await, balance(&db, SELLER).await);
+    // nothing is paid before the pay date
+    let day_before = pay_date() - Days::new(1);
+    assert_eq!(db.svc.run_corporate_actions(day_before).await.unwrap(), 0);
+    assert_eq!(db.count("dividend_entitlements").await, 1);
+
+    assert_eq!(db.svc.run_corporate_actions(pay_date()).await.unwrap(), 1);
+    assert_eq!(action_status(&db).await, "PAID");
+    assert_eq!(balance(&db, BUYER).await, before.0);
+    assert_eq!(balance(&db, SELLER).await, before.1 + net);
+    // paid once
+    assert_eq!(db.svc.run_corporate_actions(pay_date()).await.unwrap(), 0);
+    assert_eq!(balance(&db, SELLER).await, before.1 + net);
+}
+
+#[test]
+fn dividend_paid_before_its_ex_date_is_refused() {
+    let definition = ActionDefinition {
+        symbol: "BBCA".to_string(),
+        kind: ActionKind::CashDividend,
+        ex_date: pay_date(),
+        pay_date: Some(ex_date()),
+        ratio_from: None,
+        ratio_to: None,
+        amount: Some(Decimal::from(150)),
+    };
+    let product = Product {
+        product_id: 1,
+        name: "Bank Central Asia".to_string(),
+        symbol: "BBCA".to_string(),
+        reference_price: None,
+    };
+    let action = CorporateAction::new(&definition, &product);
+    assert!(
+        matches!(action, Err(OrderError::InvalidCorporateAction(_))),
+        "{:?}",
+        action
+    );
+}
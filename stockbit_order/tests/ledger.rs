@@ -0,0 +1,77 @@
+//! Every balance change is booked in the ledger as balanced entries, and the ledger pages
+//! back to the opening balance
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use serde_json::Value;
+
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC"}"#;
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"}"#;
+
+/// `GET /account/ledger` as `user_id`, the status code and body it is answered with
+async fn ledger(db: &TestDb, user_id: i32, query: &str) -> (u16, String) {
+    let mut written = Vec::new();
+    db.svc
+        .get_ledger(
+            common::request(&format!("GET /account/ledger{}", query), ""),
+            user_id,
+            &mut written,
+        )
+        .await
+        .expect("write response");
+    common::response(&written)
+}
+
+async fn page(db: &TestDb, user_id: i32, query: &str) -> Value {
+    let (status, body) = ledger(db, user_id, query).await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str::<Value>(&body).unwrap()["message"].take()
+}
+
+fn kinds(page: &Value) -> Vec<&str> {
+    page["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["kind"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn trades_are_booked_in_balanced_entries() {
+    let Some(db) = common::setup("ledger").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.send(SELLER, SELL).await.expect("sell rests");
+    db.send(BUYER, BUY).await.expect("buy fills");
+
+    let unbalanced: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM (SELECT entry_id FROM journal_legs GROUP BY entry_id
+            HAVING SUM(debit) <> SUM(credit)) unbalanced",
+    )
+    .fetch_one(&db.pool)
+    .await
+    .unwrap();
+    assert_eq!(unbalanced, 0);
+
+    let first = page(&db, BUYER, "?limit=2").await;
+    assert_eq!(first["balance"], first["ledger_balance"]);
+    assert_eq!(kinds(&first), vec!["FEE", "ORDER_DEBIT"]);
+    assert_eq!(first["entries"][0]["balance_after"], first["balance"]);
+    let before = first["next_before"].as_i64().expect("another page");
+
+    let last = page(&db, BUYER, &format!("?limit=2&before={}", before)).await;
+    assert_eq!(kinds(&last), vec!["OPENING_BALANCE"]);
+    assert_eq!(last["entries"][0]["balance_after"], 100_000_000);
+    assert!(last["next_before"].is_null());
+}
+
+#[tokio::test]
+async fn ledger_page_with_a_bad_cursor_is_refused() {
+    let Some(db) = common::setup("ledger_cursor").await else {
+        return;
+    };
+    let (status, _) = ledger(&db, BUYER, "?before=latest").await;
+    assert_eq!(status, 400);
+}
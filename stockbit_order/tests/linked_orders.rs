@@ -0,0 +1,133 @@
+//! Linked orders: the first leg of an OCO pair to fill cancels the other, and a bracket's
+//! exits activate once its entry is done and then work as an OCO pair
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::error::OrderError;
+
+/// Type and status of `user_id`'s orders, oldest first
+async fn orders(db: &TestDb, user_id: i32) -> Vec<(String, String)> {
+    sqlx::query_as("SELECT order_type, status FROM orders WHERE user_id = $1 ORDER BY order_id")
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+}
+
+fn pair(order_type: &str, status: &str) -> (String, String) {
+    (order_type.to_string(), status.to_string())
+}
+
+#[tokio::test]
+async fn oco_leg_that_fills_cancels_the_other() {
+    let Some(db) = common::setup("oco_fill").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    let oco = r#"{"oco":[
+        {"symbol":"BBCA","side":"S","price":14000,"quantity":200,"expiry":"GTC"},
+        {"symbol":"BBCA","side":"S","quantity":200,"expiry":"GTC","order_type":"STOP","stop_price":13000}
+    ]}"#;
+    db.send(SELLER, oco).await.expect("oco placed");
+    assert_eq!(
+        orders(&db, SELLER).await,
+        vec![pair("LIMIT", "OPEN"), pair("STOP", "PENDING_TRIGGER")]
+    );
+
+    let buy = r#"{"symbol":"BBCA","side":"B","price":14000,"quantity":200,"expiry":"GTC"}"#;
+    db.send(BUYER, buy).await.expect("buy fills");
+    assert_eq!(
+        orders(&db, SELLER).await,
+        vec![pair("LIMIT", "FILLED"), pair("STOP", "CANCELLED")]
+    );
+    // the cancelled stop no longer triggers on a fall
+    let bid = r#"{"symbol":"BBCA","side":"B","price":12900,"quantity":100,"expiry":"GTC"}"#;
+    let sell = r#"{"symbol":"BBCA","side":"S","price":12900,"quantity":100,"expiry":"GTC"}"#;
+    db.send(BUYER, bid).await.expect("bid rests");
+    db.send(SELLER, sell).await.expect("sell fills");
+    assert_eq!(db.svc.check_triggers().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn oco_across_symbols_is_refused() {
+    let Some(db) = common::setup("oco_symbols").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.hold_shares(SELLER, "TLKM", 1000, 3000).await;
+    let before = db.snapshot("portfolios").await;
+
+    let oco = r#"{"oco":[
+        {"symbol":"BBCA","side":"S","price":14000,"quantity":200,"expiry":"GTC"},
+        {"symbol":"TLKM","side":"S","price":3500,"quantity":200,"expiry":"GTC"}
+    ]}"#;
+    let sent = db.send(SELLER, oco).await;
+    assert!(
+        matches!(sent, Err(OrderError::InvalidLink("must share one symbol"))),
+        "{:?}",
+        sent
+    );
+    assert_eq!(db.snapshot("portfolios").await, before);
+    assert_eq!(db.count("orders").await, 0);
+}
+
+#[tokio::test]
+async fn bracket_exits_activate_on_the_entry_and_cancel_each_other() {
+    let Some(db) = common::setup("bracket").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    let bracket = r#"{"bracket":{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"},
+        "take_profit":14500,"stop_loss":13000}"#;
+    db.send(BUYER, bracket).await.expect("bracket placed");
+    assert_eq!(
+        orders(&db, BUYER).await,
+        vec![
+            pair("LIMIT", "OPEN"),
+            pair("LIMIT", "INACTIVE"),
+            pair("STOP", "INACTIVE")
+        ]
+    );
+
+    let sell = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC"}"#;
+    db.send(SELLER, sell).await.expect("entry fills");
+    db.svc.release_queued_orders().await.expect("release exits");
+    assert_eq!(
+        orders(&db, BUYER).await,
+        vec![
+            pair("LIMIT", "FILLED"),
+            pair("LIMIT", "OPEN"),
+            pair("STOP", "PENDING_TRIGGER")
+        ]
+    );
+
+    let buy = r#"{"symbol":"BBCA","side":"B","price":14500,"quantity":200,"expiry":"GTC"}"#;
+    db.send(SELLER, buy).await.expect("take-profit fills");
+    assert_eq!(
+        orders(&db, BUYER).await,
+        vec![
+            pair("LIMIT", "FILLED"),
+            pair("LIMIT", "FILLED"),
+            pair("STOP", "CANCELLED")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn bracket_with_the_take_profit_past_the_stop_loss_is_refused() {
+    let Some(db) = common::setup("bracket_sides").await else {
+        return;
+    };
+    let before = db.snapshot("accounts").await;
+
+    let bracket = r#"{"bracket":{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"},
+        "take_profit":13000,"stop_loss":14500}"#;
+    let sent = db.send(BUYER, bracket).await;
+    assert!(
+        matches!(sent, Err(OrderError::InvalidLink(_))),
+        "{:?}",
+        sent
+    );
+    assert_eq!(db.snapshot("accounts").await, before);
+    assert_eq!(orders(&db, BUYER).await, vec![pair("LIMIT", "REJECTED")]);
+}
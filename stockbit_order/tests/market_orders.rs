@@ -0,0 +1,66 @@
+//! Market orders are priced off the last trade, off the reference price until the
+//! product trades
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::cfg::CONFIG;
+use stockbit_order_ws::error::OrderError;
+use stockbit_order_ws::product::model::Product;
+use stockbit_order_ws::redis::RedisCache;
+use stockbit_order_ws::types::Price;
+
+const MARKET_BUY: &str =
+    r#"{"symbol":"TLKM","side":"B","quantity":100,"expiry":"GFD","order_type":"MARKET"}"#;
+
+/// Gives TLKM a previous close, in the database and in the product cache the tests share
+async fn set_reference_price(db: &TestDb, price: i32) {
+    let product_id: i32 = sqlx::query_scalar(
+        "UPDATE products SET reference_price = $1 WHERE symbol = 'TLKM' RETURNING product_id",
+    )
+    .bind(price)
+    .fetch_one(&db.pool)
+    .await
+    .expect("reference price");
+    let product = Product {
+        product_id,
+        name: "Telkom".to_string(),
+        symbol: "TLKM".to_string(),
+        reference_price: Some(Price::new(price)),
+    };
+    let mut cache = RedisCache::new(&CONFIG.redis_url).await.expect("redis");
+    cache
+        .set_cache("product:TLKM", &product)
+        .await
+        .expect("cache product");
+}
+
+#[tokio::test]
+async fn market_order_is_priced_off_the_reference_until_a_trade() {
+    let Some(db) = common::setup("market_reference").await else {
+        return;
+    };
+    set_reference_price(&db, 3000).await;
+    db.hold_shares(SELLER, "TLKM", 1000, 2800).await;
+    let ask = r#"{"symbol":"TLKM","side":"S","price":3300,"quantity":100,"expiry":"GTC"}"#;
+    db.send(SELLER, ask).await.expect("ask rests");
+
+    // 3300 is past the protection band around the previous close, not around the ask
+    let buy = db.send(BUYER, MARKET_BUY).await;
+    assert!(matches!(buy, Err(OrderError::NoLiquidity)), "{:?}", buy);
+    assert_eq!(db.count("trades").await, 0);
+
+    let bid = r#"{"symbol":"TLKM","side":"B","price":3250,"quantity":100,"expiry":"GTC"}"#;
+    let sell = r#"{"symbol":"TLKM","side":"S","price":3250,"quantity":100,"expiry":"GTC"}"#;
+    db.send(BUYER, bid).await.expect("bid rests");
+    db.send(SELLER, sell).await.expect("sell fills");
+
+    // the last trade takes over and brings the ask within reach
+    db.send(BUYER, MARKET_BUY).await.expect("market buy");
+    let prices: Vec<i32> =
+        sqlx::query_scalar("SELECT price FROM trades WHERE user_id = $1 ORDER BY trade_id")
+            .bind(BUYER)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(prices, vec![3250, 3300]);
+}
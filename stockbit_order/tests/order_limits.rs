@@ -0,0 +1,53 @@
+//! Orders too large to count are refused, never left to overflow
+mod common;
+
+use common::{BUYER, SELLER};
+use stockbit_order_ws::error::OrderError;
+
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":100,"expiry":"GTC","board":"NEGOTIATED"}"#;
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC","board":"NEGOTIATED"}"#;
+
+#[tokio::test]
+async fn buy_past_the_largest_position_is_refused() {
+    let Some(db) = common::setup("largest_position").await else {
+        return;
+    };
+    db.hold_shares(BUYER, "BBCA", i32::MAX - 50, 12000).await;
+    let before = db.snapshot("accounts").await;
+
+    let buy = db.send(BUYER, BUY).await;
+    assert!(
+        matches!(buy, Err(OrderError::TooLarge("quantity"))),
+        "{:?}",
+        buy
+    );
+    assert_eq!(db.snapshot("accounts").await, before);
+    let status: String = sqlx::query_scalar("SELECT status FROM orders WHERE user_id = $1")
+        .bind(BUYER)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "REJECTED");
+}
+
+#[tokio::test]
+async fn fill_past_the_largest_position_refuses_the_incoming_order() {
+    let Some(db) = common::setup("largest_fill").await else {
+        return;
+    };
+    // each buy fits the position on its own, both together do not
+    db.hold_shares(BUYER, "BBCA", i32::MAX - 150, 12000).await;
+    db.hold_shares(SELLER, "BBCA", 200, 12000).await;
+    db.send(BUYER, BUY).await.expect("first buy rests");
+    db.send(BUYER, BUY).await.expect("second buy rests");
+    let before = db.snapshot("portfolios").await;
+
+    let sell = db.send(SELLER, SELL).await;
+    assert!(
+        matches!(sell, Err(OrderError::TooLarge("quantity"))),
+        "{:?}",
+        sell
+    );
+    assert_eq!(db.snapshot("portfolios").await, before);
+    assert_eq!(db.count("trades").await, 0);
+}
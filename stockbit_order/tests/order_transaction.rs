@@ -0,0 +1,95 @@
+//! An order, the hold it takes and the trades it makes commit together or not at all
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::error::OrderError;
+
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC"}"#;
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"}"#;
+
+/// Makes every insert into `table` fail from now on
+async fn fail_inserts(db: &TestDb, table: &str) {
+    sqlx::raw_sql(&format!(
+        r#"CREATE FUNCTION injected_failure() RETURNS trigger AS $$
+        BEGIN RAISE EXCEPTION 'injected failure'; END $$ LANGUAGE plpgsql;
+        CREATE TRIGGER injected_failure BEFORE INSERT ON {} FOR EACH ROW
+        EXECUTE FUNCTION injected_failure();"#,
+        table
+    ))
+    .execute(&db.pool)
+    .await
+    .expect("install failure");
+}
+
+async fn heal(db: &TestDb, table: &str) {
+    sqlx::raw_sql(&format!(
+        "DROP TRIGGER injected_failure ON {}; DROP FUNCTION injected_failure();",
+        table
+    ))
+    .execute(&db.pool)
+    .await
+    .expect("remove failure");
+}
+
+/// Everything an order writes to
+async fn state(db: &TestDb) -> Vec<Vec<String>> {
+    let mut state = Vec::new();
+    for table in [
+        "accounts",
+        "portfolios",
+        "orders",
+        "trades",
+        "journal_entries",
+        "position_lots",
+        "realized_pnl",
+        "settlements",
+    ] {
+        state.push(db.snapshot(table).await);
+    }
+    state
+}
+
+#[tokio::test]
+async fn failed_trade_leaves_order_and_hold_behind_unwritten() {
+    let Some(db) = common::setup("failed_trade").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.send(SELLER, SELL).await.expect("sell rests");
+    let before = state(&db).await;
+
+    // the buy is held and recorded OPEN before it trades, the trade insert fails
+    fail_inserts(&db, "trades").await;
+    let failed = db.send(BUYER, BUY).await;
+    assert!(matches!(failed, Err(OrderError::Database)), "{:?}", failed);
+    assert_eq!(state(&db).await, before);
+
+    // the book was left as it was, the resting sell still trades
+    heal(&db, "trades").await;
+    db.send(BUYER, BUY).await.expect("buy trades");
+    assert_eq!(db.count("trades").await, 2);
+    let status: String =
+        sqlx::query_scalar("SELECT status FROM orders WHERE user_id = $1 AND side = 'S'")
+            .bind(SELLER)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(status, "FILLED");
+}
+
+#[tokio::test]
+async fn failed_order_insert_releases_the_hold() {
+    let Some(db) = common::setup("failed_order").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    let before = state(&db).await;
+
+    // the cash and the shares are on hold by the time the order is recorded
+    fail_inserts(&db, "orders").await;
+    let buy = db.send(BUYER, BUY).await;
+    assert!(matches!(buy, Err(OrderError::Database)), "{:?}", buy);
+    let sell = db.send(SELLER, SELL).await;
+    assert!(matches!(sell, Err(OrderError::Database)), "{:?}", sell);
+    assert_eq!(state(&db).await, before);
+}
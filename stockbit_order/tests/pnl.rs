@@ -0,0 +1,58 @@
+//! Fees count in what a position cost and in what a sell realizes, its average price
+//! included
+mod common;
+
+use common::{BUYER, SELLER};
+use rust_decimal::Decimal;
+
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC"}"#;
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"}"#;
+
+#[tokio::test]
+async fn fees_are_part_of_cost_and_realized_pnl() {
+    let Some(db) = common::setup("pnl_fees").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.send(SELLER, SELL).await.expect("sell rests");
+    db.send(BUYER, BUY).await.expect("buy fills");
+
+    let fees = |user_id| {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT commission + levy + vat + tax FROM trades WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_one(&db.pool)
+    };
+    let (buy_fees, sell_fees) = (fees(BUYER).await.unwrap(), fees(SELLER).await.unwrap());
+    assert!(buy_fees > 0 && sell_fees > 0);
+
+    let (shares, invested_value, avg_price): (i32, i64, Decimal) = sqlx::query_as(
+        "SELECT shares, invested_value, avg_price FROM portfolios WHERE user_id = $1",
+    )
+    .bind(BUYER)
+    .fetch_one(&db.pool)
+    .await
+    .unwrap();
+    assert_eq!(shares, 200);
+    assert_eq!(invested_value, 200 * 13500 + buy_fees);
+    // marked at its average price the position breaks even
+    assert_eq!(
+        avg_price * Decimal::from(shares),
+        Decimal::from(invested_value)
+    );
+
+    let (proceeds, cost_basis, realized): (i64, i64, i64) = sqlx::query_as(
+        "SELECT proceeds, cost_basis, realized FROM realized_pnl WHERE user_id = $1",
+    )
+    .bind(SELLER)
+    .fetch_one(&db.pool)
+    .await
+    .unwrap();
+    assert_eq!(proceeds, 200 * 13500 - sell_fees);
+    assert_eq!(cost_basis, 200 * 12000);
+    assert_eq!(realized, proceeds - cost_basis);
+
+    let report = db.svc.reconcile(false).await.expect("reconcile");
+    assert!(report.users.is_empty());
+}
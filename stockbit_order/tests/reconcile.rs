@@ -0,0 +1,115 @@
+//! Reconciliation replays positions held from before their trades from their opening lot,
+//! goes past users it cannot reconcile, and leaves positions held for sells as they are
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":300,"expiry":"GTC"}"#;
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":300,"expiry":"GTC"}"#;
+
+/// Asserts a dry run finds nothing to repair
+async fn assert_clean(db: &TestDb) {
+    let report = db.svc.reconcile(false).await.expect("reconcile");
+    assert!(
+        report.users.is_empty(),
+        "{}",
+        serde_json::to_string(&report.users).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn legacy_position_reconciles_clean() {
+    let Some(db) = common::setup("legacy_position").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    let before = db.snapshot("portfolios").await;
+
+    assert_clean(&db).await;
+    let report = db.svc.reconcile(true).await.expect("reconcile");
+    assert!(report.users.is_empty());
+    assert_eq!(db.snapshot("portfolios").await, before);
+}
+
+#[tokio::test]
+async fn legacy_position_sold_from_reconciles_clean() {
+    let Some(db) = common::setup("legacy_sold").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 600, 12000).await;
+
+    // the sells close the opening lot, the second one empties it
+    for _ in 0..2 {
+        db.send(BUYER, BUY).await.expect("buy rests");
+        db.send(SELLER, SELL).await.expect("sell fills");
+        assert_clean(&db).await;
+    }
+    assert_eq!(db.count("portfolios").await, 1);
+
+    // bought back, the position goes on from the trades
+    db.send(SELLER, BUY).await.expect("buy back rests");
+    db.send(BUYER, SELL).await.expect("sell fills");
+    assert_clean(&db).await;
+}
+
+#[tokio::test]
+async fn position_held_for_sells_is_reported_unrepaired() {
+    let Some(db) = common::setup("reconcile_held").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 500, 12000).await;
+    // drifted up together, the account still adds up to the positions
+    sqlx::raw_sql(
+        r#"UPDATE portfolios SET shares = 800, invested_value = invested_value + 3600000;
+        UPDATE accounts SET invested_value = invested_value + 3600000 WHERE user_id = 2"#,
+    )
+    .execute(&db.pool)
+    .await
+    .expect("drift");
+    let sell = r#"{"symbol":"BBCA","side":"S","price":14000,"quantity":700,"expiry":"GTC"}"#;
+    db.send(SELLER, sell).await.expect("sell rests");
+    let portfolios = db.snapshot("portfolios").await;
+    let accounts = db.snapshot("accounts").await;
+
+    let report = db.svc.reconcile(true).await.expect("reconcile");
+    let user = report
+        .users
+        .iter()
+        .find(|u| u.user_id == SELLER)
+        .expect("drift");
+    assert_eq!(user.positions.len(), 1);
+    assert!(!user.positions[0].repaired);
+    assert_eq!(db.snapshot("portfolios").await, portfolios);
+    assert_eq!(db.snapshot("accounts").await, accounts);
+}
+
+#[tokio::test]
+async fn user_that_fails_is_reported_and_the_run_goes_on() {
+    let Some(db) = common::setup("reconcile_error").await else {
+        return;
+    };
+    for user_id in [BUYER, SELLER] {
+        db.hold_shares(user_id, "BBCA", 500, 12000).await;
+    }
+    sqlx::raw_sql(
+        r#"UPDATE portfolios SET shares = shares + 100;
+        CREATE FUNCTION injected_failure() RETURNS trigger AS $$
+        BEGIN RAISE EXCEPTION 'injected failure'; END $$ LANGUAGE plpgsql;
+        CREATE TRIGGER injected_failure BEFORE UPDATE ON portfolios FOR EACH ROW
+        WHEN (OLD.user_id = 1) EXECUTE FUNCTION injected_failure();"#,
+    )
+    .execute(&db.pool)
+    .await
+    .expect("drift and failure");
+
+    let report = db.svc.reconcile(true).await.expect("reconcile");
+    let failed: Vec<i32> = report.errors.iter().map(|e| e.user_id).collect();
+    assert_eq!(failed, vec![BUYER]);
+    let repaired: Vec<i32> = report.users.iter().map(|u| u.user_id).collect();
+    assert_eq!(repaired, vec![SELLER]);
+    let shares: Vec<i32> = sqlx::query_scalar("SELECT shares FROM portfolios ORDER BY user_id")
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(shares, vec![600, 500]);
+}
@@ -0,0 +1,24 @@
+-- Tables the auth service owns, as far as the order service reads them
+CREATE TABLE users (
+  user_id SERIAL PRIMARY KEY,
+  username TEXT
+);
+
+CREATE TABLE products (
+  product_id SERIAL PRIMARY KEY,
+  name VARCHAR(100),
+  symbol VARCHAR(10),
+  tags TEXT,
+  last_updated TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE accounts (
+  account_id SERIAL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES users(user_id),
+  balance BIGINT NOT NULL,
+  invested_value BIGINT NOT NULL
+);
+
+INSERT INTO users (username) VALUES ('buyer'), ('seller');
+INSERT INTO products (name, symbol) VALUES ('Bank Central Asia', 'BBCA'), ('Telkom', 'TLKM');
+INSERT INTO accounts (user_id, balance, invested_value) VALUES (1, 100000000, 0), (2, 100000000, 0);
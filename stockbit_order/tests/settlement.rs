@@ -0,0 +1,63 @@
+//! Trades settle on their settlement date, the cash of a sell only becomes withdrawable
+//! once it has
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::calendar;
+use stockbit_order_ws::funding::model::CashKind;
+
+const SELL: &str = r#"{"symbol":"BBCA","side":"S","price":13500,"quantity":200,"expiry":"GTC"}"#;
+const BUY: &str = r#"{"symbol":"BBCA","side":"B","price":13500,"quantity":200,"expiry":"GTC"}"#;
+
+/// Asks for a withdrawal of `amount` as `user_id`, the status code and body it is answered with
+async fn withdraw(db: &TestDb, user_id: i32, amount: i64) -> (u16, String) {
+    let mut written = Vec::new();
+    db.svc
+        .create_cash_request(
+            common::request(
+                "POST /account/withdrawal",
+                &format!(r#"{{"amount":{}}}"#, amount),
+            ),
+            user_id,
+            CashKind::Withdrawal,
+            &mut written,
+        )
+        .await
+        .expect("write response");
+    common::response(&written)
+}
+
+async fn pending(db: &TestDb) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM settlements WHERE status = 'PENDING'")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn sell_proceeds_are_withdrawable_once_settled() {
+    let Some(db) = common::setup("settlement").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.send(SELLER, SELL).await.expect("sell rests");
+    db.send(BUYER, BUY).await.expect("buy fills");
+    assert_eq!(pending(&db).await, 2);
+    let balance: i64 = sqlx::query_scalar("SELECT balance FROM accounts WHERE user_id = $1")
+        .bind(SELLER)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert!(balance > 100_000_000);
+
+    // the proceeds are on the balance from trade date, not yet withdrawable
+    let (status, body) = withdraw(&db, SELLER, balance).await;
+    assert_eq!(status, 422);
+    assert!(body.contains("WITHDRAWAL_LIMIT"), "{}", body);
+    assert_eq!(db.svc.settle_trades(calendar::today()).await.unwrap(), 0);
+
+    let settle_date = calendar::settlement_date(calendar::today());
+    assert_eq!(db.svc.settle_trades(settle_date).await.unwrap(), 2);
+    assert_eq!(pending(&db).await, 0);
+    assert_eq!(withdraw(&db, SELLER, balance).await.0, 200);
+}
@@ -0,0 +1,77 @@
+//! Stop orders wait untriggered until the last price reaches their stop price, then
+//! release the order they carry
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::error::OrderError;
+
+const STOP_SELL: &str = r#"{"symbol":"BBCA","side":"S","quantity":200,"expiry":"GTC","order_type":"STOP","stop_price":13000}"#;
+
+/// Statuses of `user_id`'s orders, oldest first
+async fn statuses(db: &TestDb, user_id: i32) -> Vec<String> {
+    sqlx::query_scalar("SELECT status FROM orders WHERE user_id = $1 ORDER BY order_id")
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stop_releases_a_market_order_once_the_price_reaches_it() {
+    let Some(db) = common::setup("stop_trigger").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.send(SELLER, STOP_SELL).await.expect("stop armed");
+    let bids = [
+        r#"{"symbol":"BBCA","side":"B","price":13100,"quantity":100,"expiry":"GTC"}"#,
+        r#"{"symbol":"BBCA","side":"B","price":12900,"quantity":100,"expiry":"GTC"}"#,
+        r#"{"symbol":"BBCA","side":"B","price":12800,"quantity":200,"expiry":"GTC"}"#,
+    ];
+    for bid in bids {
+        db.send(BUYER, bid).await.expect("bid rests");
+    }
+    let sell = |price| {
+        format!(
+            r#"{{"symbol":"BBCA","side":"S","price":{},"quantity":100,"expiry":"GTC"}}"#,
+            price
+        )
+    };
+
+    // a trade above the stop price leaves it waiting
+    db.send(SELLER, &sell(13100)).await.expect("sell fills");
+    assert_eq!(db.svc.check_triggers().await.unwrap(), 0);
+    assert_eq!(statuses(&db, SELLER).await[0], "PENDING_TRIGGER");
+
+    db.send(SELLER, &sell(12900)).await.expect("sell fills");
+    assert_eq!(db.svc.check_triggers().await.unwrap(), 1);
+    let statuses = statuses(&db, SELLER).await;
+    assert_eq!(statuses[0], "TRIGGERED");
+    assert_eq!(statuses.last().unwrap(), "FILLED");
+    let sold: i64 =
+        sqlx::query_scalar("SELECT SUM(quantity) FROM trades WHERE user_id = $1 AND price = 12800")
+            .bind(SELLER)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(sold, 200);
+}
+
+#[tokio::test]
+async fn stop_without_a_stop_price_is_refused() {
+    let Some(db) = common::setup("stop_no_price").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    let before = db.snapshot("portfolios").await;
+
+    let stop = r#"{"symbol":"BBCA","side":"S","quantity":200,"expiry":"GTC","order_type":"STOP"}"#;
+    let sent = db.send(SELLER, stop).await;
+    assert!(
+        matches!(sent, Err(OrderError::InvalidStopPrice)),
+        "{:?}",
+        sent
+    );
+    assert_eq!(db.snapshot("portfolios").await, before);
+    assert_eq!(statuses(&db, SELLER).await, vec!["REJECTED"]);
+}
@@ -0,0 +1,90 @@
+//! Trailing stops follow the best price by their trail and trigger once the last price
+//! falls back to it
+mod common;
+
+use common::{BUYER, SELLER, TestDb};
+use stockbit_order_ws::error::OrderError;
+
+const TRAILING_SELL: &str = r#"{"symbol":"BBCA","side":"S","quantity":200,"expiry":"GTC","order_type":"TRAILING_STOP","trail_amount":500}"#;
+
+/// Trades 100 shares between the two users at `price`, then lets the stops see it.
+/// Returns how many stops it released.
+async fn trade_at(db: &TestDb, price: i32) -> usize {
+    let order = |side| {
+        format!(
+            r#"{{"symbol":"BBCA","side":"{}","price":{},"quantity":100,"expiry":"GTC"}}"#,
+            side, price
+        )
+    };
+    db.send(BUYER, &order('B')).await.expect("bid rests");
+    db.send(SELLER, &order('S')).await.expect("sell fills");
+    db.svc.check_triggers().await.expect("check triggers")
+}
+
+/// Stop price and best price seen of the trailing stop
+async fn trail(db: &TestDb) -> (Option<i32>, Option<i32>) {
+    sqlx::query_as(
+        "SELECT stop_price, high_water_mark FROM orders WHERE order_type = 'TRAILING_STOP'",
+    )
+    .fetch_one(&db.pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn trailing_stop_follows_the_high_and_triggers_on_the_fall() {
+    let Some(db) = common::setup("trailing_stop").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+    db.send(SELLER, TRAILING_SELL)
+        .await
+        .expect("trailing stop armed");
+
+    assert_eq!(trade_at(&db, 13000).await, 0);
+    assert_eq!(trail(&db).await, (Some(12500), Some(13000)));
+    assert_eq!(trade_at(&db, 13500).await, 0);
+    assert_eq!(trail(&db).await, (Some(13000), Some(13500)));
+    // a dip that stays above the stop does not pull the trail down
+    assert_eq!(trade_at(&db, 13200).await, 0);
+    assert_eq!(trail(&db).await, (Some(13000), Some(13500)));
+
+    let bid = r#"{"symbol":"BBCA","side":"B","price":12800,"quantity":200,"expiry":"GTC"}"#;
+    db.send(BUYER, bid).await.expect("bid rests");
+    assert_eq!(trade_at(&db, 12900).await, 1);
+    let status: String =
+        sqlx::query_scalar("SELECT status FROM orders WHERE order_type = 'TRAILING_STOP'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(status, "TRIGGERED");
+    let sold: i64 =
+        sqlx::query_scalar("SELECT SUM(quantity) FROM trades WHERE user_id = $1 AND price = 12800")
+            .bind(SELLER)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(sold, 200);
+}
+
+#[tokio::test]
+async fn trailing_stop_with_both_trails_is_refused() {
+    let Some(db) = common::setup("trailing_both").await else {
+        return;
+    };
+    db.hold_shares(SELLER, "BBCA", 1000, 12000).await;
+
+    let both = r#"{"symbol":"BBCA","side":"S","quantity":200,"expiry":"GTC","order_type":"TRAILING_STOP","trail_amount":500,"trail_percent":2}"#;
+    let sent = db.send(SELLER, both).await;
+    assert!(
+        matches!(sent, Err(OrderError::InvalidTrail("trail_percent"))),
+        "{:?}",
+        sent
+    );
+    let armed: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE status = 'PENDING_TRIGGER'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(armed, 0);
+}